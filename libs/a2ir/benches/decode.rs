@@ -0,0 +1,39 @@
+//! Compares decode and decode_into over the words of the test executable.
+//! Run with `cargo bench -p a2ir`.
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use a2ir::aarch64_reader::{decode, decode_into, Inst};
+
+const NRO: &[u8] = include_bytes!("../../../test/hello-world.nro");
+const ROUNDS: usize = 200;
+
+fn main() {
+    let words: Vec<u32> = NRO
+        .chunks_exact(4)
+        .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+        .collect();
+    let total = (words.len() * ROUNDS) as f64;
+
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        for &binst in &words {
+            black_box(decode(black_box(binst)));
+        }
+    }
+    let by_value = start.elapsed();
+
+    let mut inst: Inst = decode(0);
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        for &binst in &words {
+            decode_into(black_box(binst), &mut inst);
+            black_box(&inst);
+        }
+    }
+    let in_place = start.elapsed();
+
+    println!("decode:      {:6.2} ns/inst", by_value.as_nanos() as f64 / total);
+    println!("decode_into: {:6.2} ns/inst", in_place.as_nanos() as f64 / total);
+}
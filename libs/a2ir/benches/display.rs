@@ -0,0 +1,60 @@
+//! Measures rendering the words of the test executable as text and as JSON
+//! lines, in time and heap allocations per instruction.
+//! Run with `cargo bench -p a2ir --bench display`.
+#![allow(clippy::needless_return)]
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+use a2ir::aarch64_display::decode_to_jsonl;
+use a2ir::aarch64_reader::decode;
+
+const NRO: &[u8] = include_bytes!("../../../test/hello-world.nro");
+const ROUNDS: usize = 50;
+
+/// The system allocator, counting the allocations.
+struct Counting;
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        return System.alloc(layout);
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// Runs render ROUNDS times and prints its time and allocations per
+/// instruction.
+fn measure(name: &str, render: impl Fn()) {
+    let total = (NRO.len() / 4 * ROUNDS) as f64;
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        render();
+    }
+    let elapsed = start.elapsed();
+    let allocated = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+    println!("{:8} {:6.2} ns/inst {:6.3} allocations/inst", name, elapsed.as_nanos() as f64 / total, allocated as f64 / total);
+}
+
+fn main() {
+    measure("display", || {
+        let mut out = io::sink();
+        for word in NRO.chunks_exact(4) {
+            let inst = decode(u32::from_le_bytes(word.try_into().unwrap()));
+            writeln!(out, "{}", black_box(&inst)).unwrap();
+        }
+    });
+    measure("jsonl", || decode_to_jsonl(black_box(NRO), 0, io::sink()).unwrap());
+}
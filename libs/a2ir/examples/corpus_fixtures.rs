@@ -0,0 +1,116 @@
+//! Turns assembly lines into lines for tests/corpus.txt, assembling them with
+//! the GNU toolchain so that new corpus entries need no hand-computed hex.
+//!
+//! cargo run -p a2ir --features fixtures --example corpus_fixtures -- lines.s
+//!
+//! Reads one instruction per line from the given files, or stdin, and prints
+//! `hexword  mnemonic  operands` for each. Blank lines and # comments are
+//! passed through. The text is copied from the input, so write it the way
+//! llvm-mc disassembles the instruction. AARCH64_AS and AARCH64_OBJCOPY
+//! override the tools, aarch64-linux-gnu-as and aarch64-linux-gnu-objcopy by
+//! default. Without an assembler, nothing is printed.
+#![allow(clippy::needless_return)]
+
+use std::io::{self, Read};
+use std::path::Path;
+use std::process::{Command, ExitCode};
+use std::{env, fs};
+
+struct Toolchain {
+    assembler: String,
+    objcopy: String,
+}
+
+impl Toolchain {
+    fn from_env() -> Toolchain {
+        let tool = |var: &str, default: &str| env::var(var).unwrap_or_else(|_| default.to_string());
+        return Toolchain {
+            assembler: tool("AARCH64_AS", "aarch64-linux-gnu-as"),
+            objcopy: tool("AARCH64_OBJCOPY", "aarch64-linux-gnu-objcopy"),
+        };
+    }
+
+    /// Assembles a single instruction in dir and returns its word, taken
+    /// from the .text section of the object file.
+    fn assemble(&self, line: &str, dir: &Path) -> Result<u32, String> {
+        let (source, object, binary) = (dir.join("line.s"), dir.join("line.o"), dir.join("line.bin"));
+        fs::write(&source, format!(".text\n{}\n", line)).map_err(|e| e.to_string())?;
+        run(Command::new(&self.assembler).arg("-o").arg(&object).arg(&source))?;
+        run(Command::new(&self.objcopy).args(["-O", "binary", "-j", ".text"]).arg(&object).arg(&binary))?;
+
+        let bytes = fs::read(&binary).map_err(|e| e.to_string())?;
+        let word: [u8; 4] = bytes.as_slice().try_into().map_err(|_| format!("{} bytes of code instead of one instruction", bytes.len()))?;
+        return Ok(u32::from_le_bytes(word));
+    }
+}
+
+fn run(command: &mut Command) -> Result<(), String> {
+    let output = command.output().map_err(|e| format!("{}: {}", command.get_program().to_string_lossy(), e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    return Ok(());
+}
+
+/// The corpus line of an instruction: the word, then the mnemonic padded to
+/// a column and the operands.
+fn corpus_line(word: u32, line: &str) -> String {
+    let text = line.split_whitespace().collect::<Vec<_>>().join(" ");
+    return match text.split_once(' ') {
+        Some((mnemonic, operands)) => format!("{:08x}  {:<8}{}", word, mnemonic, operands),
+        None => format!("{:08x}  {}", word, text),
+    };
+}
+
+fn read_input() -> io::Result<String> {
+    let mut input = String::new();
+    let files: Vec<String> = env::args().skip(1).collect();
+    if files.is_empty() {
+        io::stdin().read_to_string(&mut input)?;
+    }
+    for file in files {
+        input.push_str(&fs::read_to_string(file)?);
+        input.push('\n');
+    }
+    return Ok(input);
+}
+
+fn main() -> ExitCode {
+    let toolchain = Toolchain::from_env();
+    if Command::new(&toolchain.assembler).arg("--version").output().is_err() {
+        eprintln!("{} not found, skipping", toolchain.assembler);
+        return ExitCode::SUCCESS;
+    }
+
+    let input = match read_input() {
+        Ok(input) => input,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let dir = env::temp_dir().join(format!("a2ir-fixtures-{}", std::process::id()));
+    if let Err(e) = fs::create_dir_all(&dir) {
+        eprintln!("{}: {}", dir.display(), e);
+        return ExitCode::FAILURE;
+    }
+
+    let mut failed = false;
+    for (number, line) in input.lines().enumerate() {
+        let text = line.trim();
+        if text.is_empty() || text.starts_with('#') {
+            println!("{}", line);
+            continue;
+        }
+        match toolchain.assemble(text, &dir) {
+            Ok(word) => println!("{}", corpus_line(word, text)),
+            Err(e) => {
+                eprintln!("line {}: {}: {}", number + 1, text, e);
+                failed = true;
+            }
+        }
+    }
+
+    let _ = fs::remove_dir_all(&dir);
+    return if failed { ExitCode::FAILURE } else { ExitCode::SUCCESS };
+}
@@ -0,0 +1,19 @@
+//! Prints the pseudo-code of every opcode the emulator models, one
+//! `OP: semantics` line each, followed by the number of opcodes without
+//! modeled semantics.
+//!
+//! cargo run -p a2ir --features emu --example op_semantics
+
+use a2ir::aarch64_emu::op_semantics;
+use a2ir::aarch64_reader::all_ops;
+
+fn main() {
+    let mut unmodeled = 0;
+    for op in all_ops() {
+        match op_semantics(&op) {
+            Some(text) => println!("{:?}: {}", op, text),
+            None => unmodeled += 1,
+        }
+    }
+    println!("# {} opcodes without semantics", unmodeled);
+}
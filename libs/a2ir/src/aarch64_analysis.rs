@@ -0,0 +1,1379 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use crate::aarch64_reader::AddrMode::{AM_LITERAL, AM_OFF_EXT, AM_OFF_IMM, AM_OFF_REG, AM_POST, AM_PRE};
+use crate::aarch64_reader::ExtendType::{SXTW, UXTW, UXTX};
+use crate::aarch64_reader::Op::*;
+use crate::aarch64_reader::FlagMasks::{SET_FLAGS, W32};
+use crate::aarch64_reader::Registries::{STACK_POINTER, ZERO_REG};
+use crate::aarch64_reader::{decode, Decoder, fad_get_addrmode, fad_get_cond, fad_get_mem_extend, fad_get_vec_arrangement, group_of, op_in, vec_elem_bits, vec_is_upper_half, Group, Inst, InstIter, LdstOrder, Op};
+
+/// Resolves the PC-relative target of an instruction at address pc: the
+/// destination of direct branches, the label of ADR, the page of ADRP, and
+/// the address literal loads read from.
+pub fn branch_target(inst: &Inst, pc: u64) -> Option<u64> {
+    return match inst.op {
+        A64_B | A64_BL | A64_BCOND | A64_CBZ | A64_CBNZ => Some(pc.wrapping_add(inst.offset as u64)),
+        A64_TBZ | A64_TBNZ => Some(pc.wrapping_add(inst.tbz.offset as i64 as u64)),
+        A64_ADR => Some(pc.wrapping_add(inst.offset as u64)),
+        A64_ADRP => Some((pc & !0xFFF).wrapping_add(inst.offset as u64)),
+        A64_LDR | A64_LDR_FP | A64_PRFM if fad_get_addrmode(inst.flags) == AM_LITERAL => {
+            Some(pc.wrapping_add(inst.offset as u64))
+        }
+        _ => None,
+    };
+}
+
+/// Fetches the constant a literal load (LDR Wt, LDR Xt, LDRSW) at address pc
+/// reads from its literal pool. The image holds the bytes mapped starting at
+/// image_base; None is returned if the pool lies outside of it. LDRSW yields
+/// the sign-extended value, as loaded into the register.
+pub fn literal_value(inst: &Inst, pc: u64, image: &[u8], image_base: u64) -> Option<u64> {
+    if inst.op != A64_LDR {
+        return None;
+    }
+
+    let target = branch_target(inst, pc)?;
+    let start = target.checked_sub(image_base)? as usize;
+    let read = |len: usize| image.get(start..start.checked_add(len)?);
+
+    return match fad_get_mem_extend(inst.flags) {
+        UXTW => Some(u32::from_le_bytes(read(4)?.try_into().unwrap()) as u64),
+        SXTW => Some(i32::from_le_bytes(read(4)?.try_into().unwrap()) as i64 as u64),
+        UXTX => Some(u64::from_le_bytes(read(8)?.try_into().unwrap())),
+        _ => None,
+    };
+}
+
+/// Resolves the full address formed by the ADRP + ADD / ADRP + LDR idiom:
+///
+/// ```text
+/// adrp x0, page         ; x0 ← (PC & ~0xFFF) + page
+/// add  x0, x0, #lo12    ; x0 ← x0 + lo12
+/// ldr  x1, [x0, #lo12]  ; x1 ← *(x0 + lo12)
+/// ```
+///
+/// The follow-up instruction must use the ADRP's destination register as its
+/// (base) operand Rn, otherwise the two are unrelated and None is returned.
+/// For loads and stores, only the unsigned immediate offset form can carry the
+/// low 12 bits of the address.
+pub fn resolve_adrp_pair(adrp: &Inst, adrp_pc: u64, follow: &Inst) -> Option<u64> {
+    if adrp.op != A64_ADRP || follow.rn != adrp.rd {
+        return None;
+    }
+
+    let page = (adrp_pc & !0xFFF).wrapping_add(adrp.offset as u64);
+    return match follow.op {
+        A64_ADD_IMM => Some(page.wrapping_add(follow.imm)),
+        A64_LDR | A64_STR | A64_LDR_FP | A64_STR_FP if fad_get_addrmode(follow.flags) == AM_OFF_IMM => {
+            Some(page.wrapping_add(follow.offset as u64))
+        }
+        _ => None,
+    };
+}
+
+/// The source address of a literal pool load through ADRP: the ADRP page
+/// plus the LDR's scaled unsigned immediate offset. None unless ldr is an
+/// LDR (GPR or SIMD&FP) with that addressing mode based on the ADRP's Rd.
+pub fn adrp_ldr_target(adrp: &Inst, adrp_pc: u64, ldr: &Inst) -> Option<u64> {
+    if !matches!(ldr.op, A64_LDR | A64_LDR_FP) || fad_get_addrmode(ldr.flags) != AM_OFF_IMM {
+        return None;
+    }
+    return resolve_adrp_pair(adrp, adrp_pc, ldr);
+}
+
+/// How an instruction passes control on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ControlFlow {
+    /// Continues with the next instruction.
+    Sequential,
+    /// B -- always jumps to branch_target.
+    Jump,
+    /// B.cond, CBZ, CBNZ, TBZ, TBNZ -- branch_target or the next instruction.
+    ConditionalJump,
+    /// BL -- calls branch_target, which returns to the next instruction.
+    Call,
+    /// BR -- jumps to the address in Inst.rn.
+    IndirectJump,
+    /// BLR -- calls the address in Inst.rn.
+    IndirectCall,
+    /// RET -- returns to the address in Inst.rn (usually X30).
+    Return,
+}
+
+pub fn control_flow(inst: &Inst) -> ControlFlow {
+    return match inst.op {
+        A64_B => ControlFlow::Jump,
+        A64_BCOND | A64_CBZ | A64_CBNZ | A64_TBZ | A64_TBNZ => ControlFlow::ConditionalJump,
+        A64_BL => ControlFlow::Call,
+        A64_BR => ControlFlow::IndirectJump,
+        A64_BLR => ControlFlow::IndirectCall,
+        A64_RET => ControlFlow::Return,
+        _ => ControlFlow::Sequential,
+    };
+}
+
+/// The targets of the direct branches and calls in code, which starts at
+/// address base, and the addresses formed by ADR and ADRP: the seed set for
+/// recursive-descent disassembly. Targets outside of code are included.
+pub fn branch_targets(code: &[u8], base: u64) -> BTreeSet<u64> {
+    let decoder = Decoder { base_pc: base, ..Decoder::default() };
+    let mut targets = BTreeSet::new();
+    for (pc, inst) in decoder.decode_buffer(code) {
+        let direct = matches!(control_flow(&inst), ControlFlow::Jump | ControlFlow::ConditionalJump | ControlFlow::Call);
+        if direct || matches!(inst.op, A64_ADR | A64_ADRP) {
+            targets.extend(branch_target(&inst, pc));
+        }
+    }
+    return targets;
+}
+
+/// The PC-relative data references in code, which starts at address base:
+/// an (instruction address, referenced address) pair for each ADR, ADRP and
+/// literal load or prefetch, in address order. ADRP references the page,
+/// the others their exact target; see resolve_adrp_pair for the full address
+/// of an ADRP idiom. The complement of branch_targets for data.
+pub fn data_refs(code: &[u8], base: u64) -> Vec<(u64, u64)> {
+    let decoder = Decoder { base_pc: base, ..Decoder::default() };
+    let mut refs = Vec::new();
+    for (pc, inst) in decoder.decode_buffer(code) {
+        if control_flow(&inst) == ControlFlow::Sequential {
+            refs.extend(branch_target(&inst, pc).map(|target| (pc, target)));
+        }
+    }
+    return refs;
+}
+
+/// Decodes the instructions of code, which starts at address base, that are
+/// reachable from the entry points: direct branches and calls are followed
+/// within code, returns and indirect branches end a path, as do words that
+/// do not decode, which are likely data and left out of the result.
+pub fn disassemble_recursive(code: &[u8], base: u64, entries: &[u64]) -> BTreeMap<u64, Inst> {
+    let mut insts = BTreeMap::new();
+    let mut pending = entries.to_vec();
+    while let Some(pc) = pending.pop() {
+        let offset = pc.wrapping_sub(base);
+        if pc % 4 != 0 || offset >= code.len() as u64 || code.len() - (offset as usize) < 4 || insts.contains_key(&pc) {
+            continue;
+        }
+        let offset = offset as usize;
+        let inst = decode(u32::from_le_bytes(code[offset..offset + 4].try_into().unwrap()));
+        if matches!(inst.op, A64_UNKNOWN | A64_ERROR) {
+            continue;
+        }
+
+        let flow = control_flow(&inst);
+        if matches!(flow, ControlFlow::Jump | ControlFlow::ConditionalJump | ControlFlow::Call) {
+            pending.extend(branch_target(&inst, pc));
+        }
+        if !matches!(flow, ControlFlow::Jump | ControlFlow::IndirectJump | ControlFlow::Return) {
+            pending.push(pc.wrapping_add(4));
+        }
+        insts.insert(pc, inst);
+    }
+    return insts;
+}
+
+/// Decodes the straight-line code at the start of code, which starts at
+/// address base, up to and including the first instruction that passes
+/// control on other than sequentially, calls included. Returns the
+/// instructions with their addresses and how the last one continues:
+/// ControlFlow::Sequential if the block runs into the end of code or into a
+/// word that does not decode, which is left out like in
+/// disassemble_recursive.
+pub fn decode_block(code: &[u8], base: u64) -> (Vec<(u64, Inst)>, ControlFlow) {
+    let decoder = Decoder { base_pc: base, ..Decoder::default() };
+    let mut insts = Vec::new();
+    for (pc, inst) in decoder.decode_buffer(code) {
+        if matches!(inst.op, A64_UNKNOWN | A64_ERROR) {
+            break;
+        }
+        let flow = control_flow(&inst);
+        insts.push((pc, inst));
+        if flow != ControlFlow::Sequential {
+            return (insts, flow);
+        }
+    }
+    return (insts, ControlFlow::Sequential);
+}
+
+/// Is op one of the Loads and Stores, whose flags hold an addressing mode?
+fn is_load_store(op: &Op) -> bool {
+    return op_in(op, A64_LD1_MULT, A64_CASP);
+}
+
+/// Does the instruction read or write SP? Register 31 decodes to
+/// STACK_POINTER only where it means SP, so this is a plain register check.
+pub fn uses_sp(inst: &Inst) -> bool {
+    return [inst.rd, inst.rn, inst.rm].contains(&STACK_POINTER);
+}
+
+/// Does the instruction write SP, either as its destination register or by
+/// the base register writeback of a pre- or post-indexed load or store?
+pub fn writes_sp(inst: &Inst) -> bool {
+    if is_load_store(&inst.op) {
+        let writeback = matches!(fad_get_addrmode(inst.flags), AM_PRE | AM_POST);
+        return writeback && inst.rn == STACK_POINTER;
+    }
+    return inst.rd == STACK_POINTER;
+}
+
+/// How a load or store updates its base register.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Writeback {
+    None,
+    /// [base, #imm]! -- accesses base + offset and writes that back to base
+    Pre,
+    /// [base], #imm -- accesses base and writes base + offset back to it
+    Post,
+}
+
+/// The index register of a register offset address, Inst.rm: extended by
+/// extend (ExtendType::*, UXTX for an Xm without extension) and shifted left
+/// by shift, both from Inst.extend.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MemIndex {
+    pub reg: u8,
+    pub extend: u8,
+    pub shift: u32,
+}
+
+/// The address of a load or store: base + index + offset, with base None
+/// for the PC-relative literal loads.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MemOperand {
+    pub base: Option<u8>,
+    pub index: Option<MemIndex>,
+    pub offset: i64,
+    pub writeback: Writeback,
+}
+
+/// The address expression of a load or store, normalizing the AddrMode of
+/// Inst.flags. None for other instructions.
+pub fn mem_operand(inst: &Inst) -> Option<MemOperand> {
+    if !is_load_store(&inst.op) {
+        return None;
+    }
+
+    let mut mem = MemOperand { base: Some(inst.rn), index: None, offset: inst.offset, writeback: Writeback::None };
+    match fad_get_addrmode(inst.flags) {
+        AM_OFF_REG => mem.index = Some(MemIndex { reg: inst.rm, extend: UXTX, shift: inst.extend.lsl }),
+        AM_OFF_EXT => mem.index = Some(MemIndex { reg: inst.rm, extend: inst.extend.typ as u8, shift: inst.extend.lsl }),
+        AM_PRE => mem.writeback = Writeback::Pre,
+        AM_POST => mem.writeback = Writeback::Post,
+        AM_LITERAL => mem.base = None,
+        _ => {} // AM_SIMPLE, AM_OFF_IMM
+    }
+    return Some(mem);
+}
+
+/// The operand fields rd, rn, rm and ra that an instruction reads as general
+/// purpose registers. Unused fields are zero, which is also X0, so this goes
+/// by the opcode rather than the field values.
+fn gpr_reads(inst: &Inst) -> [bool; 4] {
+    let op = &inst.op;
+    if is_load_store(op) {
+        let base = fad_get_addrmode(inst.flags) != AM_LITERAL;
+        let index = matches!(fad_get_addrmode(inst.flags), AM_OFF_REG | AM_OFF_EXT);
+        let store = matches!(op, A64_STR | A64_STP | A64_STNP | A64_STXR | A64_STXP | A64_CAS | A64_CASP);
+        return [store, base, index, false];
+    }
+    if op_in(op, A64_FCVT_GPR, A64_BFMMLA) {
+        // only the moves and conversions from a GPR have one as source
+        return [false, operand_widths(inst).rn != RegWidth::V, false, false];
+    }
+    return match op {
+        A64_ADR | A64_ADRP | A64_MOV_IMM => [false; 4],
+        A64_MOVK | A64_BFM | A64_BFC | A64_BFI | A64_BFXIL => [true, true, false, false],
+        A64_EXTR => [false, true, true, false],
+        A64_BR | A64_BLR | A64_RET => [false, true, false, false],
+        // the tested register, or the source of MSR and SYS, is in Inst.rd
+        A64_CBZ | A64_CBNZ | A64_TBZ | A64_TBNZ | A64_MSR_REG | A64_SYS => [true, false, false, false],
+        _ if op_in(op, A64_BCOND, A64_TBNZ) => [false; 4],
+        // PAC*, AUT* and XPAC* modify Inst.rd, with the modifier in Inst.rn
+        _ if op_in(op, A64_PACIA, A64_AUTDB) => [true, true, false, false],
+        A64_XPACI | A64_XPACD => [true, false, false, false],
+        _ if op_in(op, A64_RBIT, A64_CLS) => [false, true, false, false],
+        A64_CCMN_IMM | A64_CCMP_IMM => [false, true, false, false],
+        _ if op_in(op, A64_MADD, A64_UMULH) => [false, true, true, true],
+        _ if op_in(op, A64_UDIV, A64_UMULH) => [false, true, true, false],
+        _ => [false, true, false, false], // data processing (immediate)
+    };
+}
+
+/// Does the instruction read the general purpose register reg (or SP as
+/// STACK_POINTER)? XZR is never read, SIMD&FP operands are not counted.
+pub fn reads_register(inst: &Inst, reg: u8) -> bool {
+    if reg == ZERO_REG || matches!(inst.op, A64_UNKNOWN | A64_ERROR) {
+        return false;
+    }
+    let [rd, rn, rm, ra] = gpr_reads(inst);
+    let pair = matches!(inst.op, A64_STP | A64_STNP | A64_STXP);
+    let registers = [
+        (rd, inst.rd),
+        (rn, inst.rn),
+        (rm, inst.rm),
+        (ra, inst.ra),
+        (pair, inst.rt2),
+        (inst.op == A64_CASP, inst.rd + 1),
+        // the atomics and CAS read Inst.rs, CASP the pair starting there
+        (op_in(&inst.op, A64_LDADD, A64_CASP), inst.rs),
+        (inst.op == A64_CASP, inst.rs + 1),
+    ];
+    return registers.iter().any(|&(read, r)| read && r == reg);
+}
+
+/// Does the instruction write the general purpose register reg (or SP as
+/// STACK_POINTER)? This includes the implicit X30 of BL and BLR and the
+/// base register writeback of loads and stores, but not NZCV, see sets_nzcv.
+/// Writes to XZR are discarded and never count.
+pub fn writes_register(inst: &Inst, reg: u8) -> bool {
+    let op = &inst.op;
+    if reg == ZERO_REG {
+        return false;
+    }
+    if matches!(op, A64_BL | A64_BLR) {
+        return reg == 30;
+    }
+    if is_load_store(op) {
+        let writeback = matches!(fad_get_addrmode(inst.flags), AM_PRE | AM_POST);
+        return (writeback && inst.rn == reg) || match op {
+            A64_LDR | A64_LDAPR | A64_LDXR => inst.rd == reg,
+            A64_LDP | A64_LDNP | A64_LDXP => inst.rd == reg || inst.rt2 == reg,
+            // CAS loads into Inst.rs, STXR and STXP write their status there
+            A64_CAS | A64_STXR | A64_STXP => inst.rs == reg,
+            A64_CASP => inst.rs == reg || inst.rs + 1 == reg,
+            _ => op_in(op, A64_LDADD, A64_SWP) && inst.rd == reg,
+        };
+    }
+    let no_destination = match op {
+        A64_UNKNOWN | A64_ERROR | A64_UDF => true,
+        A64_MRS | A64_SYSL => false,
+        A64_CMN_IMM | A64_CMP_IMM | A64_TST_IMM | A64_TST_SHIFTED | A64_CMN_SHIFTED | A64_CMP_SHIFTED | A64_CMN_EXT | A64_CMP_EXT => true,
+        A64_RMIF | A64_SETF8 | A64_SETF16 => true,
+        _ => op_in(op, A64_BCOND, A64_TBNZ) || op_in(op, A64_CCMN_REG, A64_CCMP_IMM),
+    };
+    return !no_destination && operand_widths(inst).rd != RegWidth::V && inst.rd == reg;
+}
+
+/// Does the instruction also read the register it writes to Inst.rd? These
+/// are the accumulating instructions (MLA, ABAL, FMLA, ...), the ones that
+/// replace only part of it (MOVK, BFI, SLI, INS, BSL, ...) and the "2"
+/// variants of the narrowing instructions, which keep its lower half. For a
+/// dataflow analysis, Inst.rd of these is both a use and a definition.
+pub fn reads_destination(inst: &Inst) -> bool {
+    let op = &inst.op;
+    return match op {
+        A64_MOVK | A64_BFM | A64_BFC | A64_BFI | A64_BFXIL => true,
+        A64_XPACI | A64_XPACD => true,
+        _ if op_in(op, A64_PACIA, A64_AUTDB) => true,
+        A64_ABA | A64_ABAL | A64_ADALP | A64_SRA | A64_SLI | A64_SRI | A64_BSL | A64_BIT | A64_BIF => true,
+        A64_INS_ELEM | A64_INS_GPR | A64_TBX => true,
+        A64_DOT_ELEM | A64_DOT_VEC => true,
+        _ if op_in(op, A64_FMLA_ELEM, A64_FMLSL2_VEC) => true, // FMLA, FMLAL, FCMLA, FMLS, FMLSL
+        _ if op_in(op, A64_MLA_ELEM, A64_MLSL_VEC) => true,
+        _ if op_in(op, A64_SQDMLAL_ELEM, A64_SQDMLSL_VEC) || op_in(op, A64_SQRDMLAH_ELEM, A64_SQRDMLSH_VEC) => true,
+        _ if op_in(op, A64_BFDOT_ELEM, A64_BFMMLA) => true,
+        A64_AESE | A64_AESD => true, // Vd ⊕ Vn
+        _ if op_in(op, A64_SHA1C, A64_SHA256SU1) => *op != A64_SHA1H, // hash and schedule updates
+        A64_XTN | A64_QXTN | A64_SQXTUN | A64_SHRN | A64_QSHRN | A64_SQSHRUN | A64_ADDHN | A64_SUBHN | A64_FCVTN | A64_FCVTXN | A64_BFCVTN => {
+            vec_is_upper_half(fad_get_vec_arrangement(inst.flags))
+        }
+        _ => false,
+    };
+}
+
+/// Are a and b the same instruction up to their register numbers? Opcode,
+/// flags, immediates and all other operands must match, while every register
+/// field is a wildcard. ADD x0, x1, #16 and ADD x5, sp, #16 have the same
+/// shape, ADD x0, x1, #32 has not.
+pub fn same_shape(a: &Inst, b: &Inst) -> bool {
+    let wildcard = |inst: &Inst| Inst {
+        rd: 0,
+        rn: 0,
+        rm: 0,
+        rt2: 0,
+        rs: 0,
+        ra: 0,
+        ldst_order: LdstOrder { rs: 0, ..inst.ldst_order.clone() },
+        ..inst.clone()
+    };
+    return wildcard(a) == wildcard(b);
+}
+
+/// The bits of Inst.flags that carry information for the opcode:
+///
+/// - loads and stores: W32, the memory extension and the addressing mode;
+/// - SIMD&FP: all, the precision or arrangement and the SIMD_* flags;
+/// - B.cond: the condition;
+/// - other branches and system instructions: W32 for CBZ, CBNZ, TBZ, TBNZ;
+/// - integer data processing: W32 and SET_FLAGS.
+fn live_flags(op: &Op) -> u8 {
+    if is_load_store(op) {
+        return 0b11111101;
+    }
+    if op_in(op, A64_FCVT_GPR, A64_BFMMLA) {
+        return 0xFF;
+    }
+    return match op {
+        A64_BCOND => 0xF0,
+        A64_CCMN_REG | A64_CCMP_REG | A64_CCMN_IMM | A64_CCMP_IMM => 0xF0 | W32,
+        A64_CBZ | A64_CBNZ | A64_TBZ | A64_TBNZ => W32,
+        _ if op_in(op, A64_BCOND, A64_TBNZ) => 0,
+        _ => W32 | SET_FLAGS,
+    };
+}
+
+/// Are a and b the same instruction, ignoring the bits of Inst.flags that
+/// their opcode does not use? Decoders and hand-built instructions may leave
+/// different values there, which Inst's == compares.
+pub fn semantically_eq(a: &Inst, b: &Inst) -> bool {
+    let live = |inst: &Inst| Inst { flags: inst.flags & live_flags(&inst.op), ..inst.clone() };
+    return live(a) == live(b);
+}
+
+/// Does the instruction write any of the NZCV condition flags? For RMIF and
+/// SETF8/SETF16 only some of them change, see their Op documentation.
+pub fn sets_nzcv(inst: &Inst) -> bool {
+    return match inst.op {
+        A64_CMN_IMM | A64_CMP_IMM | A64_TST_IMM | A64_TST_SHIFTED | A64_CMN_SHIFTED | A64_CMP_SHIFTED | A64_CMN_EXT | A64_CMP_EXT => true,
+        A64_RMIF | A64_SETF8 | A64_SETF16 | A64_CFINV | A64_XAFlag | A64_AXFlag => true,
+        A64_CCMN_REG | A64_CCMP_REG | A64_CCMN_IMM | A64_CCMP_IMM => true,
+        A64_FCMP_REG | A64_FCMP_ZERO | A64_FCMPE_REG | A64_FCMPE_ZERO | A64_FCCMP | A64_FCCMPE => true,
+        A64_ADD_IMM | A64_SUB_IMM | A64_AND_IMM | A64_AND_SHIFTED | A64_BIC | A64_ADD_SHIFTED | A64_SUB_SHIFTED | A64_NEG => {
+            inst.flags & SET_FLAGS != 0
+        }
+        A64_ADD_EXT | A64_SUB_EXT | A64_ADC | A64_SBC | A64_NGC => inst.flags & SET_FLAGS != 0,
+        _ => false,
+    };
+}
+
+/// The immediate operand of an instruction, typed by how the instruction
+/// interprets it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Immediate {
+    /// An amount or count: ADD #imm, shifts, MOVK's imm16, SVC #imm, lane index
+    Unsigned(u64),
+    /// A PC-relative or base register offset in bytes
+    Signed(i64),
+    /// A bit pattern rather than a number: logical immediates, MOVI
+    BitMask(u64),
+    Float(f64),
+    None,
+}
+
+/// The immediate of inst with its signedness, taken from Inst.imm,
+/// Inst.offset, Inst.fimm or an opcode-specific field. Instructions with no
+/// immediate or with several (bitfield moves, CCMP) yield None, as do loads
+/// and stores addressed by register.
+pub fn immediate(inst: &Inst) -> Immediate {
+    return match inst.op {
+        A64_ADD_IMM | A64_SUB_IMM | A64_CMN_IMM | A64_CMP_IMM | A64_MOV_IMM => Immediate::Unsigned(inst.imm),
+        A64_LSL_IMM | A64_LSR_IMM | A64_ASR_IMM | A64_ROR_IMM | A64_EXTR => Immediate::Unsigned(inst.imm),
+        A64_SVC | A64_HVC | A64_SMC | A64_BRK | A64_HLT | A64_DCPS1 | A64_DCPS2 | A64_DCPS3 | A64_HINT => {
+            Immediate::Unsigned(inst.imm)
+        }
+        A64_SHRN | A64_QSHRN | A64_SQSHRUN | A64_SHLL => Immediate::Unsigned(inst.imm),
+        A64_MOVK => Immediate::Unsigned(inst.movk.imm16 as u64),
+        A64_AND_IMM | A64_ORR_IMM | A64_EOR_IMM | A64_TST_IMM | A64_MOVI => Immediate::BitMask(inst.imm),
+        A64_FMOV_IMM | A64_FMOV_VEC => Immediate::Float(inst.fimm),
+        A64_B | A64_BL | A64_BCOND | A64_CBZ | A64_CBNZ | A64_ADR | A64_ADRP => Immediate::Signed(inst.offset),
+        A64_TBZ | A64_TBNZ => Immediate::Signed(inst.tbz.offset as i64),
+        _ if is_load_store(&inst.op) => match fad_get_addrmode(inst.flags) {
+            AM_LITERAL | AM_OFF_IMM | AM_PRE | AM_POST => Immediate::Signed(inst.offset),
+            _ => Immediate::None,
+        },
+        _ => Immediate::None,
+    };
+}
+
+/// A subset of the N, Z, C and V condition flags, using their bit positions
+/// in NZCV (N is bit 3, V is bit 0). Combine them with `|`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NzcvMask(pub u8);
+
+impl NzcvMask {
+    pub const NONE: NzcvMask = NzcvMask(0);
+    pub const N: NzcvMask = NzcvMask(0b1000);
+    pub const Z: NzcvMask = NzcvMask(0b0100);
+    pub const C: NzcvMask = NzcvMask(0b0010);
+    pub const V: NzcvMask = NzcvMask(0b0001);
+    pub const ALL: NzcvMask = NzcvMask(0b1111);
+
+    /// Are all flags of other in the mask?
+    pub fn contains(self, other: NzcvMask) -> bool {
+        return self.0 & other.0 == other.0;
+    }
+
+    pub fn is_empty(self) -> bool {
+        return self.0 == 0;
+    }
+}
+
+impl std::ops::BitOr for NzcvMask {
+    type Output = NzcvMask;
+
+    fn bitor(self, rhs: NzcvMask) -> NzcvMask {
+        return NzcvMask(self.0 | rhs.0);
+    }
+}
+
+/// The flags a condition code (Cond::COND_*) tests. The inverting LSB does
+/// not change them; AL and NV test nothing.
+pub fn cond_dependency(cond: u8) -> NzcvMask {
+    return match (cond & 0b1111) >> 1 {
+        0b000 => NzcvMask::Z,                               // EQ, NE
+        0b001 => NzcvMask::C,                               // CS, CC
+        0b010 => NzcvMask::N,                               // MI, PL
+        0b011 => NzcvMask::V,                               // VS, VC
+        0b100 => NzcvMask::C | NzcvMask::Z,                 // HI, LS
+        0b101 => NzcvMask::N | NzcvMask::V,                 // GE, LT
+        0b110 => NzcvMask::N | NzcvMask::Z | NzcvMask::V,   // GT, LE
+        _ => NzcvMask::NONE,                                // AL, NV
+    };
+}
+
+/// ConditionHolds of the A64 pseudocode: does cond hold for the flags nzcv,
+/// given with NzcvMask's bit positions? AL and NV always hold.
+pub fn condition_holds(cond: u8, nzcv: u8) -> bool {
+    let flag = |mask: NzcvMask| nzcv & mask.0 != 0;
+    let (n, z, c, v) = (flag(NzcvMask::N), flag(NzcvMask::Z), flag(NzcvMask::C), flag(NzcvMask::V));
+    let result = match (cond & 0b1111) >> 1 {
+        0b000 => z,
+        0b001 => c,
+        0b010 => n,
+        0b011 => v,
+        0b100 => c && !z,
+        0b101 => n == v,
+        0b110 => n == v && !z,
+        _ => true,
+    };
+    return if cond & 1 == 1 && cond & 0b1111 != 0b1111 { !result } else { result };
+}
+
+/// The condition flags the instruction's result depends on. For conditional
+/// instructions these are the flags tested by Inst.flags.cond; the carry
+/// arithmetic (ADC, SBC, NGC) and CFINV read C, and the flag format
+/// conversions AXFLAG/XAFLAG all four. NONE for everything else.
+pub fn nzcv_dependency(inst: &Inst) -> NzcvMask {
+    return match inst.op {
+        A64_BCOND | A64_CCMN_REG | A64_CCMP_REG | A64_CCMN_IMM | A64_CCMP_IMM | A64_FCCMP | A64_FCCMPE | A64_FCSEL => {
+            cond_dependency(fad_get_cond(inst.flags))
+        }
+        A64_CSEL | A64_CSINC | A64_CINC | A64_CSET | A64_CSINV | A64_CINV | A64_CSETM | A64_CSNEG | A64_CNEG => {
+            cond_dependency(fad_get_cond(inst.flags))
+        }
+        A64_ADC | A64_SBC | A64_NGC | A64_CFINV => NzcvMask::C,
+        A64_AXFlag | A64_XAFlag => NzcvMask::ALL,
+        _ => NzcvMask::NONE,
+    };
+}
+
+/// The register holding the target of an indirect call (BLR) or branch (BR).
+/// RET is not included: it returns rather than transferring control to new
+/// code.
+pub fn call_target_reg(inst: &Inst) -> Option<u8> {
+    return match control_flow(inst) {
+        ControlFlow::IndirectCall | ControlFlow::IndirectJump => Some(inst.rn),
+        _ => None,
+    };
+}
+
+/// May the instruction be a tail call? Compilers emit indirect tail calls as
+/// BR, often through X16 or X17 (the intra-procedure-call registers). Whether
+/// it is one or a jump table dispatch within the function needs more context.
+pub fn is_tail_call_candidate(inst: &Inst) -> bool {
+    return control_flow(inst) == ControlFlow::IndirectJump;
+}
+
+/// The frame pointer and link register, saved as the frame record.
+const FP: u8 = 29;
+const LR: u8 = 30;
+
+/// Is inst a load or store pair of FP and LR, addressed relative to SP?
+fn is_frame_record_access(inst: &Inst, op: Op) -> bool {
+    return inst.op == op && (inst.rd, inst.rt2, inst.rn) == (FP, LR, STACK_POINTER);
+}
+
+/// Does the instruction only save or restore callee-saved registers, or
+/// adjust SP? These may surround the frame record handling.
+fn is_frame_bookkeeping(inst: &Inst) -> bool {
+    return match inst.op {
+        A64_STP | A64_LDP | A64_STP_FP | A64_LDP_FP | A64_STR | A64_LDR | A64_STR_FP | A64_LDR_FP => {
+            inst.rn == STACK_POINTER
+        }
+        A64_ADD_IMM | A64_SUB_IMM => inst.rd == STACK_POINTER && inst.rn == STACK_POINTER,
+        A64_PACIA | A64_PACIB | A64_AUTIA | A64_AUTIB => inst.rd == LR, // PACIASP, AUTIASP, ...
+        A64_HINT => true, // BTI
+        _ => false,
+    };
+}
+
+/// Heuristically recognizes the frame setup at the start of a function, as
+/// emitted by Clang and GCC, at the beginning of insts:
+///
+/// ```text
+/// stp x29, x30, [sp, #-N]!       sub sp, sp, #N
+/// mov x29, sp                    stp x29, x30, [sp, #M]
+///                                add x29, sp, #M
+/// ```
+///
+/// Hints (BTI, PACIASP), callee-saved register stores and SP adjustments may
+/// come in between. Functions that omit the frame pointer (leaf functions,
+/// -fomit-frame-pointer) are not recognized, and hand-written code can
+/// produce false positives.
+pub fn is_likely_prologue(insts: &[Inst]) -> bool {
+    let mut saved_record = false;
+    for inst in insts.iter().take(8) {
+        if !saved_record {
+            let mode = fad_get_addrmode(inst.flags);
+            if is_frame_record_access(inst, A64_STP) && ((mode == AM_PRE && inst.offset < 0) || mode == AM_OFF_IMM) {
+                saved_record = true;
+                continue;
+            }
+        } else if matches!(inst.op, A64_MOV_SP | A64_ADD_IMM) && inst.rd == FP && inst.rn == STACK_POINTER {
+            return true;
+        }
+        if !is_frame_bookkeeping(inst) {
+            return false;
+        }
+    }
+    return false;
+}
+
+/// Heuristically recognizes the frame teardown at the end of a function:
+/// insts ends with a RET, preceded by the reload of the frame record
+///
+/// ```text
+/// ldp x29, x30, [sp], #N         ldp x29, x30, [sp, #M]
+/// ret                            add sp, sp, #N
+///                                ret
+/// ```
+///
+/// with possibly other callee-saved register loads, SP adjustments and
+/// hints (AUTIASP) in between. Like is_likely_prologue, this is a heuristic
+/// for code following the usual frame pointer conventions.
+pub fn is_likely_epilogue(insts: &[Inst]) -> bool {
+    let Some((ret, rest)) = insts.split_last() else {
+        return false;
+    };
+    if control_flow(ret) != ControlFlow::Return || ret.rn != LR {
+        return false;
+    }
+    for inst in rest.iter().rev().take(8) {
+        if is_frame_record_access(inst, A64_LDP) {
+            return matches!(fad_get_addrmode(inst.flags), AM_POST | AM_OFF_IMM);
+        }
+        if !is_frame_bookkeeping(inst) {
+            return false;
+        }
+    }
+    return false;
+}
+
+/// Is the instruction alignment padding between functions: a NOP, or the
+/// zero word UDF #0 that linkers fill gaps with?
+pub fn is_padding(inst: &Inst) -> bool {
+    return matches!(inst.op, A64_HINT | A64_UDF) && inst.imm == 0;
+}
+
+/// The number of leading padding instructions of insts, see is_padding.
+pub fn padding_run_len(insts: &[Inst]) -> usize {
+    return insts.iter().take_while(|inst| is_padding(inst)).count();
+}
+
+/// The width of a register operand.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RegWidth {
+    /// 32-bit general purpose register Wn
+    W,
+    /// 64-bit general purpose register Xn
+    X,
+    /// SIMD&FP register (or an element of it), see the arrangement or
+    /// precision in Inst.flags
+    V,
+}
+
+/// The widths of the register operands Inst.rd, .rn, .rm and .ra. Slots the
+/// instruction does not use have the width of its other GPR operands.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OperandWidths {
+    pub rd: RegWidth,
+    pub rn: RegWidth,
+    pub rm: RegWidth,
+    pub ra: RegWidth,
+}
+
+/// The width of each register operand. Unlike the single W32 flag, this
+/// covers instructions mixing widths:
+///
+/// - the long multiplies, e.g. SMADDL Xd, Wn, Wm, Xa;
+/// - the extensions, e.g. SXTB Xd, Wn;
+/// - the moves between general purpose and SIMD&FP registers, e.g. UMOV Wd, Vn,
+///   and the conversions between integers and floating-point, e.g. SCVTF Sd, Xn.
+///
+/// The remaining SIMD&FP instructions only have V operands, all others use
+/// the width selected by W32 throughout.
+pub fn operand_widths(inst: &Inst) -> OperandWidths {
+    use RegWidth::{V, W, X};
+
+    let gpr = if inst.flags & W32 != 0 { W } else { X };
+    let (rd, rn, rm, ra) = match inst.op {
+        A64_SMADDL | A64_SMSUBL | A64_SMULL | A64_SMNEGL | A64_UMADDL | A64_UMSUBL | A64_UMULL | A64_UMNEGL => {
+            (X, W, W, X)
+        }
+        A64_EXTEND => (gpr, W, gpr, gpr),
+        A64_SMOV | A64_UMOV | A64_FMOV_VEC2GPR | A64_FMOV_TOP2GPR => (gpr, V, gpr, gpr),
+        A64_DUP_GPR | A64_INS_GPR | A64_FMOV_GPR2VEC | A64_FMOV_GPR2TOP => (V, gpr, gpr, gpr),
+        A64_FCVT_GPR => (gpr, V, gpr, gpr),
+        A64_CVTF => (V, gpr, gpr, gpr),
+        A64_FJCVTZS => (W, V, W, W),
+        _ if op_in(&inst.op, A64_FCVT_GPR, A64_BFMMLA) => (V, V, V, V),
+        _ => (gpr, gpr, gpr, gpr),
+    };
+    return OperandWidths { rd, rn, rm, ra };
+}
+
+/// The largest result of CLZ or CLS: the register width, 32 with W32 or
+/// 64, for CLZ of zero, one less for CLS, which never counts the sign bit.
+/// Other instructions yield the register width.
+pub fn clz_max(inst: &Inst) -> u32 {
+    let bits = if inst.flags & W32 != 0 { 32 } else { 64 };
+    return if inst.op == A64_CLS { bits - 1 } else { bits };
+}
+
+/// The bits of each destination element that SLI and SRI keep: the low
+/// Inst.imm bits for SLI, the high ones for SRI, while the shifted source
+/// overwrites the rest. SRI by the element size keeps everything. Zero for
+/// other instructions.
+pub fn sli_sri_mask(inst: &Inst) -> u64 {
+    let bits = vec_elem_bits(fad_get_vec_arrangement(inst.flags));
+    let ones = u64::MAX >> (64 - bits);
+    let shift = inst.imm as u32;
+    return match inst.op {
+        A64_SLI => ones.checked_shr(bits - shift).unwrap_or(0),
+        A64_SRI => !ones.checked_shr(shift).unwrap_or(0) & ones,
+        _ => 0,
+    };
+}
+
+/// What EXTR and ROR (immediate) compute, as a lifter emits it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExtractOp {
+    /// Rd ← Rn rotated right by amount, within a register of the given bits
+    Rotate { rn: u8, amount: u32, bits: u32 },
+    /// Rd ← bits lsb .. lsb + bits of the concatenation hi:lo, which is twice
+    /// as wide as the registers
+    ConcatExtract { hi: u8, lo: u8, lsb: u32, bits: u32 },
+}
+
+/// The operation of the Extract group: EXTR with Rn == Rm decodes to ROR_IMM
+/// and lifts to a rotate, any other EXTR to a concatenation and extract.
+pub fn extract_op(inst: &Inst) -> Option<ExtractOp> {
+    let bits = if inst.flags & W32 != 0 { 32 } else { 64 };
+    return match inst.op {
+        A64_ROR_IMM => Some(ExtractOp::Rotate { rn: inst.rn, amount: inst.imm as u32, bits }),
+        A64_EXTR => Some(ExtractOp::ConcatExtract { hi: inst.rn, lo: inst.rm, lsb: inst.imm as u32, bits }),
+        _ => None,
+    };
+}
+
+/// The rotate amount of ROR (immediate), below the register width of the
+/// instruction: a 32-bit ROR rotates within the W register. None for any
+/// other opcode, including the EXTR it is an alias of.
+pub fn ror_amount(inst: &Inst) -> Option<u32> {
+    return match inst.op {
+        A64_ROR_IMM => Some(inst.imm as u32),
+        _ => None,
+    };
+}
+
+/// May the instruction raise a synchronous exception (other than an access
+/// trap of a disabled feature, e.g. FP/SIMD under CPACR_EL1)? The
+/// classification is conservative:
+///
+/// - exception generating instructions (SVC, BRK, ...) and UDF always do,
+///   as does any instruction the decoder does not recognize;
+/// - all loads and stores (translation, permission and alignment faults);
+/// - floating-point arithmetic, compares and conversions, which trap if the
+///   corresponding FPCR exception trap is enabled (e.g. Inexact for FRINTX),
+///   while FMOV and FCSEL never raise FP exceptions;
+/// - system instructions and register moves, which may trap to a higher
+///   exception level;
+/// - AUT* with FEAT_FPAC, which fault on an authentication failure.
+///
+/// UDIV and SDIV do not trap: division by zero returns 0 on AArch64.
+pub fn may_trap(inst: &Inst) -> bool {
+    let op = &inst.op;
+    return match op {
+        A64_UNKNOWN | A64_ERROR | A64_UDF => true,
+        A64_SVC | A64_HVC | A64_SMC | A64_BRK | A64_HLT | A64_DCPS1 | A64_DCPS2 | A64_DCPS3 => true,
+        A64_MSR_IMM | A64_SYS | A64_SYSL | A64_MSR_REG | A64_MRS => true,
+        A64_AUTIA | A64_AUTIB | A64_AUTDA | A64_AUTDB => true,
+        A64_UDIV | A64_SDIV => false,
+        A64_FCSEL => false,
+        _ if op_in(op, A64_FMOV_VEC2GPR, A64_FMOV_VEC) => false,
+        _ => is_load_store(op) || op_in(op, A64_FCVT_GPR, A64_FMINNMV),
+    };
+}
+
+/// Opcode and encoding group frequencies of decoded code, e.g. to profile
+/// a binary in one pass.
+#[derive(Clone, Debug, Default)]
+pub struct DecodeStats {
+    pub by_op: HashMap<Op, u64>,
+    pub by_group: HashMap<Group, u64>,
+    /// Words that decoded to A64_UNKNOWN, also counted in by_op
+    pub unknown: u64,
+}
+
+impl DecodeStats {
+    /// Counts inst, decoded from word.
+    pub fn record(&mut self, inst: &Inst, word: u32) {
+        *self.by_op.entry(inst.op.clone()).or_insert(0) += 1;
+        *self.by_group.entry(group_of(word)).or_insert(0) += 1;
+        if inst.op == A64_UNKNOWN {
+            self.unknown += 1;
+        }
+    }
+
+    /// Decodes and counts the little-endian instruction words in code. A
+    /// truncated word at the end is not counted.
+    pub fn from_buffer(code: &[u8]) -> DecodeStats {
+        let mut stats = DecodeStats::default();
+        for bytes in code.chunks_exact(4) {
+            let word = u32::from_le_bytes(bytes.try_into().unwrap());
+            stats.record(&decode(word), word);
+        }
+        return stats;
+    }
+}
+
+/// A first look at a buffer of code: how much of it branches, calls,
+/// accesses memory or uses FP/SIMD and crypto, and which system calls and
+/// system registers it uses. Built by summarize.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Summary {
+    /// B, B.cond, CBZ, CBNZ, TBZ, TBNZ and BR
+    pub branches: u64,
+    /// BL and BLR
+    pub calls: u64,
+    pub returns: u64,
+    /// Loads, stores and atomics, including those of SIMD&FP registers
+    pub memory: u64,
+    /// Scalar floating-point and Advanced SIMD data processing, without
+    /// crypto
+    pub simd_fp: u64,
+    /// AES and SHA, including the SHA3 BCAX, EOR3, RAX1 and XAR
+    pub crypto: u64,
+    pub syscalls: u64,
+    /// The immediates of the SVCs
+    pub syscall_numbers: BTreeSet<u64>,
+    /// The system registers read by MRS or written by MSR, as the Inst.imm
+    /// of the instruction
+    pub sysregs: BTreeSet<u64>,
+}
+
+/// Is op an AES or SHA instruction? BCAX, EOR3, RAX1 and XAR count as SHA:
+/// they belong to FEAT_SHA3, although they are declared with the SIMD
+/// logical instructions.
+fn is_crypto(op: &Op) -> bool {
+    return op_in(op, A64_AESD, A64_SHA256SU1) || matches!(op, A64_BCAX | A64_EOR3 | A64_RAX1 | A64_XAR);
+}
+
+/// Decodes the little-endian instruction words in code and summarizes them,
+/// see Summary. A truncated word at the end is ignored.
+pub fn summarize(code: &[u8]) -> Summary {
+    let mut summary = Summary::default();
+    for inst in InstIter::new(code) {
+        let op = &inst.op;
+        match control_flow(&inst) {
+            ControlFlow::Jump | ControlFlow::ConditionalJump | ControlFlow::IndirectJump => summary.branches += 1,
+            ControlFlow::Call | ControlFlow::IndirectCall => summary.calls += 1,
+            ControlFlow::Return => summary.returns += 1,
+            ControlFlow::Sequential => {}
+        }
+        if is_load_store(op) {
+            summary.memory += 1;
+        } else if is_crypto(op) {
+            summary.crypto += 1;
+        } else if op_in(op, A64_FCVT_GPR, A64_BFMMLA) {
+            summary.simd_fp += 1;
+        }
+        match op {
+            A64_SVC => {
+                summary.syscalls += 1;
+                summary.syscall_numbers.insert(inst.imm);
+            }
+            A64_MRS | A64_MSR_REG => {
+                summary.sysregs.insert(inst.imm);
+            }
+            _ => {}
+        }
+    }
+    return summary;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aarch64_encoder::validate;
+    use crate::aarch64_reader::data_proc_imm;
+
+    use super::*;
+
+    const NRO: &[u8] = include_bytes!("../../../test/hello-world.nro");
+
+    fn code_of(words: &[u32]) -> Vec<u8> {
+        return words.iter().flat_map(|word| word.to_le_bytes()).collect();
+    }
+
+    fn word_at(bytes: &[u8], offset: usize) -> u32 {
+        return u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+    }
+
+    #[test]
+    fn adrp_add_pair_into_data_section() {
+        // adrp x0, #0x3e000; add x1, x0, #0x928 -- the .data segment of the
+        // test executable spans 0x3b000..0x3f000.
+        let adrp = data_proc_imm(word_at(NRO, 0x4b4));
+        let add = data_proc_imm(word_at(NRO, 0x4b8));
+        assert_eq!(resolve_adrp_pair(&adrp, 0x4b4, &add), Some(0x3e928));
+    }
+
+    #[test]
+    fn adrp_ldr_source_address() {
+        let adrp = decode(0xd0000080); // 0x1000: adrp x0, #0x12000 -- page 0x13000
+        assert_eq!(adrp_ldr_target(&adrp, 0x1000, &decode(0xf9400801)), Some(0x13010)); // ldr x1, [x0, #0x10]
+        assert_eq!(adrp_ldr_target(&adrp, 0x1000, &decode(0x3dc00802)), Some(0x13020)); // ldr q2, [x0, #0x20]
+        assert_eq!(adrp_ldr_target(&adrp, 0x1000, &decode(0xf9400841)), None); // ldr x1, [x2, #0x10]
+        assert_eq!(adrp_ldr_target(&adrp, 0x1000, &decode(0xf9000801)), None); // str x1, [x0, #0x10]
+        assert_eq!(adrp_ldr_target(&adrp, 0x1000, &decode(0x91004000)), None); // add x0, x0, #0x10
+    }
+
+    #[test]
+    fn register_reads_and_writes() {
+        let bl = decode(0x94000040); // bl #0x100
+        assert!(writes_register(&bl, 30) && !writes_register(&bl, 0) && !reads_register(&bl, 0));
+        let add = decode(0x91004083); // add x3, x4, #16
+        assert!(writes_register(&add, 3) && !writes_register(&add, 4));
+        assert!(reads_register(&add, 4) && !reads_register(&add, 0) && !reads_register(&add, 3));
+        let cmp = decode(0xf100041f); // cmp x0, #1
+        assert!(reads_register(&cmp, 0) && !(0..=31).any(|reg| writes_register(&cmp, reg)));
+
+        let stp = decode(0xa9bf07e0); // stp x0, x1, [sp, #-16]!
+        assert!(reads_register(&stp, 0) && reads_register(&stp, 1) && reads_register(&stp, STACK_POINTER));
+        assert!(writes_register(&stp, STACK_POINTER) && !writes_register(&stp, 0));
+        let ldr = decode(0xf9400801); // ldr x1, [x0, #16]
+        assert!(writes_register(&ldr, 1) && !writes_register(&ldr, 0) && reads_register(&ldr, 0) && !reads_register(&ldr, 1));
+        let ldp = decode(0xa8c17bfd); // ldp x29, x30, [sp], #16
+        assert!(writes_register(&ldp, 29) && writes_register(&ldp, 30) && writes_register(&ldp, STACK_POINTER));
+        let madd = decode(0x9b020c20); // madd x0, x1, x2, x3
+        assert!((1..=3).all(|reg| reads_register(&madd, reg)) && !reads_register(&madd, 0));
+        let cbz = decode(0xb4000045); // cbz x5, #8
+        assert!(reads_register(&cbz, 5) && !writes_register(&cbz, 5));
+        let and = decode(0x4e221c20); // and v0.16b, v1.16b, v2.16b
+        assert!(!writes_register(&and, 0) && !reads_register(&and, 1));
+        let ret = decode(0xd65f03c0); // ret
+        assert!(reads_register(&ret, 30) && !writes_register(&ret, 30));
+    }
+
+    #[test]
+    fn direct_branch_targets() {
+        // 0x1000: b #0x10; adr x0, #-0x1000; cbz x1, #8; b.ne #-8; bl #0x100000; br x16
+        let code = code_of(&[0x14000004, 0x10ff8000, 0xb4000041, 0x54ffffc1, 0x94040000, 0xd61f0200]);
+        let targets = branch_targets(&code, 0x1000);
+        assert_eq!(targets.into_iter().collect::<Vec<_>>(), vec![0x4, 0x1004, 0x1010, 0x101010]);
+    }
+
+    #[test]
+    fn pc_relative_data_refs() {
+        // 0x1ff8: adrp x0, #0x10000; adr x0, #-0x1000; b #8; ldr x2, #16; ldr s0, #-8
+        let code = code_of(&[0x90000080, 0x10ff8000, 0x14000002, 0x58000082, 0x1cffffc0]);
+        let refs = data_refs(&code, 0x1ff8);
+        assert_eq!(refs, vec![(0x1ff8, 0x11000), (0x1ffc, 0xffc), (0x2004, 0x2014), (0x2008, 0x2000)]);
+        assert!(data_refs(&[], 0).is_empty());
+    }
+
+    #[test]
+    fn straight_line_blocks() {
+        // 0x1000: add x0, x1, #16; add x0, x1, #16; ret; b #-8
+        let code = code_of(&[0x91004020, 0x91004020, 0xd65f03c0, 0x17fffffe]);
+        let (insts, flow) = decode_block(&code, 0x1000);
+        let ops: Vec<(u64, Op)> = insts.into_iter().map(|(pc, inst)| (pc, inst.op)).collect();
+        assert_eq!(ops, vec![(0x1000, A64_ADD_IMM), (0x1004, A64_ADD_IMM), (0x1008, A64_RET)]);
+        assert_eq!(flow, ControlFlow::Return);
+
+        assert_eq!(decode_block(&code[12..], 0x100c).1, ControlFlow::Jump);
+        assert_eq!(decode_block(&code[..8], 0x1000), (vec![(0x1000, decode(0x91004020)), (0x1004, decode(0x91004020))], ControlFlow::Sequential));
+        let (insts, flow) = decode_block(&[0x20, 0x40, 0x00, 0x91, 0xff, 0xff, 0xff, 0xff], 0); // add, then data
+        assert_eq!((insts.len(), flow), (1, ControlFlow::Sequential));
+    }
+
+    #[test]
+    fn recursive_disassembly_skips_data() {
+        // 0x1000: cbz x0, #12; add x0, x0, #1; ret; b #-8
+        // 0x1010: data that would decode as add x0, x1, #16 and an unknown word
+        // 0x1018: bl #-0x18, unreachable
+        let code = code_of(&[0xb4000060, 0x91000400, 0xd65f03c0, 0x17fffffe, 0x91004020, 0xffffffff, 0x97fffffa]);
+        let insts = disassemble_recursive(&code, 0x1000, &[0x1000]);
+        assert_eq!(insts.keys().copied().collect::<Vec<_>>(), vec![0x1000, 0x1004, 0x1008, 0x100c]);
+        assert_eq!(insts[&0x100c].op, A64_B);
+
+        // reached from a second entry, the call leads back into the function
+        let insts = disassemble_recursive(&code, 0x1000, &[0x1018, 0x2000]);
+        assert_eq!(insts.keys().copied().collect::<Vec<_>>(), vec![0x1000, 0x1004, 0x1008, 0x100c, 0x1018]);
+    }
+
+    #[test]
+    fn destination_also_read() {
+        assert!(reads_destination(&decode(0x2e225020))); // uabal v0.8h, v1.8b, v2.8b
+        assert!(reads_destination(&decode(0x6fa20020))); // mla v0.4s, v1.4s, v2.s[1]
+        assert!(reads_destination(&decode(0x6e621c20))); // bsl v0.16b, v1.16b, v2.16b
+        assert!(reads_destination(&decode(0xf2a00020))); // movk x0, #1, lsl #16
+        assert!(reads_destination(&decode(0x4f0d8420))); // shrn2 v0.16b, v1.8h, #3
+        assert!(!reads_destination(&decode(0x0f0d8420))); // shrn v0.8b, v1.8h, #3
+        assert!(reads_destination(&decode(0x5e024020))); // sha256h q0, q1, v2.4s
+        assert!(!reads_destination(&decode(0x5e2808e6))); // sha1h s6, s7
+        assert!(!reads_destination(&decode(0x2e227020))); // uabdl v0.8h, v1.8b, v2.8b
+        assert!(!reads_destination(&decode(0x91004020))); // add x0, x1, #16
+    }
+
+    #[test]
+    fn literal_pool_values() {
+        // 0x1000: ldr x0, #16; ldr w1, #20; ldrsw x2, #16; ret
+        // 0x1010: .quad 0x1122334455667788; .word 0xfffffff0
+        let mut image = code_of(&[0x58000080, 0x180000a1, 0x98000082, 0xd65f03c0]);
+        image.extend_from_slice(&0x1122334455667788u64.to_le_bytes());
+        image.extend_from_slice(&0xfffffff0u32.to_le_bytes());
+
+        let ldr = |pc: u64| decode(word_at(&image, (pc - 0x1000) as usize));
+        assert_eq!(branch_target(&ldr(0x1000), 0x1000), Some(0x1010));
+        assert_eq!(literal_value(&ldr(0x1000), 0x1000, &image, 0x1000), Some(0x1122334455667788));
+        assert_eq!(literal_value(&ldr(0x1004), 0x1004, &image, 0x1000), Some(0xfffffff0));
+        assert_eq!(literal_value(&ldr(0x1008), 0x1008, &image, 0x1000), Some(-16i64 as u64));
+        assert_eq!(literal_value(&ldr(0x1000), 0x1000, &image[..20], 0x1000), None);
+        assert_eq!(literal_value(&ldr(0x100c), 0x100c, &image, 0x1000), None);
+    }
+
+    #[test]
+    fn stack_pointer_usage() {
+        let add = decode(0x910043ff); // add sp, sp, #16
+        assert!(uses_sp(&add) && writes_sp(&add));
+
+        let stp = decode(0xa9bf07e0); // stp x0, x1, [sp, #-16]!
+        assert!(uses_sp(&stp) && writes_sp(&stp));
+
+        let stp = decode(0xad0207e0); // stp q0, q1, [sp, #64]
+        assert!(uses_sp(&stp) && !writes_sp(&stp));
+
+        let mov = decode(0x910003e0); // mov x0, sp
+        assert!(uses_sp(&mov) && !writes_sp(&mov));
+
+        let add = decode(0x91004020); // add x0, x1, #16
+        assert!(!uses_sp(&add) && !writes_sp(&add));
+    }
+
+    #[test]
+    fn trapping_instructions() {
+        for binst in [
+            0xd4000001, // svc #0
+            0xd4000002, // hvc #0
+            0xd4200000, // brk #0
+            0xd4400000, // hlt #0
+            0x00000000, // udf #0
+            0xa9bf07e0, // stp x0, x1, [sp, #-16]!
+            0x1ee22820, // fadd h0, h1, h2
+        ] {
+            assert!(may_trap(&decode(binst)), "{:#010x}", binst);
+        }
+        assert!(!may_trap(&decode(0x91004020))); // add x0, x1, #16
+    }
+
+    #[test]
+    fn branch_control_flow() {
+        let bl = decode(0x97fffffe); // bl #-8
+        assert_eq!((control_flow(&bl), branch_target(&bl, 0x1000)), (ControlFlow::Call, Some(0xff8)));
+        let tbnz = decode(0xb7400045); // tbnz x5, #40, #8
+        assert_eq!((control_flow(&tbnz), branch_target(&tbnz, 0x1000)), (ControlFlow::ConditionalJump, Some(0x1008)));
+        assert_eq!(control_flow(&decode(0x14000040)), ControlFlow::Jump); // b #256
+        assert_eq!(control_flow(&decode(0xd61f0200)), ControlFlow::IndirectJump); // br x16
+        assert_eq!(control_flow(&decode(0xd63f0100)), ControlFlow::IndirectCall); // blr x8
+        assert_eq!(control_flow(&decode(0xd65f03c0)), ControlFlow::Return); // ret
+        assert_eq!(control_flow(&decode(0x91004020)), ControlFlow::Sequential); // add x0, x1, #16
+    }
+
+    #[test]
+    fn indirect_call_targets() {
+        let blr = decode(0xd63f0100); // blr x8
+        assert_eq!((call_target_reg(&blr), is_tail_call_candidate(&blr)), (Some(8), false));
+        let br = decode(0xd61f0200); // br x16
+        assert_eq!((call_target_reg(&br), is_tail_call_candidate(&br)), (Some(16), true));
+        let b = decode(0x14000040); // b #256
+        assert_eq!((call_target_reg(&b), is_tail_call_candidate(&b)), (None, false));
+        assert_eq!(call_target_reg(&decode(0xd65f03c0)), None); // ret
+    }
+
+    fn decode_all(words: &[u32]) -> Vec<Inst> {
+        return words.iter().map(|&binst| decode(binst)).collect();
+    }
+
+    #[test]
+    fn clang_prologue_and_epilogue() {
+        let small = decode_all(&[
+            0xa9bf7bfd, // stp x29, x30, [sp, #-16]!
+            0x910003fd, // mov x29, sp
+            0x97fffffe, // bl #-8
+            0xa8c17bfd, // ldp x29, x30, [sp], #16
+            0xd65f03c0, // ret
+        ]);
+        assert!(is_likely_prologue(&small));
+        assert!(is_likely_epilogue(&small[3..]));
+
+        let large = decode_all(&[
+            0xd503233f, // paciasp
+            0xd100c3ff, // sub sp, sp, #48
+            0xa9027bfd, // stp x29, x30, [sp, #32]
+            0xa9014ff4, // stp x20, x19, [sp, #16]
+            0x910083fd, // add x29, sp, #32
+            0xa9427bfd, // ldp x29, x30, [sp, #32]
+            0xa9414ff4, // ldp x20, x19, [sp, #16]
+            0x9100c3ff, // add sp, sp, #48
+            0xd50323bf, // autiasp
+            0xd65f03c0, // ret
+        ]);
+        assert!(is_likely_prologue(&large));
+        assert!(is_likely_epilogue(&large[5..]));
+
+        assert!(!is_likely_prologue(&small[1..])); // mov x29, sp without the frame record
+        assert!(!is_likely_prologue(&large[5..]));
+        assert!(!is_likely_epilogue(&small[..3]));
+        assert!(!is_likely_epilogue(&small[2..3])); // bl
+        assert!(!is_likely_epilogue(&decode_all(&[0x97fffffe, 0xd65f03c0]))); // bl; ret
+        assert!(!is_likely_epilogue(&[]));
+    }
+
+    #[test]
+    fn conditions_hold() {
+        use crate::aarch64_reader::Cond::*;
+
+        assert!(condition_holds(COND_EQ, NzcvMask::Z.0) && !condition_holds(COND_NE, NzcvMask::Z.0));
+        assert!(condition_holds(COND_HI, NzcvMask::C.0) && !condition_holds(COND_HI, (NzcvMask::C | NzcvMask::Z).0));
+        assert!(condition_holds(COND_GE, (NzcvMask::N | NzcvMask::V).0) && condition_holds(COND_LT, NzcvMask::N.0));
+        assert!(!condition_holds(COND_GT, NzcvMask::Z.0) && condition_holds(COND_LE, NzcvMask::Z.0));
+        assert!(condition_holds(COND_AL, 0) && condition_holds(0b1111, 0)); // AL, NV
+    }
+
+    #[test]
+    fn memory_operands() {
+        use crate::aarch64_reader::AddrMode::AM_OFF_EXT;
+        use crate::aarch64_reader::{set_addrmode, Extend};
+
+        let mem = |binst: u32| mem_operand(&decode(binst)).unwrap();
+        let simple = MemOperand { base: Some(1), index: None, offset: 0, writeback: Writeback::None };
+        assert_eq!(mem(0xc8dffc20), simple); // ldar x0, [x1]
+        assert_eq!(mem(0xf9400801), MemOperand { base: Some(0), offset: 16, ..simple }); // ldr x1, [x0, #16]
+        assert_eq!(mem(0xa9bf7bfd), MemOperand { base: Some(STACK_POINTER), offset: -16, writeback: Writeback::Pre, ..simple }); // stp x29, x30, [sp, #-16]!
+        assert_eq!(mem(0xa8c27bfd), MemOperand { base: Some(STACK_POINTER), offset: 32, writeback: Writeback::Post, ..simple }); // ldp x29, x30, [sp], #32
+        assert_eq!(mem(0x58000040), MemOperand { base: None, offset: 8, ..simple }); // ldr x0, #8
+
+        // ldr x0, [x1, w2, sxtw #3], built by hand as register offsets are not decoded yet
+        let ldr = decode(0xf9400020); // ldr x0, [x1]
+        let indexed = Inst { flags: set_addrmode(ldr.flags, AM_OFF_EXT), rm: 2, extend: Extend { typ: SXTW as u32, lsl: 3 }, ..ldr };
+        assert_eq!(mem_operand(&indexed).unwrap().index, Some(MemIndex { reg: 2, extend: SXTW, shift: 3 }));
+        let indexed = Inst { flags: set_addrmode(indexed.flags, AM_OFF_REG), ..indexed };
+        assert_eq!(mem_operand(&indexed).unwrap().index, Some(MemIndex { reg: 2, extend: UXTX, shift: 3 }));
+
+        assert_eq!(mem_operand(&decode(0x91004020)), None); // add x0, x1, #16
+    }
+
+    #[test]
+    fn padding_runs() {
+        let code = decode_all(&[0xd503201f, 0xd503201f, 0xd503201f, 0xa9bf7bfd]); // nop x3; stp x29, x30, [sp, #-16]!
+        assert_eq!(padding_run_len(&code), 3);
+        assert_eq!(padding_run_len(&decode_all(&[0x00000000, 0xd503201f, 0x00000000])), 3); // udf #0; nop; udf #0
+        assert_eq!(padding_run_len(&code[3..]), 0);
+        assert_eq!(padding_run_len(&[]), 0);
+
+        assert!(!is_padding(&decode(0xd503233f))); // paciasp
+        assert!(!is_padding(&decode(0xd503241f))); // bti
+        assert!(!is_padding(&decode(0x00000001))); // udf #1
+    }
+
+    #[test]
+    fn mixed_operand_widths() {
+        use RegWidth::{V, W, X};
+
+        let smaddl = decode(0x9b220c20); // smaddl x0, w1, w2, x3
+        assert_eq!(operand_widths(&smaddl), OperandWidths { rd: X, rn: W, rm: W, ra: X });
+        let madd = decode(0x1b020c20); // madd w0, w1, w2, w3
+        assert_eq!(operand_widths(&madd), OperandWidths { rd: W, rn: W, rm: W, ra: W });
+
+        let sxtb = decode(0x93401c20); // sxtb x0, w1
+        assert_eq!((operand_widths(&sxtb).rd, operand_widths(&sxtb).rn), (X, W));
+        let fadd = decode(0x4e22d420); // fadd v0.4s, v1.4s, v2.4s
+        assert_eq!(operand_widths(&fadd), OperandWidths { rd: V, rn: V, rm: V, ra: V });
+    }
+
+    #[test]
+    fn shapes_ignore_registers() {
+        let add = decode(0x91004020); // add x0, x1, #16
+        assert!(same_shape(&add, &decode(0x91004125))); // add x5, x9, #16
+        assert!(same_shape(&add, &decode(0x910043e0))); // add x0, sp, #16
+        assert!(!same_shape(&add, &decode(0x91008020))); // add x0, x1, #32
+        assert!(!same_shape(&add, &decode(0x11004020))); // add w0, w1, #16
+        assert!(!same_shape(&add, &decode(0xd1004020))); // sub x0, x1, #16
+        assert!(same_shape(&decode(0x9b020c20), &decode(0x9b092907))); // madd x0, x1, x2, x3; madd x7, x8, x9, x10
+    }
+
+    #[test]
+    fn flag_setters() {
+        assert!(sets_nzcv(&decode(0xba018422))); // rmif x1, #3, #2
+        assert!(sets_nzcv(&decode(0x3a00084d))); // setf8 w2
+        assert!(sets_nzcv(&decode(0x3a004bcd))); // setf16 w30
+        assert!(sets_nzcv(&decode(0xb1000420))); // adds x0, x1, #1
+        assert!(!sets_nzcv(&decode(0x91000420))); // add x0, x1, #1
+        assert!(sets_nzcv(&decode(0x6b0103ff))); // cmp wzr, w1
+        assert!(!sets_nzcv(&decode(0x9b220c20))); // smaddl x0, w1, w2, x3
+    }
+
+    #[test]
+    fn count_leading_bits_range() {
+        let clz = decode(0x5ac01020); // clz w0, w1
+        assert_eq!((clz.rd, clz.rn, clz_max(&clz), clz.op), (0, 1, 32, A64_CLZ));
+        let clz = decode(0xdac01062); // clz x2, x3
+        assert_eq!((clz.flags & W32, clz_max(&clz), clz.op), (0, 64, A64_CLZ));
+        let cls = decode(0x5ac014a4); // cls w4, w5
+        assert_eq!((cls.flags & W32, clz_max(&cls), cls.op), (W32, 31, A64_CLS));
+        let cls = decode(0xdac014e6); // cls x6, x7
+        assert_eq!((cls.rd, cls.rn, clz_max(&cls), cls.op), (6, 7, 63, A64_CLS));
+    }
+
+    #[test]
+    fn shift_and_insert_masks() {
+        let sli = decode(0x6f285420); // sli v0.4s, v1.4s, #8
+        assert_eq!(sli_sri_mask(&sli), 0xff);
+        let sri = decode(0x6f1d4462); // sri v2.8h, v3.8h, #3
+        assert_eq!(sli_sri_mask(&sri), 0xe000);
+        assert_eq!(sli_sri_mask(&decode(0x6f405420)), 0); // sli v0.2d, v1.2d, #0
+        assert_eq!(sli_sri_mask(&decode(0x2f084420)), 0xff); // sri v0.8b, v1.8b, #8
+        assert_eq!(sli_sri_mask(&decode(0x6f4044a4)), u64::MAX); // sri v4.2d, v5.2d, #64
+        assert_eq!(sli_sri_mask(&decode(0x91004020)), 0); // add x0, x1, #16
+    }
+
+    #[test]
+    fn extract_group_semantics() {
+        let ror = decode(0x93c12020); // ror x0, x1, #8
+        assert_eq!(extract_op(&ror), Some(ExtractOp::Rotate { rn: 1, amount: 8, bits: 64 }));
+        let extr = decode(0x93c22020); // extr x0, x1, x2, #8
+        assert_eq!(extract_op(&extr), Some(ExtractOp::ConcatExtract { hi: 1, lo: 2, lsb: 8, bits: 64 }));
+        let extr = decode(0x13857c83); // extr w3, w4, w5, #31
+        assert_eq!(extract_op(&extr), Some(ExtractOp::ConcatExtract { hi: 4, lo: 5, lsb: 31, bits: 32 }));
+        assert_eq!(extract_op(&decode(0x91004020)), None); // add x0, x1, #16
+
+        let ror = decode(0x93c14020); // ror x0, x1, #16
+        assert_eq!((ror_amount(&ror), ror.to_string()), (Some(16), "ror x0, x1, #16".to_string()));
+        let ror = decode(0x13817c20); // ror w0, w1, #31
+        assert_eq!((ror_amount(&ror), ror.to_string()), (Some(31), "ror w0, w1, #31".to_string()));
+        assert_eq!(ror_amount(&decode(0x93c22020)), None); // extr x0, x1, x2, #8
+
+        // imms must stay below 32 for the 32-bit forms
+        assert_eq!(decode(0x13858083).op, A64_ERROR); // extr w3, w4, w5, #32
+        let mut extr = extr;
+        extr.imm = 32;
+        assert_eq!(validate(&extr), Err(vec!["imms: shift 32 is not below 32".to_string()]));
+    }
+
+    #[test]
+    fn equality_ignores_unused_flags() {
+        let cmp = decode(0xf100041f); // cmp x0, #1
+        let mut other = cmp.clone();
+        other.flags |= 0x80;
+        assert!(semantically_eq(&cmp, &other) && cmp != other);
+        other.flags |= W32;
+        assert!(!semantically_eq(&cmp, &other));
+
+        let b = decode(0x14000004); // b #0x10
+        assert!(semantically_eq(&b, &Inst { flags: 0xFF, ..b.clone() }));
+        let beq = decode(0x54000080); // b.eq #0x10
+        assert!(!semantically_eq(&beq, &Inst { flags: beq.flags | 0x10, ..beq.clone() }));
+        let ccmp = decode(0xfa5f0800); // ccmp x0, #31, #0, eq
+        assert!(!semantically_eq(&ccmp, &decode(0xfa5f1800))); // ccmp x0, #31, #0, ne
+        let and = decode(0x4e221c20); // and v0.16b, v1.16b, v2.16b
+        assert!(!semantically_eq(&and, &decode(0x0e221c20))); // and v0.8b, v1.8b, v2.8b
+        let ldr = decode(0xf9400801); // ldr x1, [x0, #16]
+        assert!(semantically_eq(&ldr, &Inst { flags: ldr.flags | SET_FLAGS, ..ldr.clone() }));
+        assert!(!semantically_eq(&cmp, &ldr));
+    }
+
+    #[test]
+    fn typed_immediates() {
+        assert_eq!(immediate(&decode(0x91004020)), Immediate::Unsigned(16)); // add x0, x1, #16
+        assert_eq!(immediate(&decode(0x17fffffe)), Immediate::Signed(-8)); // b #-8
+        assert_eq!(immediate(&decode(0x9200f020)), Immediate::BitMask(0x5555555555555555)); // and x0, x1, #0x5555555555555555
+        assert_eq!(immediate(&decode(0x1e2e1000)), Immediate::Float(1.0)); // fmov s0, #1.0
+        assert_eq!(immediate(&decode(0x1e7f9001)), Immediate::Float(-1.75)); // fmov d1, #-1.75
+        assert_eq!(immediate(&decode(0xf2a24680)), Immediate::Unsigned(0x1234)); // movk x0, #0x1234, lsl #16
+        assert_eq!(immediate(&decode(0xa9bf7bfd)), Immediate::Signed(-16)); // stp x29, x30, [sp, #-16]!
+        assert_eq!(immediate(&decode(0x8b020020)), Immediate::None); // add x0, x1, x2
+    }
+
+    #[test]
+    fn condition_flag_dependencies() {
+        use crate::aarch64_reader::Cond::*;
+
+        assert_eq!(cond_dependency(COND_EQ), NzcvMask::Z);
+        assert_eq!(cond_dependency(COND_GT), NzcvMask::N | NzcvMask::Z | NzcvMask::V);
+        assert_eq!(cond_dependency(COND_LE), cond_dependency(COND_GT));
+        assert_eq!(cond_dependency(COND_HI), NzcvMask::C | NzcvMask::Z);
+        assert_eq!(cond_dependency(COND_LT), NzcvMask::N | NzcvMask::V);
+        assert!(cond_dependency(COND_AL).is_empty() && cond_dependency(COND_NV).is_empty());
+
+        assert_eq!(nzcv_dependency(&decode(0x54000060)), NzcvMask::Z); // b.eq #12
+        assert_eq!(nzcv_dependency(&decode(0x5400006a)), NzcvMask::N | NzcvMask::V); // b.ge #12
+        assert_eq!(nzcv_dependency(&decode(0x5400006c)), NzcvMask::N | NzcvMask::Z | NzcvMask::V); // b.gt #12
+        assert_eq!(nzcv_dependency(&decode(0xb1000420)), NzcvMask::NONE); // adds x0, x1, #1
+        assert!(NzcvMask::ALL.contains(NzcvMask::N | NzcvMask::C));
+    }
+
+    #[test]
+    fn adrp_pair_register_mismatch() {
+        let adrp = data_proc_imm(0xd0000080); // adrp x0, #0x12000
+        let add = data_proc_imm(0x91004021); // add x1, x1, #16
+        assert_eq!(resolve_adrp_pair(&adrp, 0x1000, &add), None);
+        assert_eq!(resolve_adrp_pair(&add, 0x1000, &adrp), None);
+    }
+
+    #[test]
+    fn decode_stats_counts() {
+        // add x0, x1, #16; add x0, x1, #16; ret; ldxr x0, [x1]; udf #0; 2 stray bytes
+        let mut code = code_of(&[0x91004020, 0x91004020, 0xd65f03c0, 0xc85f7c20, 0x00000000]);
+        code.extend_from_slice(&[0xff, 0xff]);
+
+        let stats = DecodeStats::from_buffer(&code);
+        assert_eq!(stats.by_op.get(&A64_ADD_IMM), Some(&2));
+        assert_eq!(stats.by_op.get(&A64_RET), Some(&1));
+        assert_eq!(stats.by_op.get(&A64_UDF), Some(&1));
+        assert_eq!(stats.by_op.get(&A64_UNKNOWN), Some(&1));
+        assert_eq!(stats.by_op.values().sum::<u64>(), 5);
+        assert_eq!(stats.by_group.get(&Group::DataProcImm), Some(&2));
+        assert_eq!(stats.by_group.get(&Group::BranchExcSys), Some(&1));
+        assert_eq!(stats.by_group.get(&Group::LoadStore), Some(&1));
+        assert_eq!(stats.by_group.get(&Group::Reserved), Some(&1));
+        assert_eq!(stats.unknown, 1);
+    }
+
+    #[test]
+    fn summarize_counts() {
+        // svc #0x80; b .+8; cbz x0, .+8; bl .; ret; ldr x1, [x0, #0x10];
+        // aese v0.16b, v1.16b; fadd d0, d1, d2; mrs x0, tpidr_el0; udf #0
+        let words = [0xd4001001, 0x14000002, 0xb4000040, 0x94000000, 0xd65f03c0, 0xf9400801, 0x4e284820, 0x1e622820, 0xd53bd040, 0x00000000];
+        let code = code_of(&words);
+
+        let summary = summarize(&code);
+        assert_eq!((summary.branches, summary.calls, summary.returns, summary.syscalls), (2, 1, 1, 1));
+        assert_eq!((summary.memory, summary.crypto, summary.simd_fp), (1, 1, 1));
+        assert_eq!(summary.syscall_numbers, BTreeSet::from([0x80]));
+        assert_eq!(summary.sysregs, BTreeSet::from([0xde82])); // TPIDR_EL0
+        assert_eq!(summarize(&[]), Summary::default());
+        assert!(is_crypto(&A64_EOR3) && is_crypto(&A64_SHA1H) && !is_crypto(&A64_EOR_VEC));
+    }
+}
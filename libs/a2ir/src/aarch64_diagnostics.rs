@@ -0,0 +1,102 @@
+use std::fmt;
+
+use crate::aarch64_reader::AddrMode::{AM_POST, AM_PRE};
+use crate::aarch64_reader::Op::*;
+use crate::aarch64_reader::Registries::STACK_POINTER;
+use crate::aarch64_reader::{decode, fad_get_addrmode, Inst};
+
+/// Something dubious about an encoding that still decodes. A linter can
+/// report these, the decoder itself does not reject the word.
+///
+/// None of the instructions decoded so far has should-be-zero fields or a
+/// deprecated encoding, so there are no diagnostics for those yet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Diagnostic {
+    /// Should-be-one bits that are clear, as a mask of their positions
+    ShouldBeOne(u32),
+    /// CONSTRAINED UNPREDICTABLE operand combination
+    Unpredictable(&'static str),
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mask = match self {
+            Diagnostic::ShouldBeOne(mask) => *mask,
+            Diagnostic::Unpredictable(why) => return write!(f, "unpredictable: {}", why),
+        };
+        f.write_str(if mask.count_ones() == 1 { "bit " } else { "bits " })?;
+        let mut separator = "";
+        for bit in (0..32).rev().filter(|bit| mask & (1 << bit) != 0) {
+            write!(f, "{}{}", separator, bit)?;
+            separator = ", ";
+        }
+        return f.write_str(" should be one");
+    }
+}
+
+/// Decodes binst like `decode` and collects the diagnostics for it. Words
+/// that do not decode have none.
+///
+/// The data processing (immediate) group has no should-be-zero or
+/// should-be-one fields: data_proc_imm checks every fixed bit and reports
+/// reserved field values as errors. The checks so far cover loads and stores:
+///
+/// - the SBO Rs and Rt2 fields of LDAR, STLR, LDLAR and STLLR
+/// - pre- and post-indexed writeback to the transfer register (Rt == Rn)
+/// - pair loads into the same register twice (Rt == Rt2)
+pub fn decode_with_diagnostics(binst: u32) -> (Inst, Vec<Diagnostic>) {
+    let inst = decode(binst);
+    let mut diagnostics = Vec::new();
+
+    // Load-acquire/store-release register: size 001000 1 L 0 Rs o0 Rt2 Rn Rt
+    if matches!(inst.op, A64_LDR | A64_STR) && binst & 0x3fa00000 == 0x08800000 {
+        let sbo = 0x001f7c00;
+        if binst & sbo != sbo {
+            diagnostics.push(Diagnostic::ShouldBeOne(sbo & !binst));
+        }
+    }
+
+    let gpr_transfer = matches!(inst.op, A64_LDR | A64_STR | A64_LDP | A64_STP);
+    let writeback = matches!(fad_get_addrmode(inst.flags), AM_PRE | AM_POST);
+    if gpr_transfer && writeback && inst.rn != STACK_POINTER && (inst.rd == inst.rn || (matches!(inst.op, A64_LDP | A64_STP) && inst.rt2 == inst.rn)) {
+        diagnostics.push(Diagnostic::Unpredictable("writeback to a transfer register"));
+    }
+
+    if matches!(inst.op, A64_LDP | A64_LDNP | A64_LDP_FP | A64_LDNP_FP) && inst.rd == inst.rt2 {
+        diagnostics.push(Diagnostic::Unpredictable("pair load with Rt == Rt2"));
+    }
+
+    return (inst, diagnostics);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dubious_but_decodable() {
+        // ldar x0, [x1] with Rs = 0 instead of 11111
+        let (inst, diagnostics) = decode_with_diagnostics(0xc8c0fc20);
+        assert_eq!(inst.op, A64_LDR);
+        assert_eq!(diagnostics, vec![Diagnostic::ShouldBeOne(0x001f0000)]);
+        assert_eq!(diagnostics[0].to_string(), "bits 20, 19, 18, 17, 16 should be one");
+
+        let (inst, diagnostics) = decode_with_diagnostics(0xc8dfbc20); // ldar x0, [x1], bit 14 clear
+        assert_eq!((inst.op, inst.rd, inst.rn), (A64_LDR, 0, 1));
+        assert_eq!(diagnostics[0].to_string(), "bit 14 should be one");
+
+        let (inst, diagnostics) = decode_with_diagnostics(0xa9bf0821); // stp x1, x2, [x1, #-16]!
+        assert_eq!(inst.op, A64_STP);
+        assert_eq!(diagnostics, vec![Diagnostic::Unpredictable("writeback to a transfer register")]);
+        let (inst, diagnostics) = decode_with_diagnostics(0xa9c10420); // ldp x0, x1, [x1, #16]!
+        assert_eq!((inst.op, diagnostics.len()), (A64_LDP, 1));
+        let (inst, diagnostics) = decode_with_diagnostics(0xa9410020); // ldp x0, x0, [x1, #16]
+        assert_eq!(inst.op, A64_LDP);
+        assert_eq!(diagnostics[0].to_string(), "unpredictable: pair load with Rt == Rt2");
+
+        assert!(decode_with_diagnostics(0xc8dffc20).1.is_empty()); // ldar x0, [x1]
+        assert!(decode_with_diagnostics(0xa9bf7bfd).1.is_empty()); // stp x29, x30, [sp, #-16]!
+        assert!(decode_with_diagnostics(0x91004020).1.is_empty()); // add x0, x1, #16
+        assert!(decode_with_diagnostics(0x00000000).1.is_empty()); // udf #0
+    }
+}
@@ -0,0 +1,1070 @@
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::aarch64_analysis::{control_flow, operand_widths, sets_nzcv, RegWidth};
+use crate::aarch64_reader::ExtendType::{SXTB, SXTH, SXTW, UXTB, UXTH};
+use crate::aarch64_reader::FlagMasks::{SET_FLAGS, W32};
+use crate::aarch64_reader::Op::*;
+use crate::aarch64_reader::Registries::{STACK_POINTER, ZERO_REG};
+use crate::aarch64_reader::{all_ops, fad_get_cond, Decoder, Inst, InstShift, Op, UNKNOWN_INST};
+
+/// The mnemonic of an opcode, without any operand context: the opcode name
+/// without the A64_ prefix and the variant suffix (_IMM, _REG, _VEC, ...),
+/// lowercased. Opcodes that stand for several instructions print their
+/// generic name, e.g. A64_EXTEND (SXTB, UXTH, ...) prints "extend",
+/// A64_MOVI (MOVI, MVNI) prints "movi" and A64_BCOND prints "b" without its
+/// condition.
+pub fn op_mnemonic(op: &Op) -> &'static str {
+    return match op {
+        A64_UNKNOWN => "unknown",
+        A64_ERROR => "error",
+        A64_UDF => "udf",
+        A64_ADR => "adr",
+        A64_ADRP => "adrp",
+        A64_ADD_IMM => "add",
+        A64_CMN_IMM => "cmn",
+        A64_MOV_SP => "mov",
+        A64_SUB_IMM => "sub",
+        A64_CMP_IMM => "cmp",
+        A64_AND_IMM => "and",
+        A64_ORR_IMM => "orr",
+        A64_EOR_IMM => "eor",
+        A64_TST_IMM => "tst",
+        A64_MOVK => "movk",
+        A64_MOV_IMM => "mov",
+        A64_SBFM => "sbfm",
+        A64_ASR_IMM => "asr",
+        A64_SBFIZ => "sbfiz",
+        A64_SBFX => "sbfx",
+        A64_BFM => "bfm",
+        A64_BFC => "bfc",
+        A64_BFI => "bfi",
+        A64_BFXIL => "bfxil",
+        A64_UBFM => "ubfm",
+        A64_LSL_IMM => "lsl",
+        A64_LSR_IMM => "lsr",
+        A64_UBFIZ => "ubfiz",
+        A64_UBFX => "ubfx",
+        A64_EXTEND => "extend",
+        A64_EXTR => "extr",
+        A64_ROR_IMM => "ror",
+        A64_BCOND => "b",
+        A64_SVC => "svc",
+        A64_HVC => "hvc",
+        A64_SMC => "smc",
+        A64_BRK => "brk",
+        A64_HLT => "hlt",
+        A64_DCPS1 => "dcps1",
+        A64_DCPS2 => "dcps2",
+        A64_DCPS3 => "dcps3",
+        A64_HINT => "hint",
+        A64_CLREX => "clrex",
+        A64_DMB => "dmb",
+        A64_ISB => "isb",
+        A64_SB => "sb",
+        A64_DSB => "dsb",
+        A64_SSBB => "ssbb",
+        A64_PSSBB => "pssbb",
+        A64_MSR_IMM => "msr",
+        A64_CFINV => "cfinv",
+        A64_XAFlag => "xaflag",
+        A64_AXFlag => "axflag",
+        A64_SYS => "sys",
+        A64_SYSL => "sysl",
+        A64_MSR_REG => "msr",
+        A64_MRS => "mrs",
+        A64_BR => "br",
+        A64_BLR => "blr",
+        A64_RET => "ret",
+        A64_B => "b",
+        A64_BL => "bl",
+        A64_CBZ => "cbz",
+        A64_CBNZ => "cbnz",
+        A64_TBZ => "tbz",
+        A64_TBNZ => "tbnz",
+        A64_UDIV => "udiv",
+        A64_SDIV => "sdiv",
+        A64_LSLV => "lslv",
+        A64_LSRV => "lsrv",
+        A64_ASRV => "asrv",
+        A64_RORV => "rorv",
+        A64_CRC32B => "crc32b",
+        A64_CRC32H => "crc32h",
+        A64_CRC32W => "crc32w",
+        A64_CRC32X => "crc32x",
+        A64_CRC32CB => "crc32cb",
+        A64_CRC32CH => "crc32ch",
+        A64_CRC32CW => "crc32cw",
+        A64_CRC32CX => "crc32cx",
+        A64_SUBP => "subp",
+        A64_RBIT => "rbit",
+        A64_REV16 => "rev16",
+        A64_REV => "rev",
+        A64_REV32 => "rev32",
+        A64_CLZ => "clz",
+        A64_CLS => "cls",
+        A64_PACIA => "pacia",
+        A64_PACIB => "pacib",
+        A64_PACDA => "pacda",
+        A64_PACDB => "pacdb",
+        A64_AUTIA => "autia",
+        A64_AUTIB => "autib",
+        A64_AUTDA => "autda",
+        A64_AUTDB => "autdb",
+        A64_XPACI => "xpaci",
+        A64_XPACD => "xpacd",
+        A64_AND_SHIFTED => "and",
+        A64_TST_SHIFTED => "tst",
+        A64_BIC => "bic",
+        A64_ORR_SHIFTED => "orr",
+        A64_MOV_REG => "mov",
+        A64_ORN => "orn",
+        A64_MVN => "mvn",
+        A64_EOR_SHIFTED => "eor",
+        A64_EON => "eon",
+        A64_ADD_SHIFTED => "add",
+        A64_CMN_SHIFTED => "cmn",
+        A64_SUB_SHIFTED => "sub",
+        A64_NEG => "neg",
+        A64_CMP_SHIFTED => "cmp",
+        A64_ADD_EXT => "add",
+        A64_CMN_EXT => "cmn",
+        A64_SUB_EXT => "sub",
+        A64_CMP_EXT => "cmp",
+        A64_ADC => "adc",
+        A64_SBC => "sbc",
+        A64_NGC => "ngc",
+        A64_RMIF => "rmif",
+        A64_SETF8 => "setf8",
+        A64_SETF16 => "setf16",
+        A64_CCMN_REG => "ccmn",
+        A64_CCMP_REG => "ccmp",
+        A64_CCMN_IMM => "ccmn",
+        A64_CCMP_IMM => "ccmp",
+        A64_CSEL => "csel",
+        A64_CSINC => "csinc",
+        A64_CINC => "cinc",
+        A64_CSET => "cset",
+        A64_CSINV => "csinv",
+        A64_CINV => "cinv",
+        A64_CSETM => "csetm",
+        A64_CSNEG => "csneg",
+        A64_CNEG => "cneg",
+        A64_MADD => "madd",
+        A64_MUL => "mul",
+        A64_MSUB => "msub",
+        A64_MNEG => "mneg",
+        A64_SMADDL => "smaddl",
+        A64_SMULL => "smull",
+        A64_SMSUBL => "smsubl",
+        A64_SMNEGL => "smnegl",
+        A64_SMULH => "smulh",
+        A64_UMADDL => "umaddl",
+        A64_UMULL => "umull",
+        A64_UMSUBL => "umsubl",
+        A64_UMNEGL => "umnegl",
+        A64_UMULH => "umulh",
+        A64_LD1_MULT => "ld1",
+        A64_ST1_MULT => "st1",
+        A64_LD2_MULT => "ld2",
+        A64_ST2_MULT => "st2",
+        A64_LD3_MULT => "ld3",
+        A64_ST3_MULT => "st3",
+        A64_LD4_MULT => "ld4",
+        A64_ST4_MULT => "st4",
+        A64_LD1_SINGLE => "ld1",
+        A64_ST1_SINGLE => "st1",
+        A64_LD2_SINGLE => "ld2",
+        A64_ST2_SINGLE => "st2",
+        A64_LD3_SINGLE => "ld3",
+        A64_ST3_SINGLE => "st3",
+        A64_LD4_SINGLE => "ld4",
+        A64_ST4_SINGLE => "st4",
+        A64_LD1R => "ld1r",
+        A64_LD2R => "ld2r",
+        A64_LD3R => "ld3r",
+        A64_LD4R => "ld4r",
+        A64_LDXR => "ldxr",
+        A64_STXR => "stxr",
+        A64_LDXP => "ldxp",
+        A64_STXP => "stxp",
+        A64_LDAPR => "ldapr",
+        A64_LDNP => "ldnp",
+        A64_STNP => "stnp",
+        A64_LDNP_FP => "ldnp",
+        A64_STNP_FP => "stnp",
+        A64_LDP => "ldp",
+        A64_STP => "stp",
+        A64_LDP_FP => "ldp",
+        A64_STP_FP => "stp",
+        A64_LDR => "ldr",
+        A64_STR => "str",
+        A64_LDR_FP => "ldr",
+        A64_STR_FP => "str",
+        A64_PRFM => "prfm",
+        A64_LDADD => "ldadd",
+        A64_LDCLR => "ldclr",
+        A64_LDEOR => "ldeor",
+        A64_LDSET => "ldset",
+        A64_LDSMAX => "ldsmax",
+        A64_LDSMIN => "ldsmin",
+        A64_LDUMAX => "ldumax",
+        A64_LDUMIN => "ldumin",
+        A64_SWP => "swp",
+        A64_CAS => "cas",
+        A64_CASP => "casp",
+        A64_FCVT_GPR => "fcvt",
+        A64_FCVT_VEC => "fcvt",
+        A64_CVTF => "cvtf",
+        A64_CVTF_VEC => "cvtf",
+        A64_FJCVTZS => "fjcvtzs",
+        A64_FRINT => "frint",
+        A64_FRINT_VEC => "frint",
+        A64_FRINTX => "frintx",
+        A64_FRINTX_VEC => "frintx",
+        A64_FCVT_H => "fcvt",
+        A64_FCVT_S => "fcvt",
+        A64_FCVT_D => "fcvt",
+        A64_FCVTL => "fcvtl",
+        A64_FCVTN => "fcvtn",
+        A64_FCVTXN => "fcvtxn",
+        A64_FABS => "fabs",
+        A64_FNEG => "fneg",
+        A64_FSQRT => "fsqrt",
+        A64_FMUL => "fmul",
+        A64_FMULX => "fmulx",
+        A64_FDIV => "fdiv",
+        A64_FADD => "fadd",
+        A64_FSUB => "fsub",
+        A64_FMAX => "fmax",
+        A64_FMAXNM => "fmaxnm",
+        A64_FMIN => "fmin",
+        A64_FMINNM => "fminnm",
+        A64_FRECPE => "frecpe",
+        A64_FRECPS => "frecps",
+        A64_FRECPX => "frecpx",
+        A64_FRSQRTE => "frsqrte",
+        A64_FRSQRTS => "frsqrts",
+        A64_FNMUL => "fnmul",
+        A64_FMADD => "fmadd",
+        A64_FMSUB => "fmsub",
+        A64_FNMADD => "fnmadd",
+        A64_FNMSUB => "fnmsub",
+        A64_FCMP_REG => "fcmp",
+        A64_FCMP_ZERO => "fcmp",
+        A64_FCMPE_REG => "fcmpe",
+        A64_FCMPE_ZERO => "fcmpe",
+        A64_FCCMP => "fccmp",
+        A64_FCCMPE => "fccmpe",
+        A64_FCSEL => "fcsel",
+        A64_FMOV_VEC2GPR => "fmov",
+        A64_FMOV_GPR2VEC => "fmov",
+        A64_FMOV_TOP2GPR => "fmov",
+        A64_FMOV_GPR2TOP => "fmov",
+        A64_FMOV_REG => "fmov",
+        A64_FMOV_IMM => "fmov",
+        A64_FMOV_VEC => "fmov",
+        A64_FCMEQ_REG => "fcmeq",
+        A64_FCMEQ_ZERO => "fcmeq",
+        A64_FCMGE_REG => "fcmge",
+        A64_FCMGE_ZERO => "fcmge",
+        A64_FCMGT_REG => "fcmgt",
+        A64_FCMGT_ZERO => "fcmgt",
+        A64_FCMLE_ZERO => "fcmle",
+        A64_FCMLT_ZERO => "fcmlt",
+        A64_FACGE => "facge",
+        A64_FACGT => "facgt",
+        A64_FABS_VEC => "fabs",
+        A64_FABD_VEC => "fabd",
+        A64_FNEG_VEC => "fneg",
+        A64_FSQRT_VEC => "fsqrt",
+        A64_FMUL_ELEM => "fmul",
+        A64_FMUL_VEC => "fmul",
+        A64_FMULX_ELEM => "fmulx",
+        A64_FMULX_VEC => "fmulx",
+        A64_FDIV_VEC => "fdiv",
+        A64_FADD_VEC => "fadd",
+        A64_FCADD => "fcadd",
+        A64_FSUB_VEC => "fsub",
+        A64_FMAX_VEC => "fmax",
+        A64_FMAXNM_VEC => "fmaxnm",
+        A64_FMIN_VEC => "fmin",
+        A64_FMINNM_VEC => "fminnm",
+        A64_FRECPE_VEC => "frecpe",
+        A64_FRECPS_VEC => "frecps",
+        A64_FRSQRTE_VEC => "frsqrte",
+        A64_FRSQRTS_VEC => "frsqrts",
+        A64_FMLA_ELEM => "fmla",
+        A64_FMLA_VEC => "fmla",
+        A64_FMLAL_ELEM => "fmlal",
+        A64_FMLAL_VEC => "fmlal",
+        A64_FMLAL2_ELEM => "fmlal2",
+        A64_FMLAL2_VEC => "fmlal2",
+        A64_FCMLA_ELEM => "fcmla",
+        A64_FCMLA_VEC => "fcmla",
+        A64_FMLS_ELEM => "fmls",
+        A64_FMLS_VEC => "fmls",
+        A64_FMLSL_ELEM => "fmlsl",
+        A64_FMLSL_VEC => "fmlsl",
+        A64_FMLSL2_ELEM => "fmlsl2",
+        A64_FMLSL2_VEC => "fmlsl2",
+        A64_FADDP => "faddp",
+        A64_FADDP_VEC => "faddp",
+        A64_FMAXP => "fmaxp",
+        A64_FMAXP_VEC => "fmaxp",
+        A64_FMAXV => "fmaxv",
+        A64_FMAXNMP => "fmaxnmp",
+        A64_FMAXNMP_VEC => "fmaxnmp",
+        A64_FMAXNMV => "fmaxnmv",
+        A64_FMINP => "fminp",
+        A64_FMINP_VEC => "fminp",
+        A64_FMINV => "fminv",
+        A64_FMINNMP => "fminnmp",
+        A64_FMINNMP_VEC => "fminnmp",
+        A64_FMINNMV => "fminnmv",
+        A64_AND_VEC => "and",
+        A64_BCAX => "bcax",
+        A64_BIC_VEC_IMM => "bic",
+        A64_BIC_VEC_REG => "bic",
+        A64_BIF => "bif",
+        A64_BIT => "bit",
+        A64_BSL => "bsl",
+        A64_CLS_VEC => "cls",
+        A64_CLZ_VEC => "clz",
+        A64_CNT => "cnt",
+        A64_EOR_VEC => "eor",
+        A64_EOR3 => "eor3",
+        A64_NOT_VEC => "not",
+        A64_ORN_VEC => "orn",
+        A64_ORR_VEC_IMM => "orr",
+        A64_ORR_VEC_REG => "orr",
+        A64_MOV_VEC => "mov",
+        A64_RAX1 => "rax1",
+        A64_RBIT_VEC => "rbit",
+        A64_REV16_VEC => "rev16",
+        A64_REV32_VEC => "rev32",
+        A64_REV64_VEC => "rev64",
+        A64_SHL_IMM => "shl",
+        A64_SHL_REG => "shl",
+        A64_SHLL => "shll",
+        A64_SHR => "shr",
+        A64_SHRN => "shrn",
+        A64_SRA => "sra",
+        A64_SLI => "sli",
+        A64_SRI => "sri",
+        A64_XAR => "xar",
+        A64_DUP_ELEM => "dup",
+        A64_DUP_GPR => "dup",
+        A64_EXT => "ext",
+        A64_INS_ELEM => "ins",
+        A64_INS_GPR => "ins",
+        A64_MOVI => "movi",
+        A64_SMOV => "smov",
+        A64_UMOV => "umov",
+        A64_TBL => "tbl",
+        A64_TBX => "tbx",
+        A64_TRN1 => "trn1",
+        A64_TRN2 => "trn2",
+        A64_UZP1 => "uzp1",
+        A64_UZP2 => "uzp2",
+        A64_XTN => "xtn",
+        A64_ZIP1 => "zip1",
+        A64_ZIP2 => "zip2",
+        A64_CMEQ_REG => "cmeq",
+        A64_CMEQ_ZERO => "cmeq",
+        A64_CMGE_REG => "cmge",
+        A64_CMGE_ZERO => "cmge",
+        A64_CMGT_REG => "cmgt",
+        A64_CMGT_ZERO => "cmgt",
+        A64_CMHI_REG => "cmhi",
+        A64_CMHS_REG => "cmhs",
+        A64_CMLE_ZERO => "cmle",
+        A64_CMLT_ZERO => "cmlt",
+        A64_CMTST => "cmtst",
+        A64_ABS_VEC => "abs",
+        A64_ABD => "abd",
+        A64_ABDL => "abdl",
+        A64_ABA => "aba",
+        A64_ABAL => "abal",
+        A64_NEG_VEC => "neg",
+        A64_MUL_ELEM => "mul",
+        A64_MUL_VEC => "mul",
+        A64_MULL_ELEM => "mull",
+        A64_MULL_VEC => "mull",
+        A64_ADD_VEC => "add",
+        A64_ADDHN => "addhn",
+        A64_ADDL => "addl",
+        A64_ADDW => "addw",
+        A64_HADD => "hadd",
+        A64_SUB_VEC => "sub",
+        A64_SUBHN => "subhn",
+        A64_SUBL => "subl",
+        A64_SUBW => "subw",
+        A64_HSUB => "hsub",
+        A64_MAX_VEC => "max",
+        A64_MIN_VEC => "min",
+        A64_DOT_ELEM => "dot",
+        A64_DOT_VEC => "dot",
+        A64_URECPE => "urecpe",
+        A64_URSQRTE => "ursqrte",
+        A64_MLA_ELEM => "mla",
+        A64_MLA_VEC => "mla",
+        A64_MLS_ELEM => "mls",
+        A64_MLS_VEC => "mls",
+        A64_MLAL_ELEM => "mlal",
+        A64_MLAL_VEC => "mlal",
+        A64_MLSL_ELEM => "mlsl",
+        A64_MLSL_VEC => "mlsl",
+        A64_ADDP => "addp",
+        A64_ADDP_VEC => "addp",
+        A64_ADDV => "addv",
+        A64_ADALP => "adalp",
+        A64_ADDLP => "addlp",
+        A64_ADDLV => "addlv",
+        A64_MAXP => "maxp",
+        A64_MAXV => "maxv",
+        A64_MINP => "minp",
+        A64_MINV => "minv",
+        A64_QADD => "qadd",
+        A64_QABS => "qabs",
+        A64_SUQADD => "suqadd",
+        A64_USQADD => "usqadd",
+        A64_QSHL_IMM => "qshl",
+        A64_QSHL_REG => "qshl",
+        A64_QSHRN => "qshrn",
+        A64_QSUB => "qsub",
+        A64_QXTN => "qxtn",
+        A64_SQABS => "sqabs",
+        A64_SQADD => "sqadd",
+        A64_SQDMLAL_ELEM => "sqdmlal",
+        A64_SQDMLAL_VEC => "sqdmlal",
+        A64_SQDMLSL_ELEM => "sqdmlsl",
+        A64_SQDMLSL_VEC => "sqdmlsl",
+        A64_SQDMULH_ELEM => "sqdmulh",
+        A64_SQDMULH_VEC => "sqdmulh",
+        A64_SQDMULL_ELEM => "sqdmull",
+        A64_SQDMULL_VEC => "sqdmull",
+        A64_SQNEG => "sqneg",
+        A64_SQRDMLAH_ELEM => "sqrdmlah",
+        A64_SQRDMLAH_VEC => "sqrdmlah",
+        A64_SQRDMLSH_ELEM => "sqrdmlsh",
+        A64_SQRDMLSH_VEC => "sqrdmlsh",
+        A64_SQSHLU => "sqshlu",
+        A64_SQSHRUN => "sqshrun",
+        A64_SQXTUN => "sqxtun",
+        A64_PMUL => "pmul",
+        A64_PMULL => "pmull",
+        A64_AESD => "aesd",
+        A64_AESE => "aese",
+        A64_AESIMC => "aesimc",
+        A64_AESMC => "aesmc",
+        A64_SHA1C => "sha1c",
+        A64_SHA1H => "sha1h",
+        A64_SHA1M => "sha1m",
+        A64_SHA1P => "sha1p",
+        A64_SHA1SU0 => "sha1su0",
+        A64_SHA1SU1 => "sha1su1",
+        A64_SHA256H => "sha256h",
+        A64_SHA256H2 => "sha256h2",
+        A64_SHA256SU0 => "sha256su0",
+        A64_SHA256SU1 => "sha256su1",
+        A64_BFCVT => "bfcvt",
+        A64_BFCVTN => "bfcvtn",
+        A64_BFDOT_ELEM => "bfdot",
+        A64_BFDOT_VEC => "bfdot",
+        A64_BFMLALB_ELEM => "bfmlalb",
+        A64_BFMLALB_VEC => "bfmlalb",
+        A64_BFMLALT_ELEM => "bfmlalt",
+        A64_BFMLALT_VEC => "bfmlalt",
+        A64_BFMMLA => "bfmmla",
+    };
+}
+
+/// The first opcode of each section of the Op enum and the section's name, in
+/// declaration order. Keep in sync with the section comments there.
+const CATEGORIES: &[(Op, &str)] = &[
+    (A64_UNKNOWN, "Unknown, error, undefined"),
+    // Data Processing -- Immediate
+    (A64_ADR, "PC-rel. addressing"),
+    (A64_ADD_IMM, "Add/subtract (immediate)"),
+    (A64_AND_IMM, "Logical (immediate)"),
+    (A64_MOVK, "Move wide (immediate)"),
+    (A64_SBFM, "Bitfield"),
+    (A64_EXTR, "Extract"),
+    // Branches, Exception Generating and System Instructions
+    (A64_BCOND, "Conditional branch (immediate)"),
+    (A64_SVC, "Exception generation"),
+    (A64_HINT, "Hints"),
+    (A64_CLREX, "Barriers"),
+    (A64_MSR_IMM, "PSTATE"),
+    (A64_SYS, "System instructions"),
+    (A64_MSR_REG, "System register move"),
+    (A64_BR, "Unconditional branch (register)"),
+    (A64_B, "Unconditional branch (immediate)"),
+    (A64_CBZ, "Compare and branch (immediate)"),
+    (A64_TBZ, "Test and branch (immediate)"),
+    // Data Processing -- Register
+    (A64_UDIV, "Data-processing (2 source)"),
+    (A64_RBIT, "Data-processing (1 source)"),
+    (A64_PACIA, "Pointer authentication"),
+    (A64_AND_SHIFTED, "Logical (shifted register)"),
+    (A64_ADD_SHIFTED, "Add/subtract (shifted register)"),
+    (A64_ADD_EXT, "Add/subtract (extended register)"),
+    (A64_ADC, "Add/subtract (with carry)"),
+    (A64_RMIF, "Rotate right into flags"),
+    (A64_SETF8, "Evaluate into flags"),
+    (A64_CCMN_REG, "Conditional compare (register)"),
+    (A64_CCMN_IMM, "Conditional compare (immediate)"),
+    (A64_CSEL, "Conditional select"),
+    (A64_MADD, "Data-processing (3 source)"),
+    // Loads and Stores
+    (A64_LD1_MULT, "Advanced SIMD load/store multiple structures"),
+    (A64_LD1_SINGLE, "Advanced SIMD load/store single structure"),
+    (A64_LDXR, "Load/store exclusive"),
+    (A64_LDNP, "Load/store no-allocate pair (offset)"),
+    (A64_LDP, "Load/store register pair"),
+    (A64_LDR, "Load/store register"),
+    (A64_PRFM, "Prefetch memory"),
+    (A64_LDADD, "Atomic memory operations"),
+    // Data Processing -- Scalar Floating-Point and Advanced SIMD
+    (A64_FCVT_GPR, "Conversion between Floating Point and Integer/Fixed-Point"),
+    (A64_FRINT, "Rounding and Precision Conversion"),
+    (A64_FABS, "Floating-Point Computation (scalar)"),
+    (A64_FRECPE, "Floating-Point Stepwise (scalar)"),
+    (A64_FNMUL, "Floating-Point Fused Multiply (scalar)"),
+    (A64_FCMP_REG, "Floating-Point Compare, Select, Move (scalar)"),
+    (A64_FCMEQ_REG, "SIMD Floating-Point Compare"),
+    (A64_FABS_VEC, "SIMD Simple Floating-Point Computation"),
+    (A64_FRECPE_VEC, "SIMD Floating-Point Stepwise"),
+    (A64_FMLA_ELEM, "SIMD Floating-Point Fused Multiply"),
+    (A64_FADDP, "SIMD Floating-Point Computation (reduce)"),
+    (A64_AND_VEC, "SIMD Bitwise: Logical, Pop Count, Bit Reversal, Byte Swap, Shifts"),
+    (A64_DUP_ELEM, "SIMD Copy, Table Lookup, Transpose, Extract, Insert, Zip, Unzip"),
+    (A64_CMEQ_REG, "SIMD Integer/Bitwise Compare"),
+    (A64_ABS_VEC, "SIMD Integer Computation"),
+    (A64_URECPE, "SIMD Integer Stepwise"),
+    (A64_MLA_ELEM, "SIMD Integer Fused Multiply"),
+    (A64_ADDP, "SIMD Integer Computation (reduce)"),
+    (A64_QADD, "SIMD Saturating Integer Arithmetic (unsigned, signed)"),
+    (A64_SQABS, "SIMD Saturating Integer Arithmetic (signed exclusive)"),
+    (A64_PMUL, "SIMD Polynomial Multiply"),
+    (A64_AESD, "Cryptographic AES"),
+    (A64_SHA1C, "Cryptographic SHA"),
+    (A64_BFCVT, "SIMD BFloat16"),
+];
+
+/// The condition codes as LLVM names them, indexed by the encoded condition.
+const COND_NAMES: [&str; 16] = ["eq", "ne", "hs", "lo", "mi", "pl", "vs", "vc", "hi", "ls", "ge", "lt", "gt", "le", "al", "nv"];
+
+/// The name of the hint with number imm (CRm:op2) as stored in Inst.imm of an
+/// A64_HINT, e.g. "csdb" for 20 and "bti c" for 34, or "hint" for the
+/// unnamed ones. The pointer authentication hints (PACIASP, ...) are
+/// included although they decode to opcodes of their own.
+pub fn hint_name(imm: u64) -> &'static str {
+    return match imm {
+        0 => "nop",
+        1 => "yield",
+        2 => "wfe",
+        3 => "wfi",
+        4 => "sev",
+        5 => "sevl",
+        6 => "dgh",
+        7 => "xpaclri",
+        8 => "pacia1716",
+        10 => "pacib1716",
+        12 => "autia1716",
+        14 => "autib1716",
+        16 => "esb",
+        17 => "psb csync",
+        18 => "tsb csync",
+        20 => "csdb",
+        22 => "clrbhb",
+        24 => "paciaz",
+        25 => "paciasp",
+        26 => "pacibz",
+        27 => "pacibsp",
+        28 => "autiaz",
+        29 => "autiasp",
+        30 => "autibz",
+        31 => "autibsp",
+        32 => "bti",
+        34 => "bti c",
+        36 => "bti j",
+        38 => "bti jc",
+        _ => "hint",
+    };
+}
+
+/// The section of the Op enum an opcode is declared in, e.g. "Conditional
+/// select" for A64_CSINC. The sections follow the encoding groups of the ARM
+/// ARM, except for the SIMD&FP ones, which are ordered by functionality.
+pub fn op_category(op: &Op) -> &'static str {
+    let d = op.clone() as u32;
+    let i = CATEGORIES.partition_point(|(first, _)| first.clone() as u32 <= d);
+    return CATEGORIES[i - 1].1;
+}
+
+/// A table of every opcode the decoder knows, one per line: the Op name,
+/// mnemonic, category, control flow (see control_flow) and whether it sets
+/// NZCV: "yes", "no" or "S" for the variants with SET_FLAGS, like ADDS.
+/// Columns are separated by tabs. tests/opcode_table.txt holds the current
+/// table.
+pub fn dump_opcode_table() -> String {
+    let mut table = String::from("# op\tmnemonic\tcategory\tcontrol flow\tsets flags\n");
+    for op in all_ops() {
+        let inst = Inst { op: op.clone(), ..UNKNOWN_INST };
+        let with_s = Inst { flags: SET_FLAGS, ..inst.clone() };
+        let flags = if sets_nzcv(&inst) { "yes" } else if sets_nzcv(&with_s) { "S" } else { "no" };
+        let flow = format!("{:?}", control_flow(&inst));
+        table.push_str(&[format!("{:?}", op).as_str(), op_mnemonic(&op), op_category(&op), &flow, flags].join("\t"));
+        table.push('\n');
+    }
+    return table;
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return f.write_str(op_mnemonic(self));
+    }
+}
+
+/// How register operands are named.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum RegStyle {
+    /// Like LLVM: x29 and x30 by number, register 31 as sp/wsp or xzr/wzr.
+    #[default]
+    Llvm,
+    /// Every register by number, for diffing the output of different tools.
+    /// Register 31 is x31/w31 either way; the stack pointer is marked by a
+    /// "(sp)" suffix.
+    Numeric,
+}
+
+const X_NAMES: [&str; 32] = [
+    "x0", "x1", "x2", "x3", "x4", "x5", "x6", "x7", "x8", "x9", "x10", "x11", "x12", "x13", "x14", "x15",
+    "x16", "x17", "x18", "x19", "x20", "x21", "x22", "x23", "x24", "x25", "x26", "x27", "x28", "x29", "x30", "x31",
+];
+const W_NAMES: [&str; 32] = [
+    "w0", "w1", "w2", "w3", "w4", "w5", "w6", "w7", "w8", "w9", "w10", "w11", "w12", "w13", "w14", "w15",
+    "w16", "w17", "w18", "w19", "w20", "w21", "w22", "w23", "w24", "w25", "w26", "w27", "w28", "w29", "w30", "w31",
+];
+
+/// The name of a general purpose register as decoded into Inst.rd etc.: its
+/// W or X facet, and the zero register or the stack pointer for the two
+/// meanings of register 31.
+pub fn reg_name(reg: u8, w32: bool, style: RegStyle) -> &'static str {
+    return match (reg, style) {
+        (ZERO_REG, RegStyle::Llvm) => if w32 { "wzr" } else { "xzr" },
+        (STACK_POINTER, RegStyle::Llvm) => if w32 { "wsp" } else { "sp" },
+        (STACK_POINTER, RegStyle::Numeric) => if w32 { "w31(sp)" } else { "x31(sp)" },
+        (0..=31, _) => if w32 { W_NAMES[reg as usize] } else { X_NAMES[reg as usize] },
+        _ => "<invalid>",
+    };
+}
+
+/// A general purpose register operand, see reg_name.
+struct Gpr(u8, bool, RegStyle);
+
+impl fmt::Display for Gpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return f.write_str(reg_name(self.0, self.1, self.2));
+    }
+}
+
+/// The optional `, <shift> #amount` suffix of a shifted register operand.
+struct ShiftSuffix<'a>(&'a InstShift);
+
+impl fmt::Display for ShiftSuffix<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let InstShift { typ, amount } = *self.0;
+        if typ == 0 && amount == 0 {
+            return Ok(()); // LSL #0
+        }
+        let name = ["lsl", "lsr", "asr", "ror"][(typ & 0b11) as usize];
+        return write!(f, ", {} #{}", name, amount);
+    }
+}
+
+/// The general purpose register operands Rd, Rn, Rm and Ra, each in its own
+/// width for instructions mixing W and X registers.
+fn sized_gprs(inst: &Inst, style: RegStyle) -> [Gpr; 4] {
+    let widths = operand_widths(inst);
+    let gpr = |r: u8, width: RegWidth| Gpr(r, width == RegWidth::W, style);
+    return [gpr(inst.rd, widths.rd), gpr(inst.rn, widths.rn), gpr(inst.rm, widths.rm), gpr(inst.ra, widths.ra)];
+}
+
+/// Disassembles the instruction in the syntax of the ARM ARM, choosing the
+/// same aliases as LLVM. Operands are printed for the data processing
+/// (immediate), the shifted register and the 3 source instructions, hints and
+/// the register branches so far; all other instructions print their mnemonic
+/// only.
+///
+/// Undecodable words are printed as `.inst 0x...` directives. Registers are
+/// named in the RegStyle::Llvm style, see Inst::display for others.
+///
+/// MOVZ, MOVN and the ORR with a bitmask immediate that are MOV print the
+/// value they move, as a signed decimal of the register width like
+/// llvm-objdump: `mov x0, #-2` for MOVN X0, #1. GNU objdump prints it in hex
+/// and adds the decimal as a comment.
+impl fmt::Display for Inst {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return self.display(RegStyle::Llvm).fmt(f);
+    }
+}
+
+/// An instruction to be displayed with the given register style, returned by
+/// Inst::display.
+pub struct InstDisplay<'a> {
+    inst: &'a Inst,
+    style: RegStyle,
+}
+
+impl Inst {
+    /// Disassembles like the Display of Inst, naming the registers in the
+    /// given style.
+    pub fn display(&self, style: RegStyle) -> InstDisplay<'_> {
+        return InstDisplay { inst: self, style };
+    }
+}
+
+impl fmt::Display for InstDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (inst, style) = (self.inst, self.style);
+        let w32 = inst.flags & W32 != 0;
+        let s = if inst.flags & SET_FLAGS != 0 { "s" } else { "" };
+        let (rd, rn, rm) = (Gpr(inst.rd, w32, style), Gpr(inst.rn, w32, style), Gpr(inst.rm, w32, style));
+        let shift = ShiftSuffix(&inst.shift);
+
+        return match inst.op {
+            A64_UNKNOWN => write!(f, ".inst {:#010x}", inst.imm),
+            A64_ERROR => write!(f, "error: {}", inst.error),
+            A64_ADR | A64_ADRP => write!(f, "{} {}, #{}", inst.op, rd, inst.offset),
+            A64_ADD_IMM | A64_SUB_IMM if inst.imm > 0xFFF => {
+                write!(f, "{}{} {}, {}, #{}, lsl #12", inst.op, s, rd, rn, inst.imm >> 12)
+            }
+            A64_ADD_IMM | A64_SUB_IMM => write!(f, "{}{} {}, {}, #{}", inst.op, s, rd, rn, inst.imm),
+            A64_CMN_IMM | A64_CMP_IMM if inst.imm > 0xFFF => {
+                write!(f, "{} {}, #{}, lsl #12", inst.op, rn, inst.imm >> 12)
+            }
+            A64_CMN_IMM | A64_CMP_IMM => write!(f, "{} {}, #{}", inst.op, rn, inst.imm),
+            A64_MOV_SP => write!(f, "mov {}, {}", rd, rn),
+            A64_AND_IMM | A64_ORR_IMM | A64_EOR_IMM => write!(f, "{}{} {}, {}, #{:#x}", inst.op, s, rd, rn, inst.imm),
+            A64_TST_IMM => write!(f, "tst {}, #{:#x}", rn, inst.imm),
+            A64_MOV_IMM if w32 => write!(f, "mov {}, #{}", rd, inst.imm as u32 as i32),
+            A64_MOV_IMM => write!(f, "mov {}, #{}", rd, inst.imm as i64),
+            A64_MOVK if inst.movk.lsl != 0 => write!(f, "movk {}, #{}, lsl #{}", rd, inst.movk.imm16, inst.movk.lsl),
+            A64_MOVK => write!(f, "movk {}, #{}", rd, inst.movk.imm16),
+            A64_ASR_IMM | A64_LSL_IMM | A64_LSR_IMM | A64_ROR_IMM => write!(f, "{} {}, {}, #{}", inst.op, rd, rn, inst.imm),
+            A64_SBFIZ | A64_SBFX | A64_BFI | A64_BFXIL | A64_UBFIZ | A64_UBFX => {
+                write!(f, "{} {}, {}, #{}, #{}", inst.op, rd, rn, inst.bfm.lsb, inst.bfm.width)
+            }
+            A64_BFC => write!(f, "bfc {}, #{}, #{}", rd, inst.bfm.lsb, inst.bfm.width),
+            A64_EXTEND => {
+                let name = match inst.extend.typ as u8 {
+                    UXTB => "uxtb",
+                    UXTH => "uxth",
+                    SXTB => "sxtb",
+                    SXTH => "sxth",
+                    SXTW => "sxtw",
+                    _ => "extend",
+                };
+                write!(f, "{} {}, {}", name, rd, Gpr(inst.rn, true, style))
+            }
+            A64_EXTR => write!(f, "extr {}, {}, {}, #{}", rd, rn, rm, inst.imm),
+            // LSLV and friends print as their preferred aliases, LSL and so on
+            A64_LSLV => write!(f, "lsl {}, {}, {}", rd, rn, rm),
+            A64_LSRV => write!(f, "lsr {}, {}, {}", rd, rn, rm),
+            A64_ASRV => write!(f, "asr {}, {}, {}", rd, rn, rm),
+            A64_RORV => write!(f, "ror {}, {}, {}", rd, rn, rm),
+            A64_AND_SHIFTED | A64_BIC | A64_ORR_SHIFTED | A64_ORN | A64_EOR_SHIFTED | A64_EON | A64_ADD_SHIFTED
+            | A64_SUB_SHIFTED => write!(f, "{}{} {}, {}, {}{}", inst.op, s, rd, rn, rm, shift),
+            A64_TST_SHIFTED | A64_CMN_SHIFTED | A64_CMP_SHIFTED => write!(f, "{} {}, {}{}", inst.op, rn, rm, shift),
+            A64_MOV_REG => write!(f, "mov {}, {}", rd, rm),
+            A64_MVN => write!(f, "mvn {}, {}{}", rd, rm, shift),
+            A64_NEG => write!(f, "neg{} {}, {}{}", s, rd, rm, shift),
+            A64_MADD | A64_MSUB | A64_SMADDL | A64_SMSUBL | A64_UMADDL | A64_UMSUBL => {
+                let [rd, rn, rm, ra] = sized_gprs(inst, style);
+                write!(f, "{} {}, {}, {}, {}", inst.op, rd, rn, rm, ra)
+            }
+            A64_MUL | A64_MNEG | A64_SMULL | A64_SMNEGL | A64_UMULL | A64_UMNEGL | A64_SMULH | A64_UMULH => {
+                let [rd, rn, rm, _] = sized_gprs(inst, style);
+                write!(f, "{} {}, {}, {}", inst.op, rd, rn, rm)
+            }
+            A64_HINT => match hint_name(inst.imm) {
+                "hint" => write!(f, "hint #{}", inst.imm),
+                name => f.write_str(name),
+            },
+            A64_BCOND => write!(f, "b.{}", COND_NAMES[fad_get_cond(inst.flags) as usize]),
+            A64_RET if inst.rn == 30 => f.write_str("ret"),
+            A64_BR | A64_BLR | A64_RET => write!(f, "{} {}", inst.op, Gpr(inst.rn, false, style)),
+            _ => write!(f, "{}", inst.op),
+        };
+    }
+}
+
+/// The bytes of an instruction word as they are stored in memory, little
+/// endian: the inverse of how decode_buffer reads words.
+pub fn inst_bytes(word: u32) -> [u8; 4] {
+    return word.to_le_bytes();
+}
+
+/// The bytes of an instruction word in memory order, as two hex digits
+/// each separated by spaces, like the byte column of llvm-objdump:
+/// `20 10 00 91` for 0x91001020.
+pub fn format_bytes(word: u32) -> String {
+    let [b0, b1, b2, b3] = inst_bytes(word);
+    return format!("{:02x} {:02x} {:02x} {:02x}", b0, b1, b2, b3);
+}
+
+/// Escapes the text formatted into it for a JSON string and writes it to
+/// out, so that Display implementations can be written as JSON without an
+/// intermediate String. Keeps the first I/O error, which fmt::Error cannot
+/// carry.
+struct JsonEscape<'a, W: Write> {
+    out: &'a mut W,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> fmt::Write for JsonEscape<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut result = Ok(());
+        let mut rest = s;
+        while let Some(i) = rest.find(|c: char| c == '"' || c == '\\' || (c as u32) < 0x20) {
+            let c = rest.as_bytes()[i];
+            result = result.and_then(|_| self.out.write_all(&rest.as_bytes()[..i]));
+            result = result.and_then(|_| match c {
+                b'"' | b'\\' => self.out.write_all(&[b'\\', c]),
+                _ => write!(self.out, "\\u{:04x}", c),
+            });
+            rest = &rest[i + 1..];
+        }
+        result = result.and_then(|_| self.out.write_all(rest.as_bytes()));
+        return result.map_err(|e| {
+            self.error = Some(e);
+            fmt::Error
+        });
+    }
+}
+
+/// Writes the Display of value as a JSON string.
+fn write_json_str<W: Write>(out: &mut W, value: impl fmt::Display) -> io::Result<()> {
+    out.write_all(b"\"")?;
+    let mut escape = JsonEscape { out: &mut *out, error: None };
+    if fmt::write(&mut escape, format_args!("{}", value)).is_err() {
+        return Err(escape.error.unwrap_or_else(|| io::Error::other("formatter error")));
+    }
+    return out.write_all(b"\"");
+}
+
+/// Decodes the little-endian instruction words of code, the first one at
+/// address base, and writes one JSON object per line and instruction: its
+/// address, opcode, text, flags and operand fields, e.g.
+///
+/// `{"address":4096,"opcode":"A64_ADD_IMM","text":"add x0, x1, #16","flags":0,"rd":0,"rn":1,"rm":0,"ra":0,"rt2":0,"rs":0,"imm":16,"offset":0}`
+///
+/// Each line is written as soon as its instruction is decoded, so wrap out in
+/// a BufWriter unless it buffers already.
+pub fn decode_to_jsonl<W: Write>(code: &[u8], base: u64, mut out: W) -> io::Result<()> {
+    let decoder = Decoder { base_pc: base, ..Decoder::default() };
+    for (pc, inst) in decoder.decode_buffer(code) {
+        write!(out, "{{\"address\":{},\"opcode\":\"{:?}\",\"text\":", pc, inst.op)?;
+        write_json_str(&mut out, &inst)?;
+        write!(out, ",\"flags\":{},\"rd\":{},\"rn\":{},\"rm\":{},\"ra\":{},", inst.flags, inst.rd, inst.rn, inst.rm, inst.ra)?;
+        writeln!(out, "\"rt2\":{},\"rs\":{},\"imm\":{},\"offset\":{}}}", inst.rt2, inst.rs, inst.imm, inst.offset)?;
+    }
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn op_display() {
+        assert_eq!(format!("{}", A64_ADD_IMM), "add");
+        assert_eq!(format!("{}", A64_MOV_SP), "mov");
+        assert_eq!(format!("{}", A64_LD1_MULT), "ld1");
+        assert_eq!(format!("{}", A64_FCMEQ_ZERO), "fcmeq");
+        assert_eq!(format!("{}", A64_FMOV_GPR2VEC), "fmov");
+        assert_eq!(format!("{}", A64_BIC_VEC_IMM), "bic");
+        assert_eq!(format!("{}", A64_FMLAL2_ELEM), "fmlal2");
+        assert_eq!(format!("{}", A64_EXTEND), "extend");
+        assert_eq!(format!("{}", A64_PACIA), "pacia");
+        assert_eq!(format!("{}", A64_BCOND), "b");
+    }
+
+    #[test]
+    fn conditional_branch_names_its_condition() {
+        use crate::aarch64_reader::decode;
+
+        assert_eq!(format!("{}", decode(0x54000060)), "b.eq"); // b.eq #12
+        assert_eq!(format!("{}", decode(0x54000062)), "b.hs"); // b.hs #12
+        assert_eq!(format!("{}", decode(0x5400006d)), "b.le"); // b.le #12
+    }
+
+    #[test]
+    fn numeric_registers() {
+        use crate::aarch64_reader::decode;
+
+        let ret = decode(0xd65f03c0); // ret
+        assert_eq!(format!("{}", ret.display(RegStyle::Numeric)), "ret");
+        assert_eq!(format!("{}", decode(0xd65f0020).display(RegStyle::Numeric)), "ret x1");
+
+        let mov = decode(0x910003fd); // mov x29, sp
+        assert_eq!(format!("{}", mov), "mov x29, sp");
+        assert_eq!(format!("{}", mov.display(RegStyle::Numeric)), "mov x29, x31(sp)");
+
+        let cmp = decode(0x6b1f001f); // cmp w0, wzr
+        assert_eq!(format!("{}", cmp.display(RegStyle::Numeric)), "cmp w0, w31");
+        assert_eq!(format!("{}", cmp.display(RegStyle::Llvm)), format!("{}", cmp));
+
+        assert_eq!(reg_name(30, false, RegStyle::Numeric), "x30");
+        assert_eq!(reg_name(STACK_POINTER, true, RegStyle::Llvm), "wsp");
+    }
+
+    /// Parses a JSON string at the start of json, returning it unescaped and
+    /// the rest of json. Panics if it is not a well-formed string.
+    fn parse_json_str(json: &str) -> (String, &str) {
+        let mut chars = json.strip_prefix('"').expect("string expected").chars();
+        let mut value = String::new();
+        loop {
+            match chars.next().expect("unterminated string") {
+                '"' => return (value, chars.as_str()),
+                '\\' => match chars.next() {
+                    Some('u') => {
+                        let hex: String = chars.by_ref().take(4).collect();
+                        value.push(char::from_u32(u32::from_str_radix(&hex, 16).unwrap()).unwrap());
+                    }
+                    Some(c @ ('"' | '\\' | '/')) => value.push(c),
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    c => panic!("bad escape {:?}", c),
+                },
+                c if c < ' ' => panic!("unescaped control character {:?}", c),
+                c => value.push(c),
+            }
+        }
+    }
+
+    /// Parses a line of decode_to_jsonl: an object whose values are strings
+    /// or integers, returned in order with the strings unescaped. Panics if
+    /// the line is not exactly one such object.
+    fn parse_jsonl_object(line: &str) -> Vec<(String, String)> {
+        let mut rest = line.strip_prefix('{').expect("object expected");
+        let mut members = Vec::new();
+        loop {
+            let (key, after_key) = parse_json_str(rest);
+            rest = after_key.strip_prefix(':').expect("':' expected");
+            let value = if rest.starts_with('"') {
+                let (value, after_value) = parse_json_str(rest);
+                rest = after_value;
+                value
+            } else {
+                let end = rest.find([',', '}']).expect("unterminated object");
+                let (number, after_value) = rest.split_at(end);
+                assert!(number.parse::<i64>().is_ok() || number.parse::<u64>().is_ok(), "bad number {:?}", number);
+                rest = after_value;
+                number.to_string()
+            };
+            members.push((key, value));
+            match rest.chars().next() {
+                Some(',') => rest = &rest[1..],
+                Some('}') if rest.len() == 1 => return members,
+                _ => panic!("bad member separator in {:?}", line),
+            }
+        }
+    }
+
+    #[test]
+    fn jsonl_lines() {
+        let mut code = Vec::new();
+        for word in [0x91004020u32, 0xd65f03c0, 0xffffffff] { // add x0, x1, #16; ret; unknown
+            code.extend_from_slice(&word.to_le_bytes());
+        }
+        code.push(0x1f);
+
+        let mut out = Vec::new();
+        decode_to_jsonl(&code, 0x1000, &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], r#"{"address":4096,"opcode":"A64_ADD_IMM","text":"add x0, x1, #16","flags":0,"rd":0,"rn":1,"rm":0,"ra":0,"rt2":0,"rs":0,"imm":16,"offset":0}"#);
+        let keys = ["address", "opcode", "text", "flags", "rd", "rn", "rm", "ra", "rt2", "rs", "imm", "offset"];
+        let expected = [
+            ("4096", "A64_ADD_IMM", "add x0, x1, #16"),
+            ("4100", "A64_RET", "ret"),
+            ("4104", "A64_UNKNOWN", ".inst 0xffffffff"),
+            ("4108", "A64_ERROR", "error: truncated instruction: 1 trailing bytes"),
+        ];
+        for (line, (address, opcode, text)) in lines.iter().zip(expected) {
+            let members = parse_jsonl_object(line);
+            assert_eq!(members.iter().map(|(key, _)| key.as_str()).collect::<Vec<_>>(), keys, "{}", line);
+            assert_eq!((members[0].1.as_str(), members[1].1.as_str(), members[2].1.as_str()), (address, opcode, text));
+        }
+
+        let mut escaped = Vec::new();
+        write_json_str(&mut escaped, "a\"b\\c\n").unwrap();
+        assert_eq!(escaped, br#""a\"b\\c\u000a""#);
+        assert_eq!(parse_json_str(std::str::from_utf8(&escaped).unwrap()), ("a\"b\\c\n".to_string(), ""));
+    }
+
+    #[test]
+    fn instruction_bytes() {
+        assert_eq!(inst_bytes(0x91001020), [0x20, 0x10, 0x00, 0x91]); // add x0, x1, #4
+        assert_eq!(format_bytes(0x91001020), "20 10 00 91");
+        assert_eq!(format_bytes(0xd503201f), "1f 20 03 d5"); // nop
+        let decoder = Decoder::default();
+        assert_eq!(decoder.decode_buffer(&inst_bytes(0xd65f03c0)).next().unwrap().1.op, A64_RET);
+    }
+
+    #[test]
+    fn hint_names() {
+        assert_eq!(hint_name(32), "bti");
+        assert_eq!(hint_name(36), "bti j");
+        assert_eq!(hint_name(20), "csdb");
+        assert_eq!(hint_name(16), "esb");
+        assert_eq!(hint_name(0), "nop");
+        assert_eq!(hint_name(19), "hint");
+    }
+
+    #[test]
+    fn op_categories() {
+        let first_and_last = [
+            (A64_UNKNOWN, A64_UDF, "Unknown, error, undefined"),
+            (A64_ADD_IMM, A64_CMP_IMM, "Add/subtract (immediate)"),
+            (A64_MOVK, A64_MOV_IMM, "Move wide (immediate)"),
+            (A64_UDIV, A64_SUBP, "Data-processing (2 source)"),
+            (A64_CSEL, A64_CNEG, "Conditional select"),
+            (A64_MADD, A64_UMULH, "Data-processing (3 source)"),
+            (A64_LDADD, A64_CASP, "Atomic memory operations"),
+            (A64_QADD, A64_QXTN, "SIMD Saturating Integer Arithmetic (unsigned, signed)"),
+            (A64_PMUL, A64_PMULL, "SIMD Polynomial Multiply"),
+            (A64_AESD, A64_AESMC, "Cryptographic AES"),
+            (A64_SHA1C, A64_SHA256SU1, "Cryptographic SHA"),
+            (A64_BFCVT, A64_BFMMLA, "SIMD BFloat16"),
+        ];
+        for (first, last, category) in first_and_last {
+            assert_eq!(op_category(&first), category);
+            assert_eq!(op_category(&last), category);
+        }
+
+        for pair in CATEGORIES.windows(2) {
+            assert!((pair[0].0.clone() as u32) < pair[1].0.clone() as u32, "{:?} out of order", pair[1].0);
+        }
+    }
+
+    #[test]
+    fn opcode_table_is_stable() {
+        // Regenerate with UPDATE_OPCODE_TABLE=1 cargo test -p a2ir opcode_table
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/opcode_table.txt");
+        let table = dump_opcode_table();
+        if std::env::var_os("UPDATE_OPCODE_TABLE").is_some() {
+            std::fs::write(path, &table).unwrap();
+        }
+        assert_eq!(table, std::fs::read_to_string(path).unwrap(), "opcode table changed, see {}", path);
+
+        assert_eq!(table.lines().count(), all_ops().count() + 1);
+        assert!(table.contains("\nA64_ADD_IMM\tadd\tAdd/subtract (immediate)\tSequential\tS\n"));
+        assert!(table.contains("\nA64_BL\tbl\t"));
+    }
+}
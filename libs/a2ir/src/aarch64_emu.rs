@@ -0,0 +1,265 @@
+//! A single-step emulator for the data processing (immediate) group, the
+//! shifts by register and the conditional compares, an oracle for the
+//! decoder's view of these instructions.
+//!
+//! The register file holds X0 to X30 and, at index 31, the stack pointer.
+//! The zero register reads as 0 and ignores writes. NZCV uses the bit
+//! positions of NzcvMask, N is bit 3 and V bit 0.
+//!
+//! The module is optional, behind the emu feature:
+//! cargo test -p a2ir --features emu
+
+use crate::aarch64_reader::FlagMasks::{SET_FLAGS, W32};
+use crate::aarch64_reader::Op::{self, *};
+use crate::aarch64_reader::Registries::{STACK_POINTER, ZERO_REG};
+use crate::aarch64_analysis::condition_holds;
+use crate::aarch64_reader::{fad_get_cond, sext, Inst};
+
+/// Executes inst as if it were located at address 0, see step_at.
+pub fn step(inst: &Inst, regs: &mut [u64; 32], nzcv: &mut u8) -> bool {
+    return step_at(inst, 0, regs, nzcv);
+}
+
+/// Executes inst, located at address pc, on regs and nzcv. Returns false
+/// and leaves both untouched for instructions outside the data processing
+/// (immediate) group, the shifts by register and the conditional compares.
+/// The shifts by register shift by Rm modulo the register size, as the
+/// hardware does.
+pub fn step_at(inst: &Inst, pc: u64, regs: &mut [u64; 32], nzcv: &mut u8) -> bool {
+    let bits: u32 = if inst.flags & W32 != 0 { 32 } else { 64 };
+    let rn = read(regs, inst.rn, bits);
+    let lsb = inst.bfm.lsb;
+    let field = ones(inst.bfm.width);
+
+    let result = match inst.op {
+        A64_ADR => pc.wrapping_add(inst.offset as u64),
+        A64_ADRP => (pc & !0xFFF).wrapping_add(inst.offset as u64),
+        A64_ADD_IMM | A64_CMN_IMM | A64_SUB_IMM | A64_CMP_IMM => {
+            let (y, carry) = if matches!(inst.op, A64_ADD_IMM | A64_CMN_IMM) { (inst.imm, 0) } else { (!inst.imm & ones(bits), 1) };
+            let (result, flags) = add_with_carry(rn, y, carry, bits);
+            if inst.flags & SET_FLAGS != 0 {
+                *nzcv = flags;
+            }
+            result
+        }
+        A64_MOV_SP => rn,
+        A64_AND_IMM | A64_ORR_IMM | A64_EOR_IMM | A64_TST_IMM => {
+            let result = match inst.op {
+                A64_ORR_IMM => rn | inst.imm,
+                A64_EOR_IMM => rn ^ inst.imm,
+                _ => rn & inst.imm,
+            };
+            if inst.flags & SET_FLAGS != 0 {
+                *nzcv = (((result >> (bits - 1)) as u8 & 1) << 3) | (((result == 0) as u8) << 2);
+            }
+            result
+        }
+        A64_MOV_IMM => inst.imm,
+        A64_MOVK => {
+            let rd = read(regs, inst.rd, bits);
+            (rd & !(0xFFFF << inst.movk.lsl)) | ((inst.movk.imm16 as u64) << inst.movk.lsl)
+        }
+        A64_LSL_IMM => rn << inst.imm,
+        A64_LSR_IMM => rn >> inst.imm,
+        A64_ASR_IMM => (sext(rn, bits as u8) >> inst.imm) as u64,
+        A64_UBFX => (rn >> lsb) & field,
+        A64_SBFX => sext((rn >> lsb) & field, inst.bfm.width as u8) as u64,
+        A64_UBFIZ => (rn & field) << lsb,
+        A64_SBFIZ => (sext(rn & field, inst.bfm.width as u8) as u64) << lsb,
+        A64_BFXIL => (read(regs, inst.rd, bits) & !field) | ((rn >> lsb) & field),
+        A64_BFI | A64_BFC => (read(regs, inst.rd, bits) & !(field << lsb)) | ((rn & field) << lsb),
+        A64_EXTEND => {
+            let width = 8 << (inst.extend.typ & 0b11);
+            if inst.extend.typ & 0b100 != 0 { sext(rn & ones(width), width as u8) as u64 } else { rn & ones(width) }
+        }
+        A64_ROR_IMM => (rn >> inst.imm) | rn.checked_shl(bits - inst.imm as u32).unwrap_or(0),
+        A64_EXTR => {
+            let concat = ((rn as u128) << bits) | read(regs, inst.rm, bits) as u128;
+            (concat >> inst.imm) as u64
+        }
+        A64_CCMN_REG | A64_CCMP_REG | A64_CCMN_IMM | A64_CCMP_IMM => {
+            let y = if matches!(inst.op, A64_CCMN_REG | A64_CCMP_REG) { read(regs, inst.rm, bits) } else { inst.ccmp.imm5 as u64 };
+            *nzcv = if !condition_holds(fad_get_cond(inst.flags), *nzcv) {
+                inst.ccmp.nzcv as u8
+            } else if matches!(inst.op, A64_CCMN_REG | A64_CCMN_IMM) {
+                add_with_carry(rn, y, 0, bits).1
+            } else {
+                add_with_carry(rn, !y & ones(bits), 1, bits).1
+            };
+            return true; // no destination register
+        }
+        A64_LSLV | A64_LSRV | A64_ASRV | A64_RORV => {
+            let amount = read(regs, inst.rm, bits) & (bits as u64 - 1);
+            match inst.op {
+                A64_LSLV => rn << amount,
+                A64_LSRV => rn >> amount,
+                A64_ASRV => (sext(rn, bits as u8) >> amount) as u64,
+                _ => (rn >> amount) | rn.checked_shl(bits - amount as u32).unwrap_or(0),
+            }
+        }
+        _ => return false,
+    };
+
+    write(regs, inst.rd, result & ones(bits));
+    return true;
+}
+
+/// A one-line pseudo-code description of what step_at does for op, e.g.
+/// "Rd = Rn + imm" for A64_ADD_IMM, or None for the opcodes it does not
+/// model. Rn, Rm and Rd are read and written at the width of the
+/// instruction, the flag-setting variants of an opcode share its line.
+pub fn op_semantics(op: &Op) -> Option<&'static str> {
+    let text = match op {
+        A64_ADR => "Rd = pc + offset",
+        A64_ADRP => "Rd = (pc & ~0xfff) + offset",
+        A64_ADD_IMM => "Rd = Rn + imm",
+        A64_CMN_IMM => "NZCV = flags(Rn + imm)",
+        A64_SUB_IMM => "Rd = Rn - imm",
+        A64_CMP_IMM => "NZCV = flags(Rn - imm)",
+        A64_MOV_SP => "Rd = Rn",
+        A64_AND_IMM => "Rd = Rn & imm",
+        A64_ORR_IMM => "Rd = Rn | imm",
+        A64_EOR_IMM => "Rd = Rn ^ imm",
+        A64_TST_IMM => "NZCV = flags(Rn & imm)",
+        A64_MOV_IMM => "Rd = imm",
+        A64_MOVK => "Rd<lsl+15:lsl> = imm16",
+        A64_LSL_IMM => "Rd = Rn << imm",
+        A64_LSR_IMM => "Rd = Rn >> imm",
+        A64_ASR_IMM => "Rd = Rn >>s imm",
+        A64_ROR_IMM => "Rd = Rn ror imm",
+        A64_UBFX => "Rd = ZeroExtend(Rn<lsb+width-1:lsb>)",
+        A64_SBFX => "Rd = SignExtend(Rn<lsb+width-1:lsb>)",
+        A64_UBFIZ => "Rd = ZeroExtend(Rn<width-1:0>) << lsb",
+        A64_SBFIZ => "Rd = SignExtend(Rn<width-1:0>) << lsb",
+        A64_BFXIL => "Rd<width-1:0> = Rn<lsb+width-1:lsb>",
+        A64_BFI => "Rd<lsb+width-1:lsb> = Rn<width-1:0>",
+        A64_BFC => "Rd<lsb+width-1:lsb> = 0",
+        A64_EXTEND => "Rd = Extend(Rn<size-1:0>)",
+        A64_EXTR => "Rd = (Rn:Rm) >> imm",
+        A64_LSLV => "Rd = Rn << (Rm % bits)",
+        A64_LSRV => "Rd = Rn >> (Rm % bits)",
+        A64_ASRV => "Rd = Rn >>s (Rm % bits)",
+        A64_RORV => "Rd = Rn ror (Rm % bits)",
+        A64_CCMN_REG => "NZCV = cond ? flags(Rn + Rm) : nzcv",
+        A64_CCMP_REG => "NZCV = cond ? flags(Rn - Rm) : nzcv",
+        A64_CCMN_IMM => "NZCV = cond ? flags(Rn + imm5) : nzcv",
+        A64_CCMP_IMM => "NZCV = cond ? flags(Rn - imm5) : nzcv",
+        _ => return None,
+    };
+    return Some(text);
+}
+
+/// Ones in the low width bits, all ones for width 64.
+fn ones(width: u32) -> u64 {
+    return u64::MAX.checked_shr(64 - width).unwrap_or(0);
+}
+
+fn read(regs: &[u64; 32], reg: u8, bits: u32) -> u64 {
+    let value = match reg {
+        ZERO_REG => 0,
+        STACK_POINTER => regs[31],
+        _ => regs[reg as usize],
+    };
+    return value & ones(bits);
+}
+
+fn write(regs: &mut [u64; 32], reg: u8, value: u64) {
+    match reg {
+        ZERO_REG => {}
+        STACK_POINTER => regs[31] = value,
+        _ => regs[reg as usize] = value,
+    }
+}
+
+/// AddWithCarry of the A64 pseudocode on operands of the given bits: the
+/// truncated sum and its NZCV.
+fn add_with_carry(x: u64, y: u64, carry: u64, bits: u32) -> (u64, u8) {
+    let unsigned_sum = x as u128 + y as u128 + carry as u128;
+    let result = unsigned_sum as u64 & ones(bits);
+    let sign = |value: u64| (value >> (bits - 1)) & 1;
+
+    let n = sign(result) as u8;
+    let z = (result == 0) as u8;
+    let c = (unsigned_sum >> bits != 0) as u8;
+    let v = (sign(x) == sign(y) && sign(result) != sign(x)) as u8;
+    return (result, (n << 3) | (z << 2) | (c << 1) | v);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aarch64_reader::{all_ops, decode, Bfm, UNKNOWN_INST};
+
+    fn run(words: &[u32], regs: &mut [u64; 32], nzcv: &mut u8) {
+        for word in words {
+            assert!(step(&decode(*word), regs, nzcv), "{:08x} not executed", word);
+        }
+    }
+
+    #[test]
+    fn data_proc_imm_steps() {
+        let (mut regs, mut nzcv) = ([0; 32], 0);
+        run(&[0xd28000a0, 0x91000c00], &mut regs, &mut nzcv); // movz x0, #5; add x0, x0, #3
+        assert_eq!((regs[0], nzcv), (8, 0));
+
+        run(&[0xf2a00020, 0x53003c01], &mut regs, &mut nzcv); // movk x0, #1, lsl #16; uxth w1, w0
+        assert_eq!((regs[0], regs[1]), (0x10008, 8));
+        run(&[0x71002021], &mut regs, &mut nzcv); // subs w1, w1, #8
+        assert_eq!((regs[1], nzcv), (0, 0b0110));
+        run(&[0x7100043f], &mut regs, &mut nzcv); // cmp w1, #1
+        assert_eq!((regs[1], nzcv), (0, 0b1000));
+
+        run(&[0x12800002, 0x93407c43, 0xd3787c44], &mut regs, &mut nzcv); // mov w2, #-1; sxtw x3, w2; ubfiz x4, x2, #8, #32
+        assert_eq!((regs[2], regs[3], regs[4]), (0xffffffff, u64::MAX, 0xffffffff00));
+        run(&[0x93c42065, 0x72001c9f], &mut regs, &mut nzcv); // extr x5, x3, x4, #8; tst w4, #0xff
+        assert_eq!((regs[5], nzcv), (0xff000000ffffffff, 0b0100));
+
+        regs[31] = 0x8000;
+        run(&[0xd10043ff, 0x910003e6], &mut regs, &mut nzcv); // sub sp, sp, #16; mov x6, sp
+        assert_eq!((regs[31], regs[6]), (0x7ff0, 0x7ff0));
+
+        let adrp = decode(0xd0000080); // adrp x0, . + 0x12000, page of 0x1234 + 0x12000
+        assert!(step_at(&adrp, 0x1234, &mut regs, &mut nzcv));
+        assert_eq!(regs[0], 0x13000);
+        assert!(!step(&decode(0xd65f03c0), &mut regs, &mut nzcv)); // ret
+    }
+
+    #[test]
+    fn semantics_cover_the_emulated_opcodes() {
+        for op in all_ops() {
+            // a bitfield of width 0 does not decode
+            let inst = Inst { op: op.clone(), bfm: Bfm { lsb: 0, width: 8 }, ..UNKNOWN_INST };
+            let executed = step(&inst, &mut [0; 32], &mut 0);
+            assert_eq!(op_semantics(&op).is_some(), executed, "{:?}", op);
+        }
+        assert_eq!(op_semantics(&A64_ADD_IMM), Some("Rd = Rn + imm"));
+    }
+
+    #[test]
+    fn conditional_compare_flags() {
+        let (mut regs, mut nzcv) = ([0; 32], 0b0100); // Z set
+        regs[0] = 31;
+        run(&[0xfa5f0800], &mut regs, &mut nzcv); // ccmp x0, #31, #0, eq: holds, 31 - 31
+        assert_eq!(nzcv, 0b0110);
+        run(&[0x3a451824], &mut regs, &mut nzcv); // ccmn w1, #5, #4, ne: fails, nzcv := #4
+        assert_eq!(nzcv, 0b0100);
+        regs[3] = 1;
+        run(&[0xfa43a04f], &mut regs, &mut nzcv); // ccmp x2, x3, #15, ge: holds, 0 - 1
+        assert_eq!((nzcv, regs[0]), (0b1000, 31));
+    }
+
+    #[test]
+    fn shift_amount_is_modulo_register_size() {
+        let (mut regs, mut nzcv) = ([0; 32], 0);
+        regs[1] = 0x80000001;
+        regs[2] = 33;
+        run(&[0x1ac22020], &mut regs, &mut nzcv); // lsl w0, w1, w2: shift by 33 & 31
+        assert_eq!(regs[0], 2);
+        run(&[0x9ac22020], &mut regs, &mut nzcv); // lsl x0, x1, x2: shift by 33 & 63
+        assert_eq!(regs[0], 0x200000000);
+
+        regs[2] = 64 + 4;
+        run(&[0x9ac22020, 0x1ac22c23, 0x1ac22824], &mut regs, &mut nzcv); // lsl x0, x1, x2; ror w3, w1, w2; asr w4, w1, w2
+        assert_eq!((regs[0], regs[3], regs[4]), (0x800000010, 0x18000000, 0xf8000000));
+    }
+}
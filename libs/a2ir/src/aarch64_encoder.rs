@@ -0,0 +1,557 @@
+use crate::aarch64_reader::ExtendType::{SXTB, SXTH, SXTW, UXTB, UXTH};
+use crate::aarch64_reader::FlagMasks::{SET_FLAGS, W32};
+use crate::aarch64_reader::Op::*;
+use crate::aarch64_reader::Registries::{STACK_POINTER, ZERO_REG};
+use crate::aarch64_reader::Shift::SH_ROR;
+use crate::aarch64_reader::{decode, ror, Inst, Op};
+
+/// Reasons why an Inst cannot be encoded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EncodeError {
+    /// The encoder does not handle this opcode (yet).
+    Unsupported(Op),
+    /// An operand does not fit into its field, named after the ARM ARM.
+    OutOfRange(&'static str),
+}
+
+impl std::fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            EncodeError::Unsupported(op) => write!(f, "{:?} is not supported by the encoder", op),
+            EncodeError::OutOfRange(field) => write!(f, "operand does not fit the {} field", field),
+        };
+    }
+}
+
+/// Checks that encode accepts the instruction, reporting every problem
+/// rather than the first: register numbers outside 0..31 and SP, immediates
+/// too wide for their field, shifts too large for the register width and
+/// values that are no logical immediate. Ok means encode succeeds.
+pub fn validate(inst: &Inst) -> Result<(), Vec<String>> {
+    let mut problems = Vec::new();
+    let w32 = inst.flags & W32 != 0;
+    let bits: u32 = if w32 { 32 } else { 64 };
+
+    for (field, r) in [("Rd", inst.rd), ("Rn", inst.rn), ("Rm", inst.rm), ("Ra", inst.ra), ("Rt2", inst.rt2), ("Rs", inst.rs)] {
+        if reg(r, field).is_err() {
+            problems.push(format!("{}: register {} is neither 0..31 nor SP", field, r));
+        }
+    }
+
+    match inst.op {
+        A64_ADR | A64_ADRP => {
+            let scale = if inst.op == A64_ADRP { 4096 } else { 1 };
+            if inst.offset % scale != 0 {
+                problems.push(format!("immhi:immlo: offset {:#x} is not a multiple of {}", inst.offset, scale));
+            } else if field_signed(inst.offset / scale, 21, "immhi:immlo").is_err() {
+                problems.push(format!("immhi:immlo: offset {:#x} does not fit 21 bits", inst.offset));
+            }
+        }
+        A64_ADD_IMM | A64_SUB_IMM | A64_CMN_IMM | A64_CMP_IMM => {
+            let imm = inst.imm;
+            if imm >= 4096 && (imm & 0xFFF != 0 || imm >> 12 >= 4096) {
+                problems.push(format!("imm12: {:#x} is not a 12-bit immediate, optionally shifted left by 12", imm));
+            }
+        }
+        A64_AND_IMM | A64_ORR_IMM | A64_EOR_IMM | A64_TST_IMM => {
+            if encode_bitmask(inst.imm, w32).is_none() {
+                problems.push(format!("N:immr:imms: {:#x} is not a {}-bit logical immediate", inst.imm, bits));
+            }
+        }
+        A64_MOV_IMM => {
+            let mask = if w32 { 0xFFFFFFFF } else { u64::MAX };
+            let wide = inst.rd != STACK_POINTER
+                && (single_halfword(inst.imm & mask, bits).is_some() || single_halfword(!inst.imm & mask, bits).is_some());
+            if !wide && encode_bitmask(inst.imm, w32).is_none() {
+                problems.push(format!("imm16: {:#x} is neither a move wide nor a {}-bit logical immediate", inst.imm, bits));
+            }
+        }
+        A64_MOVK => {
+            if inst.movk.lsl & 0b1111 != 0 || inst.movk.lsl >= bits {
+                problems.push(format!("hw: shift {} is not a multiple of 16 below {}", inst.movk.lsl, bits));
+            }
+            if inst.movk.imm16 > 0xFFFF {
+                problems.push(format!("imm16: {:#x} does not fit 16 bits", inst.movk.imm16));
+            }
+        }
+        A64_SBFIZ | A64_SBFX | A64_ASR_IMM | A64_BFC | A64_BFI | A64_BFXIL | A64_LSL_IMM | A64_LSR_IMM
+        | A64_UBFIZ | A64_UBFX | A64_EXTEND => {
+            if let Err(e) = bitfield_fields(inst, bits) {
+                problems.push(e.to_string());
+            }
+        }
+        A64_EXTR | A64_ROR_IMM => {
+            if inst.imm >= bits as u64 {
+                problems.push(format!("imms: shift {} is not below {}", inst.imm, bits));
+            }
+        }
+        A64_AND_SHIFTED | A64_TST_SHIFTED | A64_BIC | A64_ORR_SHIFTED | A64_MOV_REG | A64_ORN | A64_MVN
+        | A64_EOR_SHIFTED | A64_EON | A64_ADD_SHIFTED | A64_CMN_SHIFTED | A64_SUB_SHIFTED | A64_NEG | A64_CMP_SHIFTED => {
+            let arithmetic = matches!(inst.op, A64_ADD_SHIFTED | A64_CMN_SHIFTED | A64_SUB_SHIFTED | A64_NEG | A64_CMP_SHIFTED);
+            if inst.shift.typ > 0b11 || (arithmetic && inst.shift.typ == SH_ROR as u32) {
+                problems.push(format!("shift: type {} is not allowed", inst.shift.typ));
+            }
+            if inst.shift.amount >= bits {
+                problems.push(format!("imm6: shift amount {} is not below {}", inst.shift.amount, bits));
+            }
+        }
+        A64_MOV_SP => {}
+        _ => problems.push(EncodeError::Unsupported(inst.op.clone()).to_string()),
+    }
+
+    // Anything the checks above miss still surfaces, by name of the field.
+    if problems.is_empty() {
+        if let Err(e) = encode(inst) {
+            problems.push(e.to_string());
+        }
+    }
+    return if problems.is_empty() { Ok(()) } else { Err(problems) };
+}
+
+/// Encodes an instruction into its binary representation. This is the
+/// inverse of decode, up to alias canonicalization: decoding the result
+/// yields an equal Inst whenever the Inst came out of the decoder.
+///
+/// Currently the Data Processing -- Immediate group and the shifted register
+/// forms of the logical and add/subtract instructions are supported.
+pub fn encode(inst: &Inst) -> Result<u32, EncodeError> {
+    let w32 = inst.flags & W32 != 0;
+    let sf: u32 = if w32 { 0 } else { 1 };
+    let bits: u32 = if w32 { 32 } else { 64 };
+
+    return match inst.op {
+        A64_ADR | A64_ADRP => {
+            let scale = if inst.op == A64_ADRP { 4096 } else { 1 };
+            if inst.offset % scale != 0 {
+                return Err(EncodeError::OutOfRange("immhi:immlo"));
+            }
+            let imm = field_signed(inst.offset / scale, 21, "immhi:immlo")?;
+            let op = if inst.op == A64_ADRP { 1 } else { 0 };
+            Ok((op << 31) | ((imm & 0b11) << 29) | (0b10000 << 24) | ((imm >> 2) << 5) | reg(inst.rd, "Rd")?)
+        }
+        A64_ADD_IMM | A64_SUB_IMM | A64_CMN_IMM | A64_CMP_IMM | A64_MOV_SP => {
+            let op = if matches!(inst.op, A64_SUB_IMM | A64_CMP_IMM) { 1 } else { 0 };
+            let set_flags = inst.flags & SET_FLAGS != 0 || matches!(inst.op, A64_CMN_IMM | A64_CMP_IMM);
+            let s = if set_flags { 1 } else { 0 };
+            let rd = if matches!(inst.op, A64_CMN_IMM | A64_CMP_IMM) { ZERO_REG } else { inst.rd };
+            let imm = if inst.op == A64_MOV_SP { 0 } else { inst.imm };
+
+            // ADD Rd, Rn, #0 with SP as operand is the MOV (to/from SP) alias;
+            // keep a plain ADD by shifting the zero instead.
+            let keep_add = inst.op == A64_ADD_IMM && !set_flags && imm == 0 && (rd == STACK_POINTER || inst.rn == STACK_POINTER);
+            let (sh, imm12) = if imm < 4096 && !keep_add {
+                (0, imm)
+            } else if imm & 0xFFF == 0 && imm >> 12 < 4096 {
+                (1, imm >> 12)
+            } else {
+                return Err(EncodeError::OutOfRange("imm12"));
+            };
+
+            Ok((sf << 31) | (op << 30) | (s << 29) | (0b100010 << 23) | (sh << 22) | ((imm12 as u32) << 10)
+                | (reg(inst.rn, "Rn")? << 5) | reg(rd, "Rd")?)
+        }
+        A64_AND_IMM | A64_ORR_IMM | A64_EOR_IMM | A64_TST_IMM => {
+            let opc = match inst.op {
+                A64_ORR_IMM => 0b01,
+                A64_EOR_IMM => 0b10,
+                A64_TST_IMM => 0b11,
+                _ => if inst.flags & SET_FLAGS != 0 { 0b11 } else { 0b00 },
+            };
+            let rd = if inst.op == A64_TST_IMM { ZERO_REG } else { inst.rd };
+            let (N, immr, imms) = match encode_bitmask(inst.imm, w32) {
+                Some(fields) => fields,
+                None => return Err(EncodeError::OutOfRange("N:immr:imms")),
+            };
+
+            Ok((sf << 31) | (opc << 29) | (0b100100 << 23) | (N << 22) | (immr << 16) | (imms << 10)
+                | (reg(inst.rn, "Rn")? << 5) | reg(rd, "Rd")?)
+        }
+        A64_MOV_IMM => {
+            // Prefer MOVZ, then MOVN, then ORR (bitmask immediate), the only
+            // one that can write to SP.
+            let mask = if w32 { 0xFFFFFFFF } else { u64::MAX };
+            let (opc, hw, imm16) = if inst.rd == STACK_POINTER {
+                return encode_mov_bitmask(inst, sf, w32);
+            } else if let Some((hw, imm16)) = single_halfword(inst.imm & mask, bits) {
+                (0b10, hw, imm16)
+            } else if let Some((hw, imm16)) = single_halfword(!inst.imm & mask, bits) {
+                (0b00, hw, imm16)
+            } else {
+                return encode_mov_bitmask(inst, sf, w32);
+            };
+
+            Ok((sf << 31) | (opc << 29) | (0b100101 << 23) | (hw << 21) | (imm16 << 5) | reg(inst.rd, "Rd")?)
+        }
+        A64_MOVK => {
+            if inst.movk.lsl & 0b1111 != 0 || inst.movk.lsl >= bits {
+                return Err(EncodeError::OutOfRange("hw"));
+            }
+            if inst.movk.imm16 > 0xFFFF {
+                return Err(EncodeError::OutOfRange("imm16"));
+            }
+
+            Ok((sf << 31) | (0b11 << 29) | (0b100101 << 23) | ((inst.movk.lsl / 16) << 21) | (inst.movk.imm16 << 5)
+                | reg(inst.rd, "Rd")?)
+        }
+        A64_SBFIZ | A64_SBFX | A64_ASR_IMM | A64_BFC | A64_BFI | A64_BFXIL | A64_LSL_IMM | A64_LSR_IMM
+        | A64_UBFIZ | A64_UBFX | A64_EXTEND => {
+            let (opc, immr, imms) = bitfield_fields(inst, bits)?;
+            let rn = if inst.op == A64_BFC { ZERO_REG } else { inst.rn };
+
+            Ok((sf << 31) | (opc << 29) | (0b100110 << 23) | (sf << 22) | (immr << 16) | (imms << 10)
+                | (reg(rn, "Rn")? << 5) | reg(inst.rd, "Rd")?)
+        }
+        A64_EXTR | A64_ROR_IMM => {
+            if inst.imm >= bits as u64 {
+                return Err(EncodeError::OutOfRange("imms"));
+            }
+            let rm = if inst.op == A64_ROR_IMM { inst.rn } else { inst.rm };
+
+            Ok((sf << 31) | (0b100111 << 23) | (sf << 22) | (reg(rm, "Rm")? << 16) | ((inst.imm as u32) << 10)
+                | (reg(inst.rn, "Rn")? << 5) | reg(inst.rd, "Rd")?)
+        }
+        A64_AND_SHIFTED | A64_TST_SHIFTED | A64_BIC | A64_ORR_SHIFTED | A64_MOV_REG | A64_ORN | A64_MVN
+        | A64_EOR_SHIFTED | A64_EON => {
+            let ands = if inst.flags & SET_FLAGS != 0 { 0b11 } else { 0b00 };
+            let (opc, N) = match inst.op {
+                A64_ORR_SHIFTED | A64_MOV_REG => (0b01, 0),
+                A64_ORN | A64_MVN => (0b01, 1),
+                A64_EOR_SHIFTED => (0b10, 0),
+                A64_EON => (0b10, 1),
+                A64_TST_SHIFTED => (0b11, 0),
+                A64_BIC => (ands, 1),
+                _ => (ands, 0),
+            };
+            let rd = if inst.op == A64_TST_SHIFTED { ZERO_REG } else { inst.rd };
+            let rn = if matches!(inst.op, A64_MOV_REG | A64_MVN) { ZERO_REG } else { inst.rn };
+
+            Ok((sf << 31) | (opc << 29) | (0b01010 << 24) | (N << 21) | shifted_operand(inst, bits)?
+                | (reg(rn, "Rn")? << 5) | reg(rd, "Rd")?)
+        }
+        A64_ADD_SHIFTED | A64_CMN_SHIFTED | A64_SUB_SHIFTED | A64_NEG | A64_CMP_SHIFTED => {
+            if inst.shift.typ == SH_ROR as u32 {
+                return Err(EncodeError::OutOfRange("shift"));
+            }
+            let op = if matches!(inst.op, A64_SUB_SHIFTED | A64_NEG | A64_CMP_SHIFTED) { 1 } else { 0 };
+            let set_flags = inst.flags & SET_FLAGS != 0 || matches!(inst.op, A64_CMN_SHIFTED | A64_CMP_SHIFTED);
+            let s = if set_flags { 1 } else { 0 };
+            let rd = if matches!(inst.op, A64_CMN_SHIFTED | A64_CMP_SHIFTED) { ZERO_REG } else { inst.rd };
+            let rn = if inst.op == A64_NEG { ZERO_REG } else { inst.rn };
+
+            Ok((sf << 31) | (op << 30) | (s << 29) | (0b01011 << 24) | shifted_operand(inst, bits)?
+                | (reg(rn, "Rn")? << 5) | reg(rd, "Rd")?)
+        }
+        _ => Err(EncodeError::Unsupported(inst.op.clone())),
+    };
+}
+
+/// Replaces the immediate of the instruction word with new_imm, for binary
+/// patching: the value of the operand as decode stores it, e.g. the imm of
+/// ADD (immediate) or the byte offset of a branch, as two's complement.
+/// Everything else stays as it is: the result must differ from word in the
+/// immediate field only, so ADD X0, X0, #4 can become #8 but not #0x2000,
+/// which needs the shifted form of imm12. Values that do not fit the field
+/// are OutOfRange, opcodes without an immediate or whose immediate cannot be
+/// patched yet are Unsupported.
+///
+/// Supported are the add/subtract and logical immediates, MOV and MOVK
+/// (wide immediate), the shifts and EXTR by immediate, ADR, ADRP and the
+/// immediate branches.
+pub fn patch_immediate(word: u32, new_imm: u64) -> Result<u32, EncodeError> {
+    let mut inst = decode(word);
+    let (field, mask): (&'static str, u32) = match inst.op {
+        A64_B | A64_BL => return patch_branch_offset(word, new_imm, 0, 26, "imm26"),
+        A64_BCOND | A64_CBZ | A64_CBNZ => return patch_branch_offset(word, new_imm, 5, 19, "imm19"),
+        A64_TBZ | A64_TBNZ => return patch_branch_offset(word, new_imm, 5, 14, "imm14"),
+        A64_ADR | A64_ADRP => {
+            inst.offset = new_imm as i64;
+            ("immhi:immlo", 0x60FFFFE0)
+        }
+        A64_ADD_IMM | A64_SUB_IMM | A64_CMN_IMM | A64_CMP_IMM => {
+            inst.imm = new_imm;
+            ("imm12", 0xFFF << 10)
+        }
+        A64_AND_IMM | A64_ORR_IMM | A64_EOR_IMM | A64_TST_IMM => {
+            inst.imm = new_imm;
+            ("N:immr:imms", 0x1FFF << 10)
+        }
+        // MOV (bitmask immediate) stays an ORR, encode would prefer MOVZ
+        A64_MOV_IMM if (word >> 23) & 0b111111 == 0b100100 => {
+            inst = Inst { op: A64_ORR_IMM, rn: ZERO_REG, imm: new_imm, ..inst };
+            ("N:immr:imms", 0x1FFF << 10)
+        }
+        A64_MOV_IMM => {
+            inst.imm = new_imm;
+            ("hw:imm16", 0x3FFFF << 5)
+        }
+        A64_MOVK => {
+            if new_imm > 0xFFFF {
+                return Err(EncodeError::OutOfRange("imm16"));
+            }
+            inst.movk.imm16 = new_imm as u32;
+            ("imm16", 0xFFFF << 5)
+        }
+        A64_LSL_IMM | A64_LSR_IMM | A64_ASR_IMM | A64_ROR_IMM | A64_EXTR => {
+            inst.imm = new_imm;
+            ("immr:imms", 0xFFF << 10)
+        }
+        _ => return Err(EncodeError::Unsupported(inst.op)),
+    };
+
+    let patched = encode(&inst).map_err(|_| EncodeError::OutOfRange(field))?;
+    if (patched ^ word) & !mask != 0 {
+        return Err(EncodeError::OutOfRange(field));
+    }
+    return Ok(patched);
+}
+
+/// Replaces the word-aligned byte offset in the bits-wide field at lsb of a
+/// branch, which the encoder does not handle yet.
+fn patch_branch_offset(word: u32, offset: u64, lsb: u32, bits: u32, field: &'static str) -> Result<u32, EncodeError> {
+    let offset = offset as i64;
+    if offset % 4 != 0 {
+        return Err(EncodeError::OutOfRange(field));
+    }
+    let imm = field_signed(offset / 4, bits, field)?;
+    let mask = ((1u32 << bits) - 1) << lsb;
+    return Ok((word & !mask) | (imm << lsb));
+}
+
+/// MOV (bitmask immediate): ORR Rd, ZR, #imm
+fn encode_mov_bitmask(inst: &Inst, sf: u32, w32: bool) -> Result<u32, EncodeError> {
+    let (N, immr, imms) = match encode_bitmask(inst.imm, w32) {
+        Some(fields) => fields,
+        None => return Err(EncodeError::OutOfRange("imm16")),
+    };
+    return Ok((sf << 31) | (0b01 << 29) | (0b100100 << 23) | (N << 22) | (immr << 16) | (imms << 10)
+        | (ZERO_REG as u32) << 5 | reg(inst.rd, "Rd")?);
+}
+
+/// The shift:Rm:imm6 fields of the shifted register forms, from Inst.shift.
+fn shifted_operand(inst: &Inst, bits: u32) -> Result<u32, EncodeError> {
+    if inst.shift.typ > 0b11 {
+        return Err(EncodeError::OutOfRange("shift"));
+    }
+    if inst.shift.amount >= bits {
+        return Err(EncodeError::OutOfRange("imm6"));
+    }
+    return Ok((inst.shift.typ << 22) | (reg(inst.rm, "Rm")? << 16) | (inst.shift.amount << 10));
+}
+
+/// Register fields hold 0..31; the SP is encoded as register 31.
+fn reg(r: u8, field: &'static str) -> Result<u32, EncodeError> {
+    return match r {
+        STACK_POINTER => Ok(31),
+        0..=31 => Ok(r as u32),
+        _ => Err(EncodeError::OutOfRange(field)),
+    };
+}
+
+/// Truncates a signed value to a bits-wide field, if it fits.
+fn field_signed(value: i64, bits: u32, field: &'static str) -> Result<u32, EncodeError> {
+    let min = -(1i64 << (bits - 1));
+    let max = (1i64 << (bits - 1)) - 1;
+    if value < min || value > max {
+        return Err(EncodeError::OutOfRange(field));
+    }
+    return Ok((value as u32) & ((1u32 << bits) - 1));
+}
+
+/// Finds hw and imm16 such that value == imm16 << (16 * hw), as encoded by MOVZ.
+fn single_halfword(value: u64, bits: u32) -> Option<(u32, u32)> {
+    for hw in 0..bits / 16 {
+        if value & !(0xFFFF << (16 * hw)) == 0 {
+            return Some((hw, ((value >> (16 * hw)) & 0xFFFF) as u32));
+        }
+    }
+    return None;
+}
+
+/// Inverse of decode_bitmask: finds N:immr:imms for a logical immediate.
+/// Not all values are expressible; in particular zero and all ones are not.
+pub fn encode_bitmask(imm: u64, w32: bool) -> Option<(u32, u32, u32)> {
+    let M: u32 = if w32 { 32 } else { 64 };
+    if w32 && imm >> 32 != 0 {
+        return None;
+    }
+
+    // Find the smallest element size the value is a replication of.
+    let mut esize = M;
+    while esize > 2 {
+        let half = esize / 2;
+        let mask = (1u64 << half) - 1;
+        if (imm & mask) != ((imm >> half) & mask) {
+            break;
+        }
+        esize = half;
+    }
+
+    let emask = if esize == 64 { u64::MAX } else { (1u64 << esize) - 1 };
+    let elem = imm & emask;
+    let ones = elem.count_ones();
+    if ones == 0 || ones == esize {
+        return None;
+    }
+
+    // The element must be a rotated run of ones.
+    let run = (1u64 << ones) - 1;
+    let R = (0..esize).find(|&r| ror(run, r, esize) == elem)?;
+
+    let N = if esize == 64 { 1 } else { 0 };
+    let imms = ((!(esize - 1) << 1) & 0b111111) | (ones - 1);
+    return Some((N, R, imms));
+}
+
+/// Recovers opc:immr:imms of SBFM, BFM and UBFM from their aliases.
+fn bitfield_fields(inst: &Inst, bits: u32) -> Result<(u32, u32, u32), EncodeError> {
+    let (lsb, width) = (inst.bfm.lsb, inst.bfm.width);
+    let opc = match inst.op {
+        A64_SBFIZ | A64_SBFX | A64_ASR_IMM => 0b00,
+        A64_BFC | A64_BFI | A64_BFXIL => 0b01,
+        A64_EXTEND if inst.extend.typ & 0b100 != 0 => 0b00,
+        _ => 0b10,
+    };
+
+    return match inst.op {
+        A64_BFXIL | A64_SBFX | A64_UBFX => {
+            if width == 0 || lsb + width > bits {
+                return Err(EncodeError::OutOfRange("imms"));
+            }
+            Ok((opc, lsb, lsb + width - 1))
+        }
+        A64_BFC | A64_BFI | A64_SBFIZ | A64_UBFIZ => {
+            if width == 0 || lsb >= bits || lsb + width > bits {
+                return Err(EncodeError::OutOfRange("immr"));
+            }
+            Ok((opc, (bits - lsb) % bits, width - 1))
+        }
+        A64_LSL_IMM => {
+            if inst.imm >= bits as u64 {
+                return Err(EncodeError::OutOfRange("immr"));
+            }
+            let shift = inst.imm as u32;
+            Ok((opc, (bits - shift) % bits, bits - 1 - shift))
+        }
+        A64_ASR_IMM | A64_LSR_IMM => {
+            if inst.imm >= bits as u64 {
+                return Err(EncodeError::OutOfRange("immr"));
+            }
+            Ok((opc, inst.imm as u32, bits - 1))
+        }
+        _ => {
+            // A64_EXTEND: SXTB, SXTH, SXTW, UXTB, UXTH
+            let imms = match inst.extend.typ as u8 {
+                UXTB | SXTB => 7,
+                UXTH | SXTH => 15,
+                SXTW if bits == 64 => 31,
+                _ => return Err(EncodeError::OutOfRange("imms")),
+            };
+            Ok((opc, 0, imms))
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn encode_known_words() {
+        for word in [0x91004020u32, 0xd0000080, 0x9200f020, 0xd2824682, 0x12800000, 0xd3407c20, 0x93c10c20, 0x8b020c20, 0x2aa10be0] {
+            assert_eq!(encode(&decode(word)), Ok(word), "{:#010x}", word);
+        }
+    }
+
+    #[test]
+    fn encode_out_of_range() {
+        let mut inst = decode(0x91004020); // add x0, x1, #16
+        inst.imm = 0x1001;
+        assert_eq!(encode(&inst), Err(EncodeError::OutOfRange("imm12")));
+
+        inst = decode(0x92400020); // and x0, x1, #1
+        inst.imm = 0;
+        assert_eq!(encode(&inst), Err(EncodeError::OutOfRange("N:immr:imms")));
+    }
+
+    #[test]
+    fn patch_immediates() {
+        let patched = patch_immediate(0x91001000, 8).unwrap(); // add x0, x0, #4
+        assert_eq!(patched, 0x91002000);
+        assert_eq!((decode(patched).imm, decode(patched).op), (8, A64_ADD_IMM));
+        assert_eq!(patch_immediate(0x91001000, 0x2000), Err(EncodeError::OutOfRange("imm12")));
+        assert_eq!(patch_immediate(0x91401000, 0x5000), Ok(0x91401400)); // add x0, x0, #4, lsl #12
+        assert_eq!(patch_immediate(0x91401000, 0x5001), Err(EncodeError::OutOfRange("imm12")));
+
+        assert_eq!(patch_immediate(0xd2824680, 0x5678), Ok(0xd28acf00)); // mov x0, #0x1234 (movz)
+        assert_eq!(patch_immediate(0xd2824680, 0x5678 << 16), Ok(0xd2aacf00));
+        assert_eq!(patch_immediate(0xd2824680, u64::MAX - 1), Err(EncodeError::OutOfRange("hw:imm16"))); // movn
+        assert_eq!(decode(patch_immediate(0xb200f3e0, 0xff).unwrap()).imm, 0xff); // mov x0, #0x5555555555555555
+        assert_eq!(patch_immediate(0xf2a00020, 0xbeef), Ok(0xf2b7dde0)); // movk x0, #1, lsl #16
+        assert_eq!(patch_immediate(0x92401c20, 0xf0), Ok(0x927c0c20)); // and x0, x1, #0xff
+
+        assert_eq!(patch_immediate(0x14000004, -8i64 as u64), Ok(0x17fffffe)); // b #16
+        assert_eq!(decode(patch_immediate(0x54000041, 0x100).unwrap()).offset, 0x100); // b.ne #8
+        assert_eq!(patch_immediate(0x36080040, 2), Err(EncodeError::OutOfRange("imm14"))); // tbz w0, #1, #8
+        assert_eq!(patch_immediate(0x36080040, 1 << 15), Err(EncodeError::OutOfRange("imm14")));
+        assert_eq!(decode(patch_immediate(0xd0000080, 0x3000).unwrap()).offset, 0x3000); // adrp x0, #0x12000
+
+        assert_eq!(patch_immediate(0x8b020020, 1), Err(EncodeError::Unsupported(A64_ADD_SHIFTED))); // add x0, x1, x2
+    }
+
+    #[test]
+    fn validate_reports_all_problems() {
+        let mut inst = decode(0x91004020); // add x0, x1, #16
+        assert_eq!(validate(&inst), Ok(()));
+        inst.imm = 0x1001;
+        assert_eq!(validate(&inst), Err(vec!["imm12: 0x1001 is not a 12-bit immediate, optionally shifted left by 12".to_string()]));
+        inst.rn = 40;
+        assert_eq!(validate(&inst).unwrap_err().len(), 2);
+
+        let mut inst = decode(0x8b020c20); // add x0, x1, x2, lsl #3
+        inst.flags |= W32;
+        inst.shift.amount = 40;
+        assert_eq!(validate(&inst), Err(vec!["imm6: shift amount 40 is not below 32".to_string()]));
+
+        let mut inst = decode(0x92400020); // and x0, x1, #1
+        inst.imm = 0x5;
+        assert_eq!(validate(&inst), Err(vec!["N:immr:imms: 0x5 is not a 64-bit logical immediate".to_string()]));
+
+        let inst = decode(0xd3403c20); // ubfx x0, x1, #0, #16
+        assert_eq!(validate(&inst), Ok(()));
+        assert_eq!(validate(&decode(0x0e20d400)), Err(vec!["A64_FADD_VEC is not supported by the encoder".to_string()]));
+    }
+
+    proptest! {
+        // Roughly a third of the random words hit reserved fields and get rejected.
+        #![proptest_config(ProptestConfig { cases: 20000, max_global_rejects: 100000, ..ProptestConfig::default() })]
+
+        /// Any allocated word of the Data Processing -- Immediate group (bits
+        /// 26..28 = 100) must survive a trip through encode.
+        #[test]
+        fn data_proc_imm_round_trip(fields in any::<u32>()) {
+            let word = (fields & !(0b111 << 26)) | (0b100 << 26);
+            let inst = decode(word);
+            prop_assume!(inst.op != A64_UNKNOWN && inst.op != A64_ERROR);
+            prop_assert_eq!(validate(&inst), Ok(()), "{:#010x}", word);
+
+            let encoded = encode(&inst);
+            prop_assert!(encoded.is_ok(), "{:#010x}: {:?}", word, encoded);
+            prop_assert_eq!(decode(encoded.unwrap()), inst, "{:#010x}", word);
+        }
+
+        /// Logical and add/subtract (shifted register), bits 25..28 = 0101:
+        /// the shift type and amount must survive a trip through encode.
+        #[test]
+        fn shifted_register_round_trip(fields in any::<u32>()) {
+            let word = (fields & !(0b1111 << 25)) | (0b0101 << 25);
+            let inst = decode(word);
+            prop_assume!(inst.op != A64_UNKNOWN);
+
+            let encoded = encode(&inst);
+            prop_assert!(encoded.is_ok(), "{:#010x}: {:?}", word, encoded);
+            prop_assert_eq!(decode(encoded.unwrap()), inst, "{:#010x}", word);
+        }
+    }
+}
@@ -1,8 +1,11 @@
+use std::collections::BTreeMap;
+use std::io::{ErrorKind, Read};
+
 use crate::aarch64_reader::ExtendType::{SXTB, SXTH, SXTW, UXTB, UXTH};
-use crate::aarch64_reader::FlagMasks::{SET_FLAGS, W32};
-use crate::aarch64_reader::Op::{A64_ADD_IMM, A64_ADR, A64_ADRP, A64_AND_IMM, A64_ASR_IMM, A64_BFC, A64_BFI, A64_BFM, A64_BFXIL, A64_CMN_IMM, A64_CMP_IMM, A64_EOR_IMM, A64_EXTEND, A64_EXTR, A64_LSL_IMM, A64_LSR_IMM, A64_MOV_IMM, A64_MOV_SP, A64_MOVK, A64_ORR_IMM, A64_ROR_IMM, A64_SBFIZ, A64_SBFM, A64_SBFX, A64_SUB_IMM, A64_TST_IMM, A64_UBFIZ, A64_UBFM, A64_UBFX, A64_UNKNOWN};
+use crate::aarch64_reader::FlagMasks::{SET_FLAGS, SIMD_ROUND, SIMD_SCALAR, SIMD_SIGNED, W32};
+use crate::aarch64_reader::Op::{A64_ADD_IMM, A64_ADR, A64_ADRP, A64_AND_IMM, A64_ASR_IMM, A64_BFC, A64_BFI, A64_BFM, A64_BFXIL, A64_CMN_IMM, A64_CMP_IMM, A64_EOR_IMM, A64_EXTEND, A64_EXTR, A64_LSL_IMM, A64_LSR_IMM, A64_MOV_IMM, A64_MOV_SP, A64_MOVK, A64_ORR_IMM, A64_ROR_IMM, A64_SBFIZ, A64_SBFM, A64_SBFX, A64_SUB_IMM, A64_TST_IMM, A64_UBFIZ, A64_UBFM, A64_UBFX};
 use crate::aarch64_reader::OpKind::{AddSub, AddSubTags, Bitfield, Extract, Logic, Move, PCRelAddr, Unknown};
-use crate::aarch64_reader::Registries::{STACK_POINTER, ZERO_REG};
+pub use crate::aarch64_reader::Registries::{is_sp, is_zr, STACK_POINTER, ZERO_REG};
 
 ///Register 31's interpretation is up to the instruction. Many interpret it as the
 ///zero register ZR/WZR. Reading to it yields a zero, writing discards the result.
@@ -11,11 +14,36 @@ use crate::aarch64_reader::Registries::{STACK_POINTER, ZERO_REG};
 ///We split up this overloaded register: when we encounter R31 and interpret it as
 ///the stack pointer, we assign a different number. This way, the user does not
 ///need to know which instructions use the SP and which use the ZR.
-mod Registries {
+pub mod Registries {
     pub const ZERO_REG: u8 = 31;
     pub const STACK_POINTER: u8 = 100;
+
+    /// Is reg, as found in Inst.rd, Inst.rn, ..., the stack pointer SP/WSP?
+    pub fn is_sp(reg: u8) -> bool {
+        return reg == STACK_POINTER;
+    }
+
+    /// Is reg the zero register XZR/WZR?
+    pub fn is_zr(reg: u8) -> bool {
+        return reg == ZERO_REG;
+    }
+}
+
+/// Declares Op together with ALL_OPS, so that the list of every opcode
+/// cannot fall out of step with the enum.
+macro_rules! opcodes {
+    ($(#[$attr:meta])* pub enum Op { $($(#[$doc:meta])* $name:ident,)* }) => {
+        $(#[$attr])*
+        pub enum Op {
+            $($(#[$doc])* $name,)*
+        }
+
+        /// Every opcode, in declaration order.
+        pub const ALL_OPS: &[Op] = &[$(Op::$name),*];
+    };
 }
 
+opcodes! {
 /// Opcodes ordered and grouped according to the Top-level Encodings
 /// of the A64 Instruction Set Architecture (ARMv8-A profile) document,
 /// pages 1406-1473.
@@ -32,12 +60,13 @@ mod Registries {
 /// condition encoded in the Inst.flags field. The various addressing
 /// modes of loads and stores are encoded similarly. See the Inst
 /// structure for more detail.
-#[derive(Clone, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[repr(u32)]
 pub enum Op {
     A64_UNKNOWN,
     /// unknown instruction (or Op field not set, by accident), Inst.imm contains raw binary instruction
     A64_ERROR,
-    /// invalid instruction, Inst.error contains error string
+    /// invalid instruction, Inst.error says why
     A64_UDF,
     /// throws undefined exception
 
@@ -106,6 +135,7 @@ pub enum Op {
     /*** Branches, Exception Generating and System Instructions ***/
 
     A64_BCOND,
+    /// B.cond label -- Inst.flags.cond; Inst.offset
 
     /// Exception generation
     ///
@@ -149,26 +179,27 @@ pub enum Op {
     A64_SYSL,
     /// SYSL Xt, #op1, Cn, Cm, #op2
 
-    /// System register move -- Inst.ldst.rt := Xt; Inst.imm := sysreg
+    /// System register move -- Inst.rd := Xt; Inst.imm := sysreg, see
+    /// system_register_move
     A64_MSR_REG,
     /// MSR <sysreg>, Xt
     A64_MRS,
     /// MRS Xt, <sysreg>
 
-    /// Unconditional branch (register)
+    /// Unconditional branch (register) -- Inst.rn := Xn
     A64_BR,
     A64_BLR,
     A64_RET,
 
-    /// Unconditional branch (immediate)
+    /// Unconditional branch (immediate) -- Inst.offset
     A64_B,
     A64_BL,
 
-    /// Compare and branch (immediate)
+    /// Compare and branch (immediate) -- Inst.rd := Rt; Inst.offset
     A64_CBZ,
     A64_CBNZ,
 
-    /// Test and branch (immediate) -- Inst.tbz
+    /// Test and branch (immediate) -- Inst.rd := Rt; Inst.tbz
     A64_TBZ,
     A64_TBNZ,
 
@@ -181,6 +212,8 @@ pub enum Op {
     A64_LSRV,
     A64_ASRV,
     A64_RORV,
+    /// LSLV, LSRV, ASRV, RORV Rd, Rn, Rm -- the shift amount is Rm modulo the register size,
+    /// only its low 5 (W) or 6 (X) bits are used. RORV is the only rotate by register.
     A64_CRC32B,
     A64_CRC32H,
     A64_CRC32W,
@@ -197,7 +230,26 @@ pub enum Op {
     A64_REV,
     A64_REV32,
     A64_CLZ,
+    /// Leading zero bits of Rn: 0 to 32 (W32) or 64, the width for Rn == 0
     A64_CLS,
+    /// Leading bits of Rn equal to the sign bit, not counting the sign bit: 0 to 31 (W32) or 63
+
+    /// Pointer authentication -- Inst.rd := pointer, Inst.rn := modifier
+    ///
+    /// The Z variants (e.g. PACIZA) use ZR as modifier. The variants in the
+    /// hint space (e.g. PACIASP, AUTIA1716) are decoded to the same opcodes,
+    /// with their implicit registers filled in.
+    A64_PACIA,
+    A64_PACIB,
+    A64_PACDA,
+    A64_PACDB,
+    A64_AUTIA,
+    A64_AUTIB,
+    A64_AUTDA,
+    A64_AUTDB,
+    A64_XPACI,
+    /// strip the authentication code, Inst.rn unused
+    A64_XPACD,
 
     /// Logical (shifted register)
     A64_AND_SHIFTED,
@@ -238,10 +290,18 @@ pub enum Op {
     A64_NGC,
     /// SBC alias (Rd := ZR, predicate: Rd == RR)
 
-    /// Rotate right into flags
+    /// Rotate right into flags -- Inst.rmif
+    ///
+    /// RMIF Xn, #ror, #mask rotates Xn right by Inst.rmif.ror and copies its
+    /// low four bits into those of N, Z, C, V (bits 3..0) selected by
+    /// Inst.rmif.mask. The other flags keep their value.
     A64_RMIF,
 
     /// Evaluate into flags
+    ///
+    /// SETF8/SETF16 Wn set N to bit 7/15 of Wn, Z if its low 8/16 bits are
+    /// zero and V to bit 8/16 XOR bit 7/15, i.e. whether the value overflowed
+    /// the signed byte/halfword. C is left unchanged.
     A64_SETF8,
     A64_SETF16,
 
@@ -252,6 +312,8 @@ pub enum Op {
     /// Conditional compare (immediate)
     A64_CCMN_IMM,
     A64_CCMP_IMM,
+    /// CCMP Rn, #imm5, #nzcv, cond -- NZCV ← cond ? flags of Rn - imm5 : nzcv (CCMN: Rn + imm5).
+    /// Inst.ccmp.imm5 := the zero-extended immediate 0..31, Inst.ccmp.nzcv, Inst.flags.cond.
 
     /// Conditional select
     A64_CSEL,
@@ -269,7 +331,7 @@ pub enum Op {
     A64_CNEG,
     /// CSNEG alias (cond := invert(cond), predicate: Rm == Rn)
 
-    /// Data-processing (3 source)
+    /// Data-processing (3 source) -- Inst.ra := Ra
     A64_MADD,
     A64_MUL,
     /// MADD alias (Ra omitted, predicate: Ra == ZR)
@@ -364,10 +426,11 @@ pub enum Op {
     /// Load/store register (register offset)        -- AM_OFF_REG, AM_OFF_EXT
     /// Load/store register (unsigned immediate)     -- AM_OFF_IMM
     /// Load/store register (unscaled immediate)     -- AM_OFF_IMM
+    /// LDAPR/STLR (unscaled immediate)              -- AM_OFF_IMM
     A64_LDR,
-    /// LDR, LDAR, LDLAR, LDUR
+    /// LDR, LDAR, LDLAR, LDUR, LDAPUR
     A64_STR,
-    /// STR, STLR, STLLR, STUR
+    /// STR, STLR, STLLR, STUR, STLUR
     A64_LDR_FP,
     A64_STR_FP,
 
@@ -405,6 +468,11 @@ pub enum Op {
     /// Compare and Swap (actually from Exclusive group)
     A64_CASP,
     /// Compare and Swap Pair of (double)words (actually from Exclusive group)
+    ///
+    /// CAS and CASP compare the memory with Inst.rs and, if equal, store
+    /// Inst.rd := Rt; either way the value loaded is written back to Inst.rs.
+    /// They are ordered like the atomics above. For CASP both are the first,
+    /// even register of a pair.
 
     /*** Data Processing -- Scalar Floating-Point and Advanced SIMD ***/
 
@@ -444,7 +512,8 @@ pub enum Op {
     /// Round to integral (any size, 32-bit, or 64-bit)
     A64_FRINT_VEC,
     A64_FRINTX,
-    /// ---- Exact (throws Inexact exception on failure)
+    /// ---- Exact (throws Inexact exception on failure); the rounding mode is
+    /// always FPR_CURRENT. FRINT32X and FRINT64X are FRINTX with bits 32, 64
     A64_FRINTX_VEC,
     A64_FCVT_H,
     /// Convert from any precision to Half
@@ -453,11 +522,11 @@ pub enum Op {
     A64_FCVT_D,
     /// -------------------------- to Double
     A64_FCVTL,
-    /// Extend to higher precision (vector)
+    /// Extend to higher precision (vector); Inst.flags.vec := arrangement of the narrow source
     A64_FCVTN,
-    /// Narrow to lower precision  (vector)
+    /// Narrow to lower precision  (vector); Inst.flags.vec := arrangement of the narrow destination
     A64_FCVTXN,
-    /// Narrow to lower precision, round to odd (vector)
+    /// Narrow to lower precision, round to odd (vector); Inst.frint.mode := FPR_ODD
 
     /// Floating-Point Computation (scalar)
     A64_FABS,
@@ -512,9 +581,9 @@ pub enum Op {
     A64_FMOV_REG,
     /// SIMD&FP ←→ SIMD&FP
     A64_FMOV_IMM,
-    /// SIMD&FP ← 8-bit float immediate (see VFPExpandImm)
+    /// SIMD&FP ← 8-bit float immediate (see VFPExpandImm); Inst.fimm := the value
     A64_FMOV_VEC,
-    /// vector ← 8-bit imm ----; replicate imm to all lanes
+    /// vector ← 8-bit imm ----; replicate imm to all lanes; Inst.fimm := the value of a lane
 
     /// SIMD Floating-Point Compare
     A64_FCMEQ_REG,
@@ -531,6 +600,7 @@ pub enum Op {
     /// SIMD Simple Floating-Point Computation (vector <op> vector, vector <op> vector[i])
     A64_FABS_VEC,
     A64_FABD_VEC,
+    /// also the scalar FABD (SIMD_SCALAR), like the other Advanced SIMD scalar ops
     A64_FNEG_VEC,
     A64_FSQRT_VEC,
     A64_FMUL_ELEM,
@@ -555,23 +625,38 @@ pub enum Op {
 
     /// SIMD Floating-Point Fused Multiply
     A64_FMLA_ELEM,
+    /// Inst.imm := index; the sources and destination share the arrangement,
+    /// unlike the widening FMLAL_ELEM
     A64_FMLA_VEC,
     A64_FMLAL_ELEM,
+    /// Inst.flags.vec = arrangement of destination (2s, 4s); sources are the lower halves (2h, 4h)
+    /// Inst.imm = index (0-7, Rm is V0-V15)
     A64_FMLAL_VEC,
+    /// Inst.flags.vec = arrangement of destination (2s, 4s); sources are the lower halves (2h, 4h)
     A64_FMLAL2_ELEM,
+    /// Like FMLAL_ELEM, reading the upper halves of the sources
     A64_FMLAL2_VEC,
+    /// Like FMLAL_VEC, reading the upper halves of the sources
     A64_FCMLA_ELEM,
     /// Inst.imm := rotation in degrees (0, 90, 180, 270)
     A64_FCMLA_VEC,
     /// ---
     A64_FMLS_ELEM,
+    /// See FMLA_ELEM
     A64_FMLS_VEC,
     A64_FMLSL_ELEM,
+    /// See FMLAL_ELEM
     A64_FMLSL_VEC,
+    /// See FMLAL_VEC
     A64_FMLSL2_ELEM,
+    /// See FMLAL2_ELEM
     A64_FMLSL2_VEC,
+    /// See FMLAL2_VEC
 
     /// SIMD Floating-Point Computation (reduce)
+    ///
+    /// FADDP, FMAXP, FMAXNMP, FMINP and FMINNMP are the scalar forms, e.g.
+    /// FADDP Sd, Vn.2S, with SIMD_SCALAR set; the _VEC ones are the vectors.
     A64_FADDP,
     A64_FADDP_VEC,
     A64_FMAXP,
@@ -617,17 +702,21 @@ pub enum Op {
     A64_REV64_VEC,
     A64_SHL_IMM,
     A64_SHL_REG,
-    /// SSHL, USHL, SRSHL, URSHL
+    /// SSHL, USHL, SRSHL, URSHL; shifts each lane of Vn by the signed low
+    /// byte of the same lane of Vm, a negative amount shifts right
+    /// (arithmetic if SIMD_SIGNED, rounding if SIMD_ROUND)
     A64_SHLL,
-    /// SSHLL, USSHL
+    /// SHLL, SSHLL, USHLL; Inst.flags.vec := arrangement of the narrow source
     A64_SHR,
     /// SSHR, USHR, SRSHR, URSHR
     A64_SHRN,
-    /// SHRN, RSHRN
+    /// SHRN, RSHRN; Inst.flags.vec := arrangement of the narrow destination
     A64_SRA,
     /// SSRA, USRA, SRSRA, URSRA
     A64_SLI,
+    /// Inst.imm := shift, the low Inst.imm bits of each element of Vd are kept
     A64_SRI,
+    /// Inst.imm := shift, the high Inst.imm bits of each element of Vd are kept
     A64_XAR,
     /// ARMv8.2-SHA
 
@@ -637,18 +726,21 @@ pub enum Op {
     A64_DUP_ELEM,
     /// ∀k < lanes: Dst[k] ← Src[i] (or if Dst is scalar: Dst ← Src[i])
     A64_DUP_GPR,
-    /// ∀k < lanes: Dst[k] ← Xn
+    /// ∀k < lanes: Dst[k] ← Xn; W32 unless the arrangement is 2D
     A64_EXT,
     A64_INS_ELEM,
-    /// Dst[j] ← Src[i], (i, j stored in Inst.ins_elem)
+    /// Dst[j] ← Src[i], (i, j stored in Inst.ins_elem); Inst.flags.vec :=
+    /// the 128-bit arrangement of the element size
     A64_INS_GPR,
     /// Dst[i] ← Xn
     A64_MOVI,
-    /// includes MVNI
+    /// includes MVNI; Inst.imm := the constant of a single lane, after shifting and (for MVNI)
+    /// inverting within the lane width, see simd_imm_u128 for the whole register
     A64_SMOV,
-    /// Xd ← sext(Src[i])
+    /// Xd ← sext(Src[i]) -- Inst.imm := index i; W32 for a W destination;
+    /// Inst.flags.vec := the 128-bit arrangement of the element size
     A64_UMOV,
-    /// Xd ← Src[i]
+    /// Xd ← Src[i] -- like SMOV; the destination is X only for D elements
     A64_TBL,
     /// Inst.imm := #regs of table ∈ {1,2,3,4}
     A64_TBX,
@@ -688,28 +780,35 @@ pub enum Op {
     A64_ABDL,
     A64_ABA,
     A64_ABAL,
+    /// SABAL, UABAL; Inst.flags.vec := arrangement of the narrow sources,
+    /// Vd accumulates (see reads_destination)
 
     A64_NEG_VEC,
 
     A64_MUL_ELEM,
     A64_MUL_VEC,
     A64_MULL_ELEM,
+    /// SMULL, UMULL; Inst.flags.vec := arrangement of the narrow source
     A64_MULL_VEC,
 
     A64_ADD_VEC,
     A64_ADDHN,
+    /// ADDHN, RADDHN; Inst.flags.vec := arrangement of the narrow destination
     A64_ADDL,
     A64_ADDW,
     A64_HADD,
 
     A64_SUB_VEC,
     A64_SUBHN,
+    /// SUBHN, RSUBHN; Inst.flags.vec := arrangement of the narrow destination
     A64_SUBL,
     A64_SUBW,
     A64_HSUB,
 
     A64_MAX_VEC,
+    /// SMAX, UMAX
     A64_MIN_VEC,
+    /// SMIN, UMIN
 
     A64_DOT_ELEM,
     A64_DOT_VEC,
@@ -718,6 +817,7 @@ pub enum Op {
     /// SIMD Integer Stepwise (both are unsigned exclusive)
     A64_URECPE,
     A64_URSQRTE,
+    /// Inst.flags.vec = 2s or 4s, the only arrangements of both
 
     /// SIMD Integer Fused Multiply
     A64_MLA_ELEM,
@@ -725,11 +825,11 @@ pub enum Op {
     A64_MLS_ELEM,
     A64_MLS_VEC,
     A64_MLAL_ELEM,
-    /// SMLAL, UMLAL
+    /// SMLAL, UMLAL; Inst.flags.vec := arrangement of the narrow sources
     A64_MLAL_VEC,
     /// SMLAL, UMLAL
     A64_MLSL_ELEM,
-    /// SMLSL, UMLSL
+    /// SMLSL, UMLSL; Inst.flags.vec := arrangement of the narrow sources
     A64_MLSL_VEC,
     /// SMLSL, UMLSL
 
@@ -740,31 +840,48 @@ pub enum Op {
     /// Concatenate Vn:Vm, then add pairwise and store result in Vd
     A64_ADDV,
     A64_ADALP,
+    /// SADALP, UADALP; accumulates into Vd. Inst.flags.vec := arrangement of the narrow source
     A64_ADDLP,
+    /// SADDLP, UADDLP; Inst.flags.vec := arrangement of the narrow source
     A64_ADDLV,
     A64_MAXP,
+    /// SMAXP, UMAXP
     A64_MAXV,
+    /// SMAXV, UMAXV; SIMD_SCALAR, the result is one element. Inst.flags.vec := arrangement of the source
     A64_MINP,
+    /// SMINP, UMINP
     A64_MINV,
+    /// SMINV, UMINV; SIMD_SCALAR, the result is one element. Inst.flags.vec := arrangement of the source
 
     /// SIMD Saturating Integer Arithmetic (unsigned, signed)
+    ///
+    /// The scalar forms (SQADD B0, B1, B2) set SIMD_SCALAR, with the element
+    /// size as precision in Inst.flags.vec.
     A64_QADD,
+    /// SQADD, UQADD
     A64_QABS,
     A64_SUQADD,
     A64_USQADD,
     A64_QSHL_IMM,
+    /// SQSHL, UQSHL; Inst.imm := left shift
     A64_QSHL_REG,
+    /// SQSHL, UQSHL, SQRSHL, UQRSHL; shift by register like SHL_REG
     A64_QSHRN,
+    /// SQSHRN, UQSHRN, SQRSHRN, UQRSHRN
     A64_QSUB,
+    /// SQSUB, UQSUB
     A64_QXTN,
+    /// SQXTN, UQXTN; Inst.flags.vec := arrangement of the narrow destination
 
     /// SIMD Saturating Integer Arithmetic (signed exclusive)
     A64_SQABS,
     A64_SQADD,
 
     A64_SQDMLAL_ELEM,
+    /// Inst.flags.vec := arrangement of the narrow source
     A64_SQDMLAL_VEC,
     A64_SQDMLSL_ELEM,
+    /// Inst.flags.vec := arrangement of the narrow source
     A64_SQDMLSL_VEC,
 
     A64_SQDMULH_ELEM,
@@ -772,7 +889,7 @@ pub enum Op {
     A64_SQDMULH_VEC,
     /// SQDMULH, SQRDMULH
     A64_SQDMULL_ELEM,
-    /// SQDMULL, SQRDMULL
+    /// SQDMULL, SQRDMULL; Inst.flags.vec := arrangement of the narrow source
     A64_SQDMULL_VEC,
     /// SQDMULL, SQRDMULL
 
@@ -783,15 +900,76 @@ pub enum Op {
     A64_SQRDMLAH_VEC,
     A64_SQRDMLSH_ELEM,
     A64_SQRDMLSH_VEC,
+    /// FEAT_RDM; SIMD_ROUND is always set. The _ELEM forms store the index in Inst.imm
 
     A64_SQSHLU,
+    /// Inst.imm := left shift; saturates the signed source elements to the
+    /// unsigned range, SIMD_SIGNED is not set
     A64_SQSHRUN,
     /// SQSHRUN, SQRSHRUN
     A64_SQXTUN,
+    /// Saturates the signed source elements to the unsigned range of the
+    /// destination; Inst.flags.vec := arrangement of the narrow destination
 
     /// SIMD Polynomial Multiply
     A64_PMUL,
     A64_PMULL,
+    /// Inst.flags.vec := arrangement of the sources (8B, 16B, 1D, 2D); the destination is 8H or 1Q
+
+    /// Cryptographic AES (FEAT_AES); Inst.flags.vec := VA_16B
+    A64_AESD,
+    /// AESD Vd.16B, Vn.16B -- Vd ← AESInvSubBytes(AESInvShiftRows(Vd ⊕ Vn))
+    A64_AESE,
+    /// AESE Vd.16B, Vn.16B -- Vd ← AESSubBytes(AESShiftRows(Vd ⊕ Vn))
+    A64_AESIMC,
+    /// AESIMC Vd.16B, Vn.16B -- Vd ← AESInvMixColumns(Vn)
+    A64_AESMC,
+    /// AESMC Vd.16B, Vn.16B -- Vd ← AESMixColumns(Vn)
+
+    /// Cryptographic SHA (FEAT_SHA1, FEAT_SHA256); Inst.flags.vec := VA_4S
+    A64_SHA1C,
+    /// SHA1C Qd, Sn, Vm.4S -- hash update, choose; SHA1P and SHA1M alike
+    A64_SHA1H,
+    /// SHA1H Sd, Sn -- fixed rotate
+    A64_SHA1M,
+    A64_SHA1P,
+    A64_SHA1SU0,
+    /// SHA1SU0 Vd.4S, Vn.4S, Vm.4S -- schedule update 0
+    A64_SHA1SU1,
+    /// SHA1SU1 Vd.4S, Vn.4S -- schedule update 1
+    A64_SHA256H,
+    /// SHA256H Qd, Qn, Vm.4S -- hash update, part 1; SHA256H2 part 2
+    A64_SHA256H2,
+    A64_SHA256SU0,
+    /// SHA256SU0 Vd.4S, Vn.4S -- schedule update 0
+    A64_SHA256SU1,
+    /// SHA256SU1 Vd.4S, Vn.4S, Vm.4S -- schedule update 1
+
+    /// SIMD BFloat16 (FEAT_BF16)
+    A64_BFCVT,
+    /// Hd := BFloat16(Sn)
+    A64_BFCVTN,
+    /// BFCVTN, BFCVTN2; Inst.flags.vec := arrangement of the narrow destination (4H, 8H)
+    A64_BFDOT_ELEM,
+    /// Inst.imm := index of the pair of elements in Vm (0-3)
+    A64_BFDOT_VEC,
+    /// Inst.flags.vec = arrangement of destination (2s, 4s); sources are (4h, 8h)
+    A64_BFMLALB_ELEM,
+    /// Inst.imm := index (0-7, Rm is V0-V15)
+    A64_BFMLALB_VEC,
+    /// Vd.4S += the even elements of Vn.8H × Vm.8H
+    A64_BFMLALT_ELEM,
+    /// Inst.imm := index (0-7, Rm is V0-V15)
+    A64_BFMLALT_VEC,
+    /// Vd.4S += the odd elements of Vn.8H × Vm.8H
+    A64_BFMMLA,
+    // Vd.4S += Vn (2×4 matrix, 8H) × Vm (4×2 matrix, 8H)
+}
+}
+
+/// Every opcode, in declaration order; see ALL_OPS.
+pub fn all_ops() -> impl Iterator<Item = Op> {
+    return ALL_OPS.iter().cloned();
 }
 
 /// The condition bits used by conditial branches, selects and compares, stored in the
@@ -855,9 +1033,10 @@ pub mod Shift {
 /// (#4 for 128 bits (SIMD), #3 for 64 bits, #2 for 32 bits, #1 for
 /// 16 bits, #0 for 8 bits) and is used for array indexing:
 ///
-///     u64 a[128];
-///     u64 x0 = a[i]; → ldr x0, [a, i, LSL #3]
-///
+/// ```text
+/// u64 a[128];
+/// u64 x0 = a[i]; → ldr x0, [a, i, LSL #3]
+/// ```
 pub mod AddrMode {
     /// [base] -- used by atomics, exclusive, ordered load/stores → check Inst.ldst_order
     pub const AM_SIMPLE: u8 = 0;
@@ -877,7 +1056,7 @@ pub mod AddrMode {
 
 /// Memory ordering semantics for Atomic instructions and the Load/Stores in the
 /// Exclusive group.
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub enum MemOrdering {
     MO_NONE,
     /// Load-Acquire -- sequentially consistent Acquire
@@ -954,7 +1133,7 @@ pub mod VectorArrangement {
 /// Floating-point rounding mode. See shared/functions/float/fprounding/FPRounding
 /// in the shared pseudocode functions of the A64 ISA documentation. The letter
 /// is the one used in the FCVT* mnemonics.
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub enum FPRounding {
     /// "Current rounding mode"
     FPR_CURRENT,
@@ -987,7 +1166,7 @@ pub mod ExtendType {
 }
 
 /// PstateField: encodes which PSTATE bits the MSR_IMM instruction modifies.
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub enum PStateField {
     PSF_UAO,
     PSF_PAN,
@@ -1011,133 +1190,136 @@ pub mod FlagMasks {
     pub const SIMD_ROUND: u8 = 1 << 7;
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Movk {
-    imm16: u32,
-    lsl: u32,
+    pub imm16: u32,
+    pub lsl: u32,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Bfm {
-    lsb: u32,
-    width: u32,
+    pub lsb: u32,
+    pub width: u32,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Ccmp {
-    nzcv: u32,
-    imm5: u32,
+    pub nzcv: u32,
+    pub imm5: u32,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Sys {
-    op1: u16,
-    op2: u16,
-    crn: u16,
-    crm: u16,
+    pub op1: u16,
+    pub op2: u16,
+    pub crn: u16,
+    pub crm: u16,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct MsrImm {
-    psfld: u32,
-    imm: u32,
+    pub psfld: u32,
+    pub imm: u32,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Tbz {
-    offset: i32,
-    bit: u32,
+    pub offset: i32,
+    pub bit: u32,
 }
 
-#[derive(Clone)]
+/// The shifted register operand `Rm, <typ> #amount` of the shifted register
+/// forms of the logical and add/subtract instructions, stored in Inst.shift.
+/// typ is one of the Shift constants, amount is 0..63 (0..31 for W registers).
+#[derive(Clone, Debug, PartialEq)]
 pub struct InstShift {
-    typ: u32,
-    amount: u32,
+    pub typ: u32,
+    pub amount: u32,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Rmif {
-    mask: u32,
-    ror: u32,
+    pub mask: u32,
+    pub ror: u32,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Extend {
-    typ: u32,
-    lsl: u32,
+    pub typ: u32,
+    pub lsl: u32,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct LdstOrder {
-    load: u16,
-    store: u16,
-    rs: u8,
+    pub load: u16,
+    pub store: u16,
+    pub rs: u8,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct SimdLdst {
-    nreg: u32,
-    index: u16,
-    offset: i16,
+    pub nreg: u32,
+    pub index: u16,
+    pub offset: i16,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Fcvt {
-    mode: u32,
-    fbits: u16,
-    sgn: u16,
+    pub mode: u32,
+    pub fbits: u16,
+    pub sgn: u16,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Frint {
-    mode: u32,
-    bits: u32,
+    pub mode: u32,
+    pub bits: u32,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct InsElem {
-    dst: u32,
-    src: u32,
+    pub dst: u32,
+    pub src: u32,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct FcmlaElem {
-    idx: u32,
-    rot: u32,
+    pub idx: u32,
+    pub rot: u32,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Inst {
-    op: Op,
-    flags: u8,
-    rd: u8,
-    rn: u8,
-    rm: u8,
-    rt2: u8,
-    rs: u8,
-    imm: u64,
-    fimm: f64,
-    offset: i64,
-    ra: u8,
-    error: String,
-    movk: Movk,
-    bfm: Bfm,
-    ccmp: Ccmp,
-    sys: Sys,
-    msr_imm: MsrImm,
-    tbz: Tbz,
-    shift: u8,
-    rmif: Rmif,
-    extend: Extend,
-    ldst_order: LdstOrder,
-    simd_ldst: SimdLdst,
-    fcvt: Fcvt,
-    frint: Frint,
-    ins_elem: InsElem,
-    fcmla_elem: FcmlaElem,
-}
-
-const UNKNOWN_INST: Inst = Inst {
+    pub op: Op,
+    pub flags: u8,
+    pub rd: u8,
+    pub rn: u8,
+    pub rm: u8,
+    pub rt2: u8,
+    pub rs: u8,
+    pub imm: u64,
+    pub fimm: f64,
+    pub offset: i64,
+    pub ra: u8,
+    pub error: DecodeError,
+    pub movk: Movk,
+    pub bfm: Bfm,
+    pub ccmp: Ccmp,
+    pub sys: Sys,
+    pub msr_imm: MsrImm,
+    pub tbz: Tbz,
+    pub shift: InstShift,
+    pub rmif: Rmif,
+    pub extend: Extend,
+    pub ldst_order: LdstOrder,
+    pub simd_ldst: SimdLdst,
+    pub fcvt: Fcvt,
+    pub frint: Frint,
+    pub ins_elem: InsElem,
+    pub fcmla_elem: FcmlaElem,
+}
+
+pub(crate) const UNKNOWN_INST: Inst = Inst {
     op: Op::A64_UNKNOWN,
     flags: 0,
     rd: 0,
@@ -1149,7 +1331,7 @@ const UNKNOWN_INST: Inst = Inst {
     fimm: 0.0,
     offset: 0,
     ra: 0,
-    error: String::new(),
+    error: DecodeError::Message(String::new()),
     movk: Movk { imm16: 0, lsl: 0 },
     bfm: Bfm { lsb: 0, width: 0 },
     ccmp: Ccmp { nzcv: 0, imm5: 0 },
@@ -1161,7 +1343,7 @@ const UNKNOWN_INST: Inst = Inst {
     },
     msr_imm: MsrImm { psfld: 0, imm: 0 },
     tbz: Tbz { offset: 0, bit: 0 },
-    shift: Shift::SH_LSL,
+    shift: InstShift { typ: Shift::SH_LSL as u32, amount: 0 },
     rmif: Rmif { mask: 0, ror: 0 },
     extend: Extend { typ: 0, lsl: 0 },
     ldst_order: LdstOrder {
@@ -1184,13 +1366,64 @@ const UNKNOWN_INST: Inst = Inst {
     fcmla_elem: FcmlaElem { idx: 0, rot: 0 },
 };
 
+/// Why an instruction decoded to A64_ERROR.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    Message(String),
+    /// An encoding field holds a value that is unallocated or UNDEFINED for
+    /// the instruction, e.g. hw = 2 for a 32-bit MOVK. field is named as in
+    /// the Arm ARM.
+    Reserved { field: &'static str, value: u32 },
+}
+
+impl Default for DecodeError {
+    fn default() -> DecodeError {
+        return DecodeError::Message(String::new());
+    }
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        return match self {
+            DecodeError::Message(msg) => write!(f, "{}", msg),
+            DecodeError::Reserved { field, value } => write!(f, "reserved {} field value {}", field, value),
+        };
+    }
+}
+
 pub fn errinst(err: String) -> Inst {
     let mut inst = UNKNOWN_INST;
     inst.op = Op::A64_ERROR;
-    inst.error = err;
+    inst.error = DecodeError::Message(err);
+    return inst;
+}
+
+/// An A64_ERROR for a reserved value of the encoding field `field`.
+pub fn field_errinst(field: &'static str, value: u32) -> Inst {
+    let mut inst = UNKNOWN_INST;
+    inst.op = Op::A64_ERROR;
+    inst.error = DecodeError::Reserved { field, value };
     return inst;
 }
 
+/// Moves inst into out. An empty error message of inst takes over the buffer
+/// of the message in out, so that decoding into the same Inst again and
+/// again does not free and reallocate it.
+fn move_into(out: &mut Inst, mut inst: Inst) {
+    if let (DecodeError::Message(msg), DecodeError::Message(buf)) = (&mut inst.error, &mut out.error) {
+        if msg.is_empty() {
+            buf.clear();
+            std::mem::swap(msg, buf);
+        }
+    }
+    *out = inst;
+}
+
+/// Resets inst to UNKNOWN_INST, keeping the buffer of its error message.
+fn reset_inst(inst: &mut Inst) {
+    move_into(inst, UNKNOWN_INST);
+}
+
 pub fn fad_get_cond(flags: u8) -> u8 {
     return (flags >> 4) & 0b1111;
 }
@@ -1245,6 +1478,37 @@ pub fn fad_size_from_vec_arrangement(va: u8) -> u8 {
     return va >> 1;
 }
 
+/// Element size in bits of a VectorArrangement, e.g. 32 for VA_4S.
+pub fn vec_elem_bits(arrangement: u8) -> u32 {
+    return 8 << (fad_size_from_vec_arrangement(arrangement) & 0b11);
+}
+
+/// Number of lanes of a VectorArrangement, e.g. 8 for VA_8B and 2 for VA_2D.
+pub fn vec_lane_count(arrangement: u8) -> u32 {
+    let bits = if arrangement & 1 != 0 { 128 } else { 64 };
+    return bits / vec_elem_bits(arrangement);
+}
+
+/// The arrangement of the wide operand of a long or narrowing instruction
+/// whose narrow operand has the given arrangement, e.g. VA_8H for both VA_8B
+/// and VA_16B (the latter used by the "2" variants operating on the upper half).
+/// There is no arrangement for the single 128-bit element (1Q) that the 64-bit
+/// polynomial multiply PMULL widens VA_1D and VA_2D to, so these yield None.
+pub fn vec_widen_arrangement(arrangement: u8) -> Option<u8> {
+    let size = fad_size_from_vec_arrangement(arrangement);
+    if size == FPSize::FSZ_D {
+        return None;
+    }
+    return Some(((size + 1) << 1) | 1);
+}
+
+/// Does a long or narrowing instruction whose narrow operand has the given
+/// arrangement use the upper half of its register, i.e. is it a "2" variant
+/// such as SMULL2? This is the Q bit: VA_8H is the upper half, VA_4H the lower.
+pub fn vec_is_upper_half(arrangement: u8) -> bool {
+    return arrangement & 1 != 0;
+}
+
 // The destination register Rd, if present, occupies bits 0..4.
 // Register 31 is treated as the Zero/Discard register ZR/WZR.
 pub fn regRd(binst: u32) -> u8 {
@@ -1290,6 +1554,591 @@ pub fn sext(x: u64, b: u8) -> i64 {
     return ((x as i64) ^ mask) - mask;
 }
 
+/// The top-level encoding groups of the A64 instruction set, selected by
+/// bits 25..28 of the instruction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Group {
+    /// 0000 -- only UDF is allocated
+    Reserved,
+    /// 0001, 0011
+    Unallocated,
+    /// 0010 -- Scalable Vector Extension
+    SVE,
+    /// 100x
+    DataProcImm,
+    /// 101x -- Branches, Exception Generating and System Instructions
+    BranchExcSys,
+    /// x1x0
+    LoadStore,
+    /// x101
+    DataProcReg,
+    /// x111 -- Scalar Floating-Point and Advanced SIMD
+    DataProcSimdFp,
+}
+
+pub fn group_of(binst: u32) -> Group {
+    let op0 = (binst >> 25) & 0b1111;
+    return match op0 {
+        0b0000 => Group::Reserved,
+        0b0001 | 0b0011 => Group::Unallocated,
+        0b0010 => Group::SVE,
+        0b1000 | 0b1001 => Group::DataProcImm,
+        0b1010 | 0b1011 => Group::BranchExcSys,
+        0b0100 | 0b0110 | 0b1100 | 0b1110 => Group::LoadStore,
+        0b0101 | 0b1101 => Group::DataProcReg,
+        _ => Group::DataProcSimdFp, // x111
+    };
+}
+
+/// Decodes a single instruction. Unallocated encodings and those of groups
+/// without a decoder yet are returned as A64_UNKNOWN, with the raw binary
+/// instruction in Inst.imm. All optional architecture features are assumed to
+/// be implemented, see decode_with_features. This is the decode of
+/// `Decoder::default()`.
+pub fn decode(binst: u32) -> Inst {
+    return Decoder::default().decode(binst);
+}
+
+/// Decodes binst into out, like `*out = decode(binst)`, but in place: the
+/// decoders of the Data Processing -- Immediate and the common load/store
+/// instructions write into out, and the buffer of an error message in out is
+/// reused. This lets a single Inst be reused across a stream of instructions.
+pub fn decode_into(binst: u32, out: &mut Inst) {
+    Decoder::default().decode_into(binst, out);
+}
+
+fn decode_word(binst: u32) -> Inst {
+    let mut inst = UNKNOWN_INST;
+    decode_word_into(binst, &mut inst);
+    return inst;
+}
+
+fn decode_word_into(binst: u32, out: &mut Inst) {
+    reset_inst(out);
+    match group_of(binst) {
+        Group::Reserved => move_into(out, reserved(binst)),
+        Group::DataProcImm => data_proc_imm_into(binst, out),
+        Group::BranchExcSys => move_into(out, branches_exc_sys(binst)),
+        Group::DataProcReg => move_into(out, data_proc_reg(binst)),
+        Group::LoadStore => loads_and_stores_into(binst, out),
+        Group::DataProcSimdFp => move_into(out, data_proc_simd_fp(binst)),
+        _ => {}
+    }
+
+    if out.op == Op::A64_UNKNOWN {
+        out.imm = binst as u64;
+    }
+}
+
+/// A set of optional architecture features, grouped as far as the decoder
+/// tells them apart. Combine them with `|`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FeatureSet(u32);
+
+impl FeatureSet {
+    pub const NONE: FeatureSet = FeatureSet(0);
+    /// FEAT_LSE: atomic memory operations (LDADD, SWP, CAS, ...)
+    pub const LSE: FeatureSet = FeatureSet(1 << 0);
+    /// FEAT_LRCPC, FEAT_LRCPC2: LDAPR, LDAPUR, STLUR
+    pub const RCPC: FeatureSet = FeatureSet(1 << 1);
+    /// FEAT_FP16: half-precision floating-point data processing
+    pub const FP16: FeatureSet = FeatureSet(1 << 2);
+    /// FEAT_SHA1, FEAT_SHA256, FEAT_SHA512, FEAT_SHA3
+    pub const SHA: FeatureSet = FeatureSet(1 << 3);
+    /// FEAT_LOR: LDLAR, STLLR
+    pub const LOR: FeatureSet = FeatureSet(1 << 4);
+    /// FEAT_BF16: BFloat16 conversions, dot products and matrix multiply
+    pub const BF16: FeatureSet = FeatureSet(1 << 5);
+    /// FEAT_RDM: SQRDMLAH, SQRDMLSH
+    pub const RDM: FeatureSet = FeatureSet(1 << 6);
+    /// FEAT_AES: AESE, AESD, AESMC, AESIMC
+    pub const AES: FeatureSet = FeatureSet(1 << 7);
+    /// FEAT_FRINTTS: FRINT32Z, FRINT32X, FRINT64Z, FRINT64X
+    pub const FRINTTS: FeatureSet = FeatureSet(1 << 8);
+    /// FEAT_FHM: FMLAL, FMLAL2, FMLSL, FMLSL2
+    pub const FHM: FeatureSet = FeatureSet(1 << 9);
+    pub const ALL: FeatureSet = FeatureSet((1 << 10) - 1);
+
+    const NAMES: [(FeatureSet, &'static str); 10] = [
+        (FeatureSet::LSE, "FEAT_LSE"),
+        (FeatureSet::RCPC, "FEAT_LRCPC"),
+        (FeatureSet::FP16, "FEAT_FP16"),
+        (FeatureSet::SHA, "FEAT_SHA"),
+        (FeatureSet::LOR, "FEAT_LOR"),
+        (FeatureSet::BF16, "FEAT_BF16"),
+        (FeatureSet::RDM, "FEAT_RDM"),
+        (FeatureSet::AES, "FEAT_AES"),
+        (FeatureSet::FRINTTS, "FEAT_FRINTTS"),
+        (FeatureSet::FHM, "FEAT_FHM"),
+    ];
+
+    /// Are all features of other in the set?
+    pub fn contains(self, other: FeatureSet) -> bool {
+        return self.0 & other.0 == other.0;
+    }
+}
+
+impl std::ops::BitOr for FeatureSet {
+    type Output = FeatureSet;
+
+    fn bitor(self, rhs: FeatureSet) -> FeatureSet {
+        return FeatureSet(self.0 | rhs.0);
+    }
+}
+
+/// Is op declared between first and last (inclusive) in the Op enum?
+pub(crate) fn op_in(op: &Op, first: Op, last: Op) -> bool {
+    let d = op.clone() as u32;
+    return (first as u32..=last as u32).contains(&d);
+}
+
+/// Does the floating-point instruction operate on half-precision values?
+/// Conversions from and to half precision are part of the base FP.
+fn is_half_precision(inst: &Inst) -> bool {
+    let op = &inst.op;
+    if !op_in(op, Op::A64_FCVT_GPR, Op::A64_FMINNMV) || op_in(op, Op::A64_FCVT_H, Op::A64_FCVTXN) {
+        return false;
+    }
+    let vector = op_in(op, Op::A64_FCMEQ_REG, Op::A64_FMINNMV)
+        || matches!(op, Op::A64_FCVT_VEC | Op::A64_CVTF_VEC | Op::A64_FRINT_VEC | Op::A64_FRINTX_VEC | Op::A64_FMOV_VEC);
+    let size = if vector { fad_size_from_vec_arrangement(fad_get_vec_arrangement(inst.flags)) } else { fad_get_prec(inst.flags) };
+    return size == FPSize::FSZ_H;
+}
+
+/// Is the load or store an LDAPUR* or STLUR*, i.e. ordered with an offset?
+fn is_unscaled_ordered(inst: &Inst) -> bool {
+    let ordered = inst.ldst_order.load != MemOrdering::MO_NONE as u16 || inst.ldst_order.store != MemOrdering::MO_NONE as u16;
+    return ordered && fad_get_addrmode(inst.flags) == AddrMode::AM_OFF_IMM;
+}
+
+/// The optional features a decoded instruction requires.
+pub fn required_features(inst: &Inst) -> FeatureSet {
+    let op = &inst.op;
+    return match op {
+        Op::A64_LDAPR => FeatureSet::RCPC,
+        Op::A64_LDR | Op::A64_STR if is_unscaled_ordered(inst) => FeatureSet::RCPC,
+        Op::A64_LDR if inst.ldst_order.load == MemOrdering::MO_LO_ACQUIRE as u16 => FeatureSet::LOR,
+        Op::A64_STR if inst.ldst_order.store == MemOrdering::MO_LO_RELEASE as u16 => FeatureSet::LOR,
+        Op::A64_BCAX | Op::A64_EOR3 | Op::A64_RAX1 | Op::A64_XAR => FeatureSet::SHA,
+        _ if op_in(op, Op::A64_SHA1C, Op::A64_SHA256SU1) => FeatureSet::SHA,
+        _ if op_in(op, Op::A64_AESD, Op::A64_AESMC) => FeatureSet::AES,
+        _ if op_in(op, Op::A64_LDADD, Op::A64_CASP) => FeatureSet::LSE,
+        _ if op_in(op, Op::A64_BFCVT, Op::A64_BFMMLA) => FeatureSet::BF16,
+        _ if op_in(op, Op::A64_SQRDMLAH_ELEM, Op::A64_SQRDMLSH_VEC) => FeatureSet::RDM,
+        _ if op_in(op, Op::A64_FRINT, Op::A64_FRINTX_VEC) && inst.frint.bits != 0 => FeatureSet::FRINTTS,
+        Op::A64_FMLAL_ELEM | Op::A64_FMLAL_VEC | Op::A64_FMLAL2_ELEM | Op::A64_FMLAL2_VEC => FeatureSet::FHM,
+        Op::A64_FMLSL_ELEM | Op::A64_FMLSL_VEC | Op::A64_FMLSL2_ELEM | Op::A64_FMLSL2_VEC => FeatureSet::FHM,
+        _ if is_half_precision(inst) => FeatureSet::FP16,
+        _ => FeatureSet::NONE,
+    };
+}
+
+/// Decodes an instruction for a CPU implementing only the given optional
+/// features: instructions requiring others yield an A64_ERROR naming the
+/// missing feature, e.g. "requires FEAT_LSE". decode is
+/// `decode_with_features(binst, FeatureSet::ALL)`.
+pub fn decode_with_features(binst: u32, features: FeatureSet) -> Inst {
+    return Decoder { features, ..Decoder::default() }.decode(binst);
+}
+
+/// Decodes an instruction word the built-in decoder does not know, returning
+/// None to keep it A64_UNKNOWN.
+pub type UnknownHandler = fn(u32) -> Option<Inst>;
+
+/// Options changing how instructions are decoded.
+#[derive(Clone, Debug)]
+pub struct DecodeOptions {
+    /// Consulted for every word that would decode to A64_UNKNOWN: vendor
+    /// specific instructions in unallocated space, for example.
+    pub unknown_handler: Option<UnknownHandler>,
+    /// The number of consecutive undecodable words after which
+    /// decode_with_data_recovery takes them for data. 4 by default.
+    pub data_run: usize,
+    /// Decode the encodings known to be unallocated to A64_ERROR instead of
+    /// A64_UNKNOWN, for verifiers that reject any non-instruction. Off by
+    /// default. See is_unallocated for which words are affected.
+    pub strict_unallocated: bool,
+}
+
+impl Default for DecodeOptions {
+    fn default() -> DecodeOptions {
+        return DecodeOptions { unknown_handler: None, data_run: 4, strict_unallocated: false };
+    }
+}
+
+/// A region of a buffer split up by decode_with_data_recovery.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Span<'a> {
+    /// Instructions and their addresses.
+    Code(Vec<(u64, Inst)>),
+    /// The address and bytes of embedded data.
+    Data(u64, &'a [u8]),
+}
+
+/// A decoder for a particular CPU and code location. The default one has no
+/// options, assumes all optional features and starts at address 0.
+///
+/// All configuration lives in the Decoder; there are no global options or
+/// other state shared between decodes. Decoder, Inst and the free decode
+/// functions are therefore Send + Sync: one Decoder can be shared by threads
+/// decoding parts of a buffer in parallel, with the same results as a serial
+/// run. Keep it that way when adding options, e.g. unknown handlers are
+/// plain fn pointers rather than closures capturing state.
+#[derive(Clone, Debug)]
+pub struct Decoder {
+    pub options: DecodeOptions,
+    /// The features of the CPU, see decode_with_features.
+    pub features: FeatureSet,
+    /// The address of the first instruction decode_buffer decodes.
+    pub base_pc: u64,
+}
+
+impl Default for Decoder {
+    fn default() -> Decoder {
+        return Decoder { options: DecodeOptions::default(), features: FeatureSet::ALL, base_pc: 0 };
+    }
+}
+
+impl Decoder {
+    pub fn new() -> Decoder {
+        return Decoder::default();
+    }
+
+    pub fn set_unknown_handler(&mut self, handler: UnknownHandler) {
+        self.options.unknown_handler = Some(handler);
+    }
+
+    pub fn decode(&self, binst: u32) -> Inst {
+        let mut inst = UNKNOWN_INST;
+        self.decode_into(binst, &mut inst);
+        return inst;
+    }
+
+    /// Decodes binst into out, like `*out = self.decode(binst)`, see the
+    /// free decode_into.
+    pub fn decode_into(&self, binst: u32, out: &mut Inst) {
+        decode_word_into(binst, out);
+        if out.op == Op::A64_UNKNOWN {
+            if let Some(custom) = self.options.unknown_handler.and_then(|handler| handler(binst)) {
+                move_into(out, custom);
+            }
+        }
+        if out.op == Op::A64_UNKNOWN && self.options.strict_unallocated && is_unallocated(binst) {
+            move_into(out, errinst("unallocated encoding".to_string()));
+            return;
+        }
+
+        if self.features == FeatureSet::ALL {
+            return;
+        }
+        let required = required_features(out);
+        if self.features.contains(required) {
+            return;
+        }
+        let (_, name) = FeatureSet::NAMES.iter().find(|(f, _)| !self.features.contains(*f) && required.contains(*f)).unwrap();
+        move_into(out, errinst(format!("requires {}", name)));
+    }
+
+    /// Decodes the little-endian instruction words of code, together with
+    /// their addresses counted from base_pc. Trailing bytes short of a word
+    /// yield an A64_ERROR, like InstIter.
+    pub fn decode_buffer<'a>(&'a self, code: &'a [u8]) -> impl Iterator<Item = (u64, Inst)> + 'a {
+        return code.chunks(4).enumerate().map(move |(i, word)| {
+            let pc = self.base_pc.wrapping_add(4 * i as u64);
+            let inst = match word.try_into() {
+                Ok(bytes) => self.decode(u32::from_le_bytes(bytes)),
+                Err(_) => errinst(format!("truncated instruction: {} trailing bytes", word.len())),
+            };
+            return (pc, inst);
+        });
+    }
+
+    /// Splits code into Code and Data spans by a linear sweep, like
+    /// decode_buffer. A run of at least options.data_run words decoding to
+    /// A64_UNKNOWN, A64_UDF or A64_ERROR becomes a Data span, which lasts up
+    /// to the next decodable word. Shorter runs stay within their Code span.
+    /// Trailing bytes short of a word are always data.
+    pub fn decode_with_data_recovery<'a>(&self, code: &'a [u8]) -> Vec<Span<'a>> {
+        let data_run = self.options.data_run.max(1);
+        let mut spans = Vec::new();
+        let mut insts: Vec<(u64, Inst)> = Vec::new();
+        let mut run = 0; // undecodable words at the end of insts
+        let mut data_start: Option<usize> = None;
+
+        for (i, (pc, inst)) in self.decode_buffer(code).enumerate() {
+            let offset = 4 * i;
+            let truncated = offset + 4 > code.len();
+            let undecodable = truncated || matches!(inst.op, Op::A64_UNKNOWN | Op::A64_UDF | Op::A64_ERROR);
+
+            if !undecodable {
+                if let Some(start) = data_start.take() {
+                    spans.push(Span::Data(self.base_pc.wrapping_add(start as u64), &code[start..offset]));
+                }
+                insts.push((pc, inst));
+                run = 0;
+                continue;
+            }
+            if data_start.is_some() {
+                continue;
+            }
+
+            insts.push((pc, inst));
+            run += 1;
+            if run >= data_run || truncated {
+                insts.truncate(insts.len() - run);
+                if !insts.is_empty() {
+                    spans.push(Span::Code(std::mem::take(&mut insts)));
+                }
+                data_start = Some(offset + 4 - 4 * run);
+                run = 0;
+            }
+        }
+
+        if let Some(start) = data_start {
+            spans.push(Span::Data(self.base_pc.wrapping_add(start as u64), &code[start..]));
+        } else if !insts.is_empty() {
+            spans.push(Span::Code(insts));
+        }
+        return spans;
+    }
+}
+
+/// Splits code starting at address base into Code and Data spans, see
+/// Decoder::decode_with_data_recovery.
+pub fn decode_with_data_recovery(code: &[u8], base: u64) -> Vec<Span<'_>> {
+    return Decoder { base_pc: base, ..Decoder::default() }.decode_with_data_recovery(code);
+}
+
+/// Is binst known to be an unallocated encoding? True for the unallocated
+/// top-level group, the reserved group other than UDF and the reserved
+/// field values of the Data Processing -- Immediate group. Other unknown
+/// words may just not be supported by the decoder yet, so they are not.
+pub fn is_unallocated(binst: u32) -> bool {
+    if decode_word(binst).op != Op::A64_UNKNOWN {
+        return false;
+    }
+    return match group_of(binst) {
+        Group::Reserved | Group::Unallocated => true,
+        // ADDG and SUBG are allocated, just not decoded
+        Group::DataProcImm => !matches!(data_proc_imm_kind(binst), AddSubTags) && data_proc_imm_reserved(binst).is_some(),
+        _ => false,
+    };
+}
+
+/// Explains why decode returns A64_UNKNOWN for an encoding: the top-level group
+/// it belongs to, the sub-decoder that would have handled it and why that one
+/// bailed. This is a debugging aid; the wording is not stable.
+pub fn explain_unknown(binst: u32) -> String {
+    let group = group_of(binst);
+    let inst = decode(binst);
+    if inst.op == Op::A64_ERROR {
+        return format!("{:?} / {}", group, inst.error);
+    }
+    if inst.op != Op::A64_UNKNOWN {
+        return format!("{:?}: not unknown, decodes to {:?}", group, inst.op);
+    }
+
+    return match group {
+        Group::Reserved | Group::Unallocated => format!("group {:?}: unallocated encoding", group),
+        Group::DataProcImm => {
+            let reason = data_proc_imm_reserved(binst).unwrap_or("no matching encoding");
+            format!("{:?} / {}", group, reason)
+        }
+        Group::BranchExcSys | Group::DataProcReg | Group::LoadStore | Group::DataProcSimdFp => match inst_class(binst) {
+            Some(class) => format!("{:?} / {}: {}", group, class.name(), inst_class_unknown_reason(binst, class)),
+            None => format!("group {:?}: no instruction class, unallocated encoding", group),
+        },
+        _ => format!("group {:?}: decoder not yet implemented", group),
+    };
+}
+
+/// Named bit fields of an encoding, spelled as in the Arm ARM (sf, imm12, Rd).
+pub type FieldMap = BTreeMap<&'static str, u32>;
+
+/// The bit fields of binst according to the encoding tables of group, a
+/// debugging and testing aid to check field offsets against the Arm ARM. For
+/// data processing (immediate), these are the fields of the instruction
+/// class; for the other groups, the opN fields that select the class.
+/// group need not be group_of(binst), the fields are extracted regardless.
+pub fn extract_fields(binst: u32, group: Group) -> FieldMap {
+    const RD: (&str, u32, u32) = ("Rd", 0, 5);
+    const RN: (&str, u32, u32) = ("Rn", 5, 5);
+    const SF: (&str, u32, u32) = ("sf", 31, 1);
+
+    // name, lowest bit, width
+    let fields: &[(&'static str, u32, u32)] = match group {
+        Group::DataProcImm => match data_proc_imm_kind(binst) {
+            PCRelAddr => &[("op", 31, 1), ("immlo", 29, 2), ("immhi", 5, 19), RD],
+            AddSub => &[SF, ("op", 30, 1), ("S", 29, 1), ("sh", 22, 1), ("imm12", 10, 12), RN, RD],
+            AddSubTags => &[SF, ("op", 30, 1), ("S", 29, 1), ("o2", 22, 1), ("uimm6", 16, 6), ("op3", 14, 2), ("uimm4", 10, 4), RN, RD],
+            Logic | Bitfield => &[SF, ("opc", 29, 2), ("N", 22, 1), ("immr", 16, 6), ("imms", 10, 6), RN, RD],
+            Move => &[SF, ("opc", 29, 2), ("hw", 21, 2), ("imm16", 5, 16), RD],
+            Extract => &[SF, ("op21", 29, 2), ("N", 22, 1), ("o0", 21, 1), ("Rm", 16, 5), ("imms", 10, 6), RN, RD],
+            Unknown => &[],
+        },
+        Group::Reserved => &[("op0", 29, 3), ("op1", 16, 9), ("imm16", 0, 16)],
+        Group::Unallocated => &[("op0", 25, 4)],
+        Group::SVE => &[("op0", 29, 3), ("op1", 23, 2), ("op2", 17, 5), ("op3", 10, 6), ("op4", 4, 1)],
+        Group::BranchExcSys => &[("op0", 29, 3), ("op1", 12, 14), ("op2", 0, 5)],
+        Group::LoadStore => &[("op0", 28, 4), ("op1", 26, 1), ("op2", 23, 2), ("op3", 16, 6), ("op4", 10, 2)],
+        Group::DataProcReg => &[("op0", 30, 1), ("op1", 28, 1), ("op2", 21, 4), ("op3", 10, 6)],
+        Group::DataProcSimdFp => &[("op0", 28, 4), ("op1", 23, 2), ("op2", 19, 4), ("op3", 10, 9)],
+    };
+    return fields.iter().map(|&(name, lsb, width)| (name, (binst >> lsb) & ((1 << width) - 1))).collect();
+}
+
+/// Iterates over the instructions in a buffer of little-endian instruction
+/// words. Code and data are often interleaved, so undecodable words are
+/// yielded as A64_UNKNOWN and iteration carries on with the next word. A
+/// truncated word at the end of the buffer is yielded as A64_ERROR.
+pub struct InstIter<'a> {
+    code: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> InstIter<'a> {
+    pub fn new(code: &'a [u8]) -> InstIter<'a> {
+        return InstIter { code, pos: 0 };
+    }
+}
+
+impl<'a> Iterator for InstIter<'a> {
+    type Item = Inst;
+
+    fn next(&mut self) -> Option<Inst> {
+        let rest = &self.code[self.pos..];
+        if rest.is_empty() {
+            return None;
+        }
+        if rest.len() < 4 {
+            self.pos = self.code.len();
+            return Some(errinst(format!("truncated instruction: {} trailing bytes", rest.len())));
+        }
+
+        self.pos += 4;
+        return Some(decode(u32::from_le_bytes([rest[0], rest[1], rest[2], rest[3]])));
+    }
+}
+
+/// Iterator returned by decode_reader.
+pub struct ReaderIter<R: Read> {
+    reader: R,
+    done: bool,
+}
+
+/// Decodes the little-endian instruction words read from reader, with the
+/// same recovery behaviour as InstIter. An I/O error is yielded as A64_ERROR
+/// and ends the iteration.
+pub fn decode_reader<R: Read>(reader: R) -> ReaderIter<R> {
+    return ReaderIter { reader, done: false };
+}
+
+impl<R: Read> Iterator for ReaderIter<R> {
+    type Item = Inst;
+
+    fn next(&mut self) -> Option<Inst> {
+        if self.done {
+            return None;
+        }
+
+        let mut buf = [0u8; 4];
+        let mut len = 0;
+        while len < 4 {
+            match self.reader.read(&mut buf[len..]) {
+                Ok(0) => break,
+                Ok(n) => len += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => {
+                    self.done = true;
+                    return Some(errinst(e.to_string()));
+                }
+            }
+        }
+
+        if len < 4 {
+            self.done = true;
+            return if len == 0 { None } else { Some(errinst(format!("truncated instruction: {} trailing bytes", len))) };
+        }
+        return Some(decode(u32::from_le_bytes(buf)));
+    }
+}
+
+/// Filters the A64_UNKNOWN and A64_ERROR instructions out of a stream of
+/// decoded instructions, e.g. InstIter or decode_reader.
+pub fn skip_unknown<I: Iterator<Item = Inst>>(insts: I) -> impl Iterator<Item = Inst> {
+    return insts.filter(|inst| inst.op != Op::A64_UNKNOWN && inst.op != Op::A64_ERROR);
+}
+
+/// Why decode_hex could not read an instruction word.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// Neither a hex digit nor a separator
+    InvalidDigit(char),
+    /// The number of hex digits of a word (1 to 8) or of bytes (4)
+    WrongLength(usize),
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        return match self {
+            ParseError::InvalidDigit(c) => write!(f, "invalid hex digit {:?}", c),
+            ParseError::WrongLength(n) => write!(f, "{} hex digits or bytes do not make an instruction word", n),
+        };
+    }
+}
+
+/// Decodes an instruction written in hex, in one of two forms:
+///
+/// - a word, "0x91001020" or "91001020", read as a number like llvm-mc and
+///   objdump print it, i.e. most significant digit first;
+/// - four bytes separated by spaces or commas, "20 10 00 91", in memory
+///   order, i.e. little-endian like the instruction stream.
+///
+/// Both examples are add x0, x1, #4.
+pub fn decode_hex(s: &str) -> Result<Inst, ParseError> {
+    let hex = |digits: &str| -> Result<u32, ParseError> {
+        let digits = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")).unwrap_or(digits);
+        if let Some(c) = digits.chars().find(|c| !c.is_ascii_hexdigit()) {
+            return Err(ParseError::InvalidDigit(c));
+        }
+        if digits.is_empty() || digits.len() > 8 {
+            return Err(ParseError::WrongLength(digits.len()));
+        }
+        return Ok(u32::from_str_radix(digits, 16).unwrap());
+    };
+
+    let s = s.trim();
+    let bytes: Vec<&str> = s.split(|c: char| c.is_whitespace() || c == ',').filter(|b| !b.is_empty()).collect();
+    if bytes.len() == 1 {
+        return Ok(decode(hex(s)?));
+    }
+    if bytes.len() != 4 {
+        return Err(ParseError::WrongLength(bytes.len()));
+    }
+    let mut word = 0;
+    for (i, byte) in bytes.iter().enumerate() {
+        let value = hex(byte)?;
+        if value > 0xFF {
+            return Err(ParseError::WrongLength(byte.len()));
+        }
+        word |= value << (8 * i);
+    }
+    return Ok(decode(word));
+}
+
+/// The only allocated encoding in the reserved group is UDF #imm16, with all
+/// of the upper 16 bits clear.
+fn reserved(binst: u32) -> Inst {
+    if (binst >> 16) != 0 {
+        return UNKNOWN_INST;
+    }
+
+    let mut inst = UNKNOWN_INST;
+    inst.op = Op::A64_UDF;
+    inst.imm = (binst & 0xFFFF) as u64;
+    return inst;
+}
+
 enum OpKind {
     Unknown,
     PCRelAddr,
@@ -1301,14 +2150,11 @@ enum OpKind {
     Extract,
 }
 
-pub fn data_proc_imm(binst: u32) -> Inst {
-    let mut inst = UNKNOWN_INST.clone();
-
+fn data_proc_imm_kind(binst: u32) -> OpKind {
     let op01 = (binst >> 22) & 0b1111; // op0 and op1 together
-    let top3 = (binst >> 29) & 0b111;
 
-    let kind = match op01 {
-        0b0000 | 0b0001 | 0b0010 | 0b0011 => PCRelAddr, // 00xx
+    return match op01 {
+        0b0000..=0b0011 => PCRelAddr, // 00xx
         0b0110 | 0b0111 => AddSubTags, // 011x
         0b0100 | 0b0101 => AddSub, // 010x
         0b1000 | 0b1001 => Logic, // 100x
@@ -1320,6 +2166,71 @@ pub fn data_proc_imm(binst: u32) -> Inst {
             Unknown
         }
     };
+}
+
+/// Returns why a data processing (immediate) encoding is unallocated or not
+/// supported, None if data_proc_imm can decode it.
+fn data_proc_imm_reserved(binst: u32) -> Option<&'static str> {
+    let sf = (binst >> 31) & 1;
+    let opc = (binst >> 29) & 0b11;
+    let N = (binst >> 22) & 1;
+    let immr = (binst >> 16) & 0b111111;
+    let imms = (binst >> 10) & 0b111111;
+
+    return match data_proc_imm_kind(binst) {
+        Unknown => Some("unknown operation kind"),
+        PCRelAddr | AddSub => None,
+        AddSubTags => Some("add/subtract (immediate, with tags): ADDG, SUBG not supported"),
+        Logic if sf == 0 && N == 1 => Some("logical (immediate): N must be 0 for the 32-bit variant"),
+        Logic if decode_bitmask(N as u8, imms as u8, immr as u8, sf == 0).is_none() => {
+            Some("logical (immediate): reserved bitmask immediate N:imms")
+        }
+        Logic => None,
+        Move if opc == 0b01 => Some("move wide (immediate): unallocated opc field"),
+        Move => None,
+        Bitfield if opc == 0b11 => Some("bitfield: unallocated opc field"),
+        Bitfield => None,
+        Extract if opc != 0 || (binst & (1 << 21)) != 0 => Some("extract: unallocated op21, o0 fields"),
+        Extract => None,
+    };
+}
+
+/// Returns the first field of an otherwise allocated data processing
+/// (immediate) encoding whose value is reserved, together with that value.
+fn data_proc_imm_bad_field(binst: u32) -> Option<(&'static str, u32)> {
+    let sf = (binst >> 31) & 1;
+    let hw = (binst >> 21) & 0b11;
+    let N = (binst >> 22) & 1;
+    let immr = (binst >> 16) & 0b111111;
+    let imms = (binst >> 10) & 0b111111;
+
+    return match data_proc_imm_kind(binst) {
+        Move if sf == 0 && hw >= 0b10 => Some(("hw", hw)),
+        Bitfield | Extract if sf != N => Some(("N", N)),
+        Bitfield if sf == 0 && immr >= 32 => Some(("immr", immr)),
+        Bitfield | Extract if sf == 0 && imms >= 32 => Some(("imms", imms)),
+        _ => None,
+    };
+}
+
+pub fn data_proc_imm(binst: u32) -> Inst {
+    let mut inst = UNKNOWN_INST;
+    data_proc_imm_into(binst, &mut inst);
+    return inst;
+}
+
+/// data_proc_imm, writing into inst, which is UNKNOWN_INST on entry.
+fn data_proc_imm_into(binst: u32, inst: &mut Inst) {
+    let top3 = (binst >> 29) & 0b111;
+
+    let kind = data_proc_imm_kind(binst);
+    if data_proc_imm_reserved(binst).is_some() {
+        return;
+    }
+    if let Some((field, value)) = data_proc_imm_bad_field(binst) {
+        move_into(inst, field_errinst(field, value));
+        return;
+    }
 
     // Bit 31 (sf) controls length of registers (0 → 32 bit, 1 → 64 bit)
     // for most of these data processing operations.
@@ -1328,7 +2239,7 @@ pub fn data_proc_imm(binst: u32) -> Inst {
     }
 
     match kind {
-        Unknown => return UNKNOWN_INST,
+        Unknown => return reset_inst(inst),
         PCRelAddr => {
             if (top3 & 0b100) == 0 {
                 inst.op = A64_ADR;
@@ -1349,7 +2260,7 @@ pub fn data_proc_imm(binst: u32) -> Inst {
 
             inst.rd = regRd(binst);
         }
-        AddSubTags => panic!("ADDG, SUBG not supported"),
+        AddSubTags => return reset_inst(inst), // ADDG, SUBG not supported
         AddSub => {
             let is_add = (top3 & 0b010) == 0;
             inst.op = if is_add { A64_ADD_IMM } else { A64_SUB_IMM };
@@ -1372,7 +2283,7 @@ pub fn data_proc_imm(binst: u32) -> Inst {
                     A64_SUB_IMM => inst.op = A64_CMP_IMM,
                     _ => {} // impossible
                 }
-            } else if inst.op == A64_ADD_IMM && !shift_by_12 && unshifted_imm == 0 && ((inst.rd == STACK_POINTER) || (inst.rn == STACK_POINTER)) {
+            } else if inst.op == A64_ADD_IMM && inst.flags & SET_FLAGS == 0 && !shift_by_12 && unshifted_imm == 0 && ((inst.rd == STACK_POINTER) || (inst.rn == STACK_POINTER)) {
                 inst.op = A64_MOV_SP;
             }
         }
@@ -1391,12 +2302,21 @@ pub fn data_proc_imm(binst: u32) -> Inst {
             let immr: u8 = ((binst >> 16) & 0b111111) as u8;
             let imms: u8 = ((binst >> 10) & 0b111111) as u8;
             let N: u8 = if inst.flags & W32 != 0 { 0 } else { ((binst >> 22) & 1) as u8 }; // N is part of imm for 64-bit variants
-            inst.imm = decode_bitmask(N, imms, immr, inst.flags & W32 != 0);
+            inst.imm = match decode_bitmask(N, imms, immr, inst.flags & W32 != 0) {
+                Some(imm) => imm,
+                None => return reset_inst(inst),
+            };
 
             // ANDS and by extension TST interpret R31 as the zero register, while
             // regular immediate AND interprets it as the stack pointer.
             inst.rd = if inst.flags & SET_FLAGS != 0 { regRd(binst) } else { regRdSP(binst) };
             inst.rn = regRn(binst);
+
+            // MOV (bitmask immediate), unless MOVZ or MOVN can produce the value
+            if inst.op == A64_ORR_IMM && inst.rn == ZERO_REG && !is_move_wide_imm(inst.imm, inst.flags & W32 != 0) {
+                inst.op = A64_MOV_IMM;
+                inst.rn = 0;
+            }
         }
         Move => {
             let hw: u8 = ((binst >> 21) & 0b11) as u8;
@@ -1407,8 +2327,11 @@ pub fn data_proc_imm(binst: u32) -> Inst {
                 0b00 => { // MOVN: Move with NOT
                     inst.op = A64_MOV_IMM;
                     inst.imm = !(imm16 << shift);
+                    if inst.flags & W32 != 0 {
+                        inst.imm &= 0xFFFFFFFF; // only invert within the W register
+                    }
                 }
-                0b01 => return UNKNOWN_INST,
+                0b01 => return reset_inst(inst),
                 0b10 => { // MOVZ: zero other bits
                     inst.op = A64_MOV_IMM;
                     inst.imm = imm16 << shift;
@@ -1436,7 +2359,7 @@ pub fn data_proc_imm(binst: u32) -> Inst {
             let imms: u8 = ((binst >> 10) & 0b111111) as u8;
             let rd = regRd(binst);
             let rn = regRn(binst);
-            inst = find_bfm_alias(op, w32, rd, rn, immr, imms);
+            find_bfm_alias(op, w32, rd, rn, immr, imms, inst);
         }
         Extract => {
             inst.op = A64_EXTR;
@@ -1451,58 +2374,2168 @@ pub fn data_proc_imm(binst: u32) -> Inst {
             }
         }
     }
-
-    inst
 }
 
 
-/// Returns the 0-based index of the highest bit. Should be compiled down
-/// to a single native instruction.
-fn highest_bit(mut x: u32) -> i32 {
-    let mut n = 0;
-    while x != 1 {
-        x >>= 1;
-        n += 1;
-    }
-    return n;
+/// The instruction classes within the top-level groups, as the Arm ARM
+/// names them. The group decoders dispatch on these, and explain_unknown
+/// reports them; classes without a decoder yet are listed too.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum InstClass {
+    // Branches, Exception Generating and System
+    ConditionalBranch,
+    ExceptionGeneration,
+    SystemRegisterArgument,
+    Hint,
+    Barrier,
+    Pstate,
+    SystemInstruction,
+    SystemRegisterMove,
+    BranchRegister,
+    BranchImmediate,
+    CompareBranch,
+    TestBranch,
+    // Data Processing -- Register
+    DataProc1Src,
+    DataProc2Src,
+    LogicalShifted,
+    AddSubShifted,
+    AddSubExtended,
+    AddSubCarry,
+    RotateIntoFlags,
+    EvaluateIntoFlags,
+    ConditionalCompare,
+    ConditionalSelect,
+    DataProc3Src,
+    // Loads and Stores
+    SimdLoadStoreStructures,
+    CompareAndSwap,
+    LoadStoreExclusive,
+    LoadStoreOrdered,
+    LdaprStlrUnscaled,
+    MemoryCopySet,
+    MemoryTags,
+    LoadLiteral,
+    LoadStorePair,
+    LoadStoreUnscaledImm,
+    LoadStorePostIndexed,
+    LoadStoreUnprivileged,
+    LoadStorePreIndexed,
+    AtomicMemory,
+    LoadStoreRegisterOffset,
+    LoadStorePac,
+    LoadStoreUnsignedImm,
+    // Data Processing -- Scalar Floating-Point and Advanced SIMD
+    CryptoAes,
+    CryptoSha3Reg,
+    CryptoSha2Reg,
+    SimdThreeSameFp16,
+    SimdTwoRegMisc,
+    SimdAcrossLanes,
+    SimdThreeSame,
+    SimdThreeDifferent,
+    SimdThreeSameExtra,
+    SimdCopy,
+    SimdModifiedImm,
+    SimdShiftImm,
+    SimdIndexedElement,
+    SimdScalarThreeSameFp16,
+    SimdScalarThreeSame,
+    SimdScalarPairwise,
+    FpFixedConversion,
+    FpIntegerConversion,
+    FpDataProc1Src,
+    FpCompare,
+    FpImm,
+    FpConditionalCompare,
+    FpDataProc2Src,
+    FpConditionalSelect,
+    FpDataProc3Src,
 }
 
-/// Rotate the len-bit number x n places to the right. Based on the first
-/// example at https://en.wikipedia.org/wiki/Bitwise_operation#Circular_shifts
-/// (except turned around, to make it rotate right).
-fn ror(x: u64, n: u32, len: u32) -> u64 {
-    let raw = (x >> n) | (x << (len - n));
-    if len == 64 {
-        return raw;
+impl InstClass {
+    fn name(self) -> &'static str {
+        return match self {
+            InstClass::ConditionalBranch => "conditional branch (immediate)",
+            InstClass::ExceptionGeneration => "exception generation",
+            InstClass::SystemRegisterArgument => "system instructions with register argument",
+            InstClass::Hint => "hints",
+            InstClass::Barrier => "barriers",
+            InstClass::Pstate => "PSTATE",
+            InstClass::SystemInstruction => "system instructions",
+            InstClass::SystemRegisterMove => "system register move",
+            InstClass::BranchRegister => "unconditional branch (register)",
+            InstClass::BranchImmediate => "unconditional branch (immediate)",
+            InstClass::CompareBranch => "compare and branch (immediate)",
+            InstClass::TestBranch => "test and branch (immediate)",
+            InstClass::DataProc1Src => "data-processing (1 source)",
+            InstClass::DataProc2Src => "data-processing (2 source)",
+            InstClass::LogicalShifted => "logical (shifted register)",
+            InstClass::AddSubShifted => "add/subtract (shifted register)",
+            InstClass::AddSubExtended => "add/subtract (extended register)",
+            InstClass::AddSubCarry => "add/subtract (with carry)",
+            InstClass::RotateIntoFlags => "rotate right into flags",
+            InstClass::EvaluateIntoFlags => "evaluate into flags",
+            InstClass::ConditionalCompare => "conditional compare",
+            InstClass::ConditionalSelect => "conditional select",
+            InstClass::DataProc3Src => "data-processing (3 source)",
+            InstClass::SimdLoadStoreStructures => "advanced SIMD load/store structures",
+            InstClass::CompareAndSwap => "compare and swap",
+            InstClass::LoadStoreExclusive => "load/store exclusive",
+            InstClass::LoadStoreOrdered => "load/store ordered",
+            InstClass::LdaprStlrUnscaled => "LDAPR/STLR (unscaled immediate)",
+            InstClass::MemoryCopySet => "memory copy and memory set",
+            InstClass::MemoryTags => "load/store memory tags",
+            InstClass::LoadLiteral => "load register (literal)",
+            InstClass::LoadStorePair => "load/store register pair",
+            InstClass::LoadStoreUnscaledImm => "load/store register (unscaled immediate)",
+            InstClass::LoadStorePostIndexed => "load/store register (immediate post-indexed)",
+            InstClass::LoadStoreUnprivileged => "load/store register (unprivileged)",
+            InstClass::LoadStorePreIndexed => "load/store register (immediate pre-indexed)",
+            InstClass::AtomicMemory => "atomic memory operations",
+            InstClass::LoadStoreRegisterOffset => "load/store register (register offset)",
+            InstClass::LoadStorePac => "load/store register (pac)",
+            InstClass::LoadStoreUnsignedImm => "load/store register (unsigned immediate)",
+            InstClass::CryptoAes => "cryptographic AES",
+            InstClass::CryptoSha3Reg => "cryptographic three-register SHA",
+            InstClass::CryptoSha2Reg => "cryptographic two-register SHA",
+            InstClass::SimdThreeSameFp16 => "advanced SIMD three same (FP16)",
+            InstClass::SimdTwoRegMisc => "advanced SIMD two-register miscellaneous",
+            InstClass::SimdAcrossLanes => "advanced SIMD across lanes",
+            InstClass::SimdThreeSame => "advanced SIMD three same",
+            InstClass::SimdThreeDifferent => "advanced SIMD three different",
+            InstClass::SimdThreeSameExtra => "advanced SIMD three same extra",
+            InstClass::SimdCopy => "advanced SIMD copy",
+            InstClass::SimdModifiedImm => "advanced SIMD modified immediate",
+            InstClass::SimdShiftImm => "advanced SIMD shift by immediate",
+            InstClass::SimdIndexedElement => "advanced SIMD vector x indexed element",
+            InstClass::SimdScalarThreeSameFp16 => "advanced SIMD scalar three same (FP16)",
+            InstClass::SimdScalarThreeSame => "advanced SIMD scalar three same",
+            InstClass::SimdScalarPairwise => "advanced SIMD scalar pairwise",
+            InstClass::FpFixedConversion => "conversion between floating-point and fixed-point",
+            InstClass::FpIntegerConversion => "conversion between floating-point and integer",
+            InstClass::FpDataProc1Src => "floating-point data-processing (1 source)",
+            InstClass::FpCompare => "floating-point compare",
+            InstClass::FpImm => "floating-point immediate",
+            InstClass::FpConditionalCompare => "floating-point conditional compare",
+            InstClass::FpDataProc2Src => "floating-point data-processing (2 source)",
+            InstClass::FpConditionalSelect => "floating-point conditional select",
+            InstClass::FpDataProc3Src => "floating-point data-processing (3 source)",
+        };
     }
-    return raw & ((1u64 << len) - 1); // truncate left side to len bits
 }
 
+/// The instruction class of binst, None for the groups that are not split
+/// into classes and for encodings outside every class of their group.
+fn inst_class(binst: u32) -> Option<InstClass> {
+    return match group_of(binst) {
+        Group::BranchExcSys => branch_exc_sys_class(binst),
+        Group::DataProcReg => data_proc_reg_class(binst),
+        Group::LoadStore => load_store_class(binst),
+        Group::DataProcSimdFp => simd_fp_class(binst),
+        _ => None,
+    };
+}
 
-/// Implementation of the A64 pseudocode function DecodeBitMasks (pp. 1683-1684).
-///
-/// The logical immediate instructions encode 32-bit or 64-bit masks using merely
-/// 12 or 13 bits. We want the decoded mask in our Inst.imm field. We only need
-/// the "wmask" of DecodeBitMasks, so return only that.
-fn decode_bitmask(immN: u8, imms: u8, immr: u8, w32: bool) -> u64 {
-    let M: u32 = if w32 { 32 } else { 64 };
+/// Why the decoder of class returns A64_UNKNOWN for binst: the encoding field
+/// that holds a reserved or unsupported value.
+fn inst_class_unknown_reason(binst: u32, class: InstClass) -> &'static str {
+    let sf = binst >> 31;
+    let S = (binst >> 29) & 1;
+    return match class {
+        InstClass::ConditionalBranch if (binst >> 4) & 1 == 1 => "o0 field: BC.cond not supported yet",
+        InstClass::ConditionalBranch => "reserved o1 field",
+        InstClass::ExceptionGeneration if (binst >> 2) & 0b111 != 0 => "reserved op2 field",
+        InstClass::ExceptionGeneration => "reserved opc, LL fields",
+        InstClass::BranchRegister if (binst & 0x001FFC1F) != 0x001F0000 => "op2, op3, op4 fields: only BR, BLR and RET are supported",
+        InstClass::BranchRegister => "opc field: only BR, BLR and RET are supported",
+        InstClass::DataProc1Src | InstClass::DataProc2Src if S == 1 => "reserved S field",
+        InstClass::DataProc1Src if (binst >> 16) & 0b11111 != 0 && !(sf == 1 && (binst >> 16) & 0b11111 == 1) => "reserved opcode2 field",
+        InstClass::DataProc1Src => "opcode field not supported yet",
+        InstClass::DataProc2Src => "opcode field: only the shifts by register are supported",
+        InstClass::LogicalShifted | InstClass::AddSubShifted if sf == 0 && (binst >> 15) & 1 == 1 => "reserved imm6 field for the 32-bit variant",
+        InstClass::AddSubShifted => "reserved shift field",
+        InstClass::DataProc3Src if (binst >> 29) & 0b11 != 0 => "reserved op54 field",
+        InstClass::DataProc3Src if sf == 0 => "op31 field: the long multiplies have no 32-bit variant",
+        InstClass::DataProc3Src => "reserved op31, o0 fields",
+        InstClass::RotateIntoFlags | InstClass::EvaluateIntoFlags => "reserved sf, op, S fields",
+        InstClass::ConditionalCompare if S == 0 => "reserved S field",
+        InstClass::ConditionalCompare => "reserved o2, o3 fields",
+        InstClass::LoadLiteral => "reserved opc field",
+        InstClass::LoadStorePair => "opc, L fields: reserved or STGP, not supported yet",
+        InstClass::AtomicMemory => "reserved o3, opc fields",
+        InstClass::CompareAndSwap => "Rt2 field must be 11111",
+        InstClass::LdaprStlrUnscaled | InstClass::LoadStoreUnsignedImm => "reserved size, opc fields",
+        InstClass::CryptoAes | InstClass::CryptoSha3Reg | InstClass::CryptoSha2Reg => "reserved size, opcode fields",
+        InstClass::SimdCopy => "reserved op, imm5, imm4 fields",
+        InstClass::SimdModifiedImm => "reserved op, cmode, o2 fields",
+        InstClass::SimdShiftImm => "reserved U, immh, opcode fields",
+        InstClass::SimdThreeSameFp16 | InstClass::SimdScalarThreeSameFp16 => "reserved U, a, opcode fields",
+        InstClass::SimdThreeDifferent | InstClass::SimdThreeSameExtra => "reserved or unsupported U, size, opcode fields",
+        InstClass::SimdTwoRegMisc | InstClass::SimdAcrossLanes | InstClass::SimdThreeSame | InstClass::SimdIndexedElement => "reserved or unsupported U, size, opcode fields",
+        InstClass::SimdScalarThreeSame | InstClass::SimdScalarPairwise => "reserved or unsupported U, size, opcode fields",
+        InstClass::FpDataProc1Src | InstClass::FpDataProc2Src | InstClass::FpImm => "reserved or unsupported M, S, ptype, opcode fields",
+        _ => "not supported yet",
+    };
+}
 
-    // Guarantee it's only the number of bits in the pseudocode signature.
-    let immN = immN & 1;
-    let imms = imms & 0b111111;
-    let immr = immr & 0b111111;
+/// Branches, Exception Generating and System instructions.
+fn branches_exc_sys(binst: u32) -> Inst {
+    return match branch_exc_sys_class(binst) {
+        Some(InstClass::Hint) => hint(((binst >> 5) & 0b1111111) as u8),
+        Some(InstClass::ExceptionGeneration) => exception(binst),
+        Some(InstClass::ConditionalBranch) => conditional_branch(binst),
+        Some(InstClass::BranchRegister) => branch_reg(binst),
+        Some(InstClass::BranchImmediate) => branch_imm(binst),
+        Some(InstClass::CompareBranch) => compare_branch(binst),
+        Some(InstClass::TestBranch) => test_branch(binst),
+        Some(InstClass::SystemRegisterMove) => system_register_move(binst),
+        _ => UNKNOWN_INST,
+    };
+}
+
+/// The instruction class of a Branches, Exception Generating and System
+/// encoding: op0 (bits 29..31), op1 (bits 12..25) and op2 (bits 0..4).
+fn branch_exc_sys_class(binst: u32) -> Option<InstClass> {
+    let op0 = binst >> 29;
+    let op1 = (binst >> 12) & 0x3FFF;
+    let op2 = binst & 0b11111;
+
+    return match op0 {
+        0b010 if op1 >> 13 == 0 => Some(InstClass::ConditionalBranch),
+        0b110 if op1 >> 12 == 0b00 => Some(InstClass::ExceptionGeneration),
+        0b110 if op1 == 0b01000000110001 => Some(InstClass::SystemRegisterArgument),
+        0b110 if op1 == 0b01000000110010 && op2 == 0b11111 => Some(InstClass::Hint),
+        0b110 if op1 == 0b01000000110011 => Some(InstClass::Barrier),
+        0b110 if (op1 & 0b11111110001111) == 0b01000000000100 => Some(InstClass::Pstate),
+        0b110 if (op1 & 0b11110110000000) == 0b01000010000000 => Some(InstClass::SystemInstruction),
+        0b110 if (op1 & 0b11110100000000) == 0b01000100000000 => Some(InstClass::SystemRegisterMove),
+        0b110 if op1 >> 13 == 1 => Some(InstClass::BranchRegister),
+        0b000 | 0b100 => Some(InstClass::BranchImmediate),
+        0b001 | 0b101 if op1 >> 13 == 0 => Some(InstClass::CompareBranch),
+        0b001 | 0b101 => Some(InstClass::TestBranch),
+        _ => None,
+    };
+}
+
+/// Conditional branch (immediate) -- B.cond, the condition in the flags
+fn conditional_branch(binst: u32) -> Inst {
+    // 0101 0100 imm19 o0 cond, o1 (bit 24) = 0; o0 = 1 is BC.cond (FEAT_HBC)
+    if (binst & 0x01000010) != 0 {
+        return UNKNOWN_INST;
+    }
+    let mut inst = UNKNOWN_INST;
+    inst.op = Op::A64_BCOND;
+    inst.flags = set_cond(inst.flags, (binst & 0b1111) as u8);
+    inst.offset = sext(((binst >> 5) & 0x7FFFF) as u64, 19) << 2;
+    return inst;
+}
+
+/// Unconditional branch (immediate) -- B, BL
+fn branch_imm(binst: u32) -> Inst {
+    let mut inst = UNKNOWN_INST;
+    inst.op = if binst >> 31 == 0 { Op::A64_B } else { Op::A64_BL };
+    inst.offset = sext((binst & 0x3FFFFFF) as u64, 26) << 2;
+    return inst;
+}
+
+/// Compare and branch (immediate) -- CBZ, CBNZ: sf 011010 op imm19 Rt
+fn compare_branch(binst: u32) -> Inst {
+    let mut inst = UNKNOWN_INST;
+    inst.op = if (binst >> 24) & 1 == 0 { Op::A64_CBZ } else { Op::A64_CBNZ };
+    if binst >> 31 == 0 {
+        inst.flags |= W32;
+    }
+    inst.rd = regRd(binst);
+    inst.offset = sext(((binst >> 5) & 0x7FFFF) as u64, 19) << 2;
+    return inst;
+}
+
+/// Test and branch (immediate) -- TBZ, TBNZ: b5 011011 op b40(5) imm14 Rt
+fn test_branch(binst: u32) -> Inst {
+    let mut inst = UNKNOWN_INST;
+    let b5 = binst >> 31;
+    inst.op = if (binst >> 24) & 1 == 0 { Op::A64_TBZ } else { Op::A64_TBNZ };
+    if b5 == 0 {
+        inst.flags |= W32;
+    }
+    inst.rd = regRd(binst);
+    inst.tbz.bit = (b5 << 5) | ((binst >> 19) & 0b11111);
+    inst.tbz.offset = (sext(((binst >> 5) & 0x3FFF) as u64, 14) << 2) as i32;
+    return inst;
+}
+
+/// System register move -- MRS, MSR (register): 1101 0101 00 L 1 o0 op1(3)
+/// CRn CRm op2(3) Rt. Inst.imm := op0:op1:CRn:CRm:op2, the 16-bit encoding
+/// of the system register that the Arm ARM and LLVM use, e.g. 0xde82 for
+/// TPIDR_EL0; op0 is 2 + o0.
+fn system_register_move(binst: u32) -> Inst {
+    let mut inst = UNKNOWN_INST;
+    inst.op = if (binst >> 21) & 1 == 1 { Op::A64_MRS } else { Op::A64_MSR_REG };
+    inst.imm = (0x8000 | ((binst >> 5) & 0x7FFF)) as u64;
+    inst.rd = regRd(binst);
+    return inst;
+}
+
+/// Unconditional branch (register) -- Inst.rn := Xn
+///
+/// Only the plain BR, BLR and RET are decoded; the pointer authenticating
+/// variants (BRAA, RETAA, ...) and ERET/DRPS are left unknown.
+fn branch_reg(binst: u32) -> Inst {
+    // 1101 011 opc(4) op2(5) op3(6) Rn op4(5), op2 = 11111, op3 = op4 = 0
+    if (binst & 0x001FFC1F) != 0x001F0000 {
+        return UNKNOWN_INST;
+    }
+    let mut inst = UNKNOWN_INST;
+    inst.op = match (binst >> 21) & 0b1111 {
+        0b0000 => Op::A64_BR,
+        0b0001 => Op::A64_BLR,
+        0b0010 => Op::A64_RET,
+        _ => return UNKNOWN_INST,
+    };
+    inst.rn = regRn(binst);
+    return inst;
+}
+
+/// Exception generation -- Inst.imm := imm16
+fn exception(binst: u32) -> Inst {
+    let mut inst = UNKNOWN_INST;
+    let opc = (binst >> 21) & 0b111;
+    let op2 = (binst >> 2) & 0b111;
+    let LL = binst & 0b11;
+
+    if op2 != 0 {
+        return UNKNOWN_INST;
+    }
+    inst.op = match (opc, LL) {
+        (0b000, 0b01) => Op::A64_SVC,
+        (0b000, 0b10) => Op::A64_HVC,
+        (0b000, 0b11) => Op::A64_SMC,
+        (0b001, 0b00) => Op::A64_BRK,
+        (0b010, 0b00) => Op::A64_HLT,
+        (0b101, 0b01) => Op::A64_DCPS1,
+        (0b101, 0b10) => Op::A64_DCPS2,
+        (0b101, 0b11) => Op::A64_DCPS3,
+        _ => return UNKNOWN_INST,
+    };
+    inst.imm = ((binst >> 5) & 0xFFFF) as u64;
+    return inst;
+}
+
+/// Decodes the hint number CRm:op2. Pointer authentication instructions
+/// were allocated in the hint space so that they execute as NOPs on older
+/// cores, but they are decoded to their PAC opcodes here.
+fn hint(imm: u8) -> Inst {
+    let mut inst = UNKNOWN_INST;
+
+    let (op, rd, rn) = match imm {
+        7 => (Op::A64_XPACI, 30, 0), // XPACLRI
+        8 => (Op::A64_PACIA, 17, 16), // PACIA1716
+        10 => (Op::A64_PACIB, 17, 16), // PACIB1716
+        12 => (Op::A64_AUTIA, 17, 16), // AUTIA1716
+        14 => (Op::A64_AUTIB, 17, 16), // AUTIB1716
+        24 => (Op::A64_PACIA, 30, ZERO_REG), // PACIAZ
+        25 => (Op::A64_PACIA, 30, STACK_POINTER), // PACIASP
+        26 => (Op::A64_PACIB, 30, ZERO_REG), // PACIBZ
+        27 => (Op::A64_PACIB, 30, STACK_POINTER), // PACIBSP
+        28 => (Op::A64_AUTIA, 30, ZERO_REG), // AUTIAZ
+        29 => (Op::A64_AUTIA, 30, STACK_POINTER), // AUTIASP
+        30 => (Op::A64_AUTIB, 30, ZERO_REG), // AUTIBZ
+        31 => (Op::A64_AUTIB, 30, STACK_POINTER), // AUTIBSP
+        _ => {
+            inst.op = Op::A64_HINT;
+            inst.imm = imm as u64;
+            return inst;
+        }
+    };
+
+    inst.op = op;
+    inst.rd = rd;
+    inst.rn = rn;
+    return inst;
+}
+
+/// Data Processing -- Register
+fn data_proc_reg(binst: u32) -> Inst {
+    return match data_proc_reg_class(binst) {
+        Some(InstClass::DataProc1Src) => data_proc_1src(binst),
+        Some(InstClass::DataProc2Src) => data_proc_2src(binst),
+        Some(InstClass::LogicalShifted) => logical_shifted(binst),
+        Some(InstClass::AddSubShifted) => add_sub_shifted(binst),
+        Some(InstClass::DataProc3Src) => data_proc_3src(binst),
+        Some(InstClass::RotateIntoFlags | InstClass::EvaluateIntoFlags) => rotate_evaluate_into_flags(binst),
+        Some(InstClass::ConditionalCompare) => conditional_compare(binst),
+        _ => UNKNOWN_INST,
+    };
+}
+
+/// The instruction class of a Data Processing -- Register encoding: op0
+/// (bit 30), op1 (bit 28), op2 (bits 21..24) and op3 (bits 10..15).
+fn data_proc_reg_class(binst: u32) -> Option<InstClass> {
+    let op0 = (binst >> 30) & 1;
+    let op1 = (binst >> 28) & 1;
+    let op2 = (binst >> 21) & 0b1111;
+    let op3 = (binst >> 10) & 0b111111;
+
+    return match (op1, op2) {
+        (1, 0b0110) if op0 == 1 => Some(InstClass::DataProc1Src),
+        (1, 0b0110) => Some(InstClass::DataProc2Src),
+        (0, 0b0000..=0b0111) => Some(InstClass::LogicalShifted),
+        (0, _) if op2 & 1 == 0 => Some(InstClass::AddSubShifted),
+        (0, _) => Some(InstClass::AddSubExtended),
+        (1, 0b1000..=0b1111) => Some(InstClass::DataProc3Src),
+        (1, 0b0000) if op3 == 0 => Some(InstClass::AddSubCarry),
+        (1, 0b0000) if (op3 & 0b011111) == 0b000001 => Some(InstClass::RotateIntoFlags),
+        (1, 0b0000) if (op3 & 0b001111) == 0b000010 => Some(InstClass::EvaluateIntoFlags),
+        (1, 0b0010) => Some(InstClass::ConditionalCompare),
+        (1, 0b0100) => Some(InstClass::ConditionalSelect),
+        _ => None,
+    };
+}
+
+/// Data-processing (2 source). Only the shifts by register are decoded.
+fn data_proc_2src(binst: u32) -> Inst {
+    let mut inst = UNKNOWN_INST;
+    let sf = (binst >> 31) & 1;
+    let S = (binst >> 29) & 1;
+    let opcode = (binst >> 10) & 0b111111;
+
+    if S != 0 {
+        return UNKNOWN_INST;
+    }
+    inst.op = match opcode {
+        0b001000 => Op::A64_LSLV,
+        0b001001 => Op::A64_LSRV,
+        0b001010 => Op::A64_ASRV,
+        0b001011 => Op::A64_RORV,
+        _ => return UNKNOWN_INST,
+    };
+    if sf == 0 {
+        inst.flags |= W32;
+    }
+    inst.rd = regRd(binst);
+    inst.rn = regRn(binst);
+    inst.rm = regRm(binst);
+    return inst;
+}
+
+/// Conditional compare (register and immediate). The immediate form keeps
+/// imm5 unsigned, CCMP compares against its zero extension.
+fn conditional_compare(binst: u32) -> Inst {
+    let mut inst = UNKNOWN_INST;
+    // sf op 1 11010010 imm5/Rm cond 1/0 o2 Rn o3 nzcv
+    if (binst & 0x20000410) != 0x20000000 {
+        return UNKNOWN_INST;
+    }
+    let is_ccmp = (binst >> 30) & 1 == 1;
+    if (binst >> 11) & 1 == 1 {
+        inst.op = if is_ccmp { Op::A64_CCMP_IMM } else { Op::A64_CCMN_IMM };
+        inst.ccmp.imm5 = (binst >> 16) & 0b11111;
+    } else {
+        inst.op = if is_ccmp { Op::A64_CCMP_REG } else { Op::A64_CCMN_REG };
+        inst.rm = regRm(binst);
+    }
+    if (binst >> 31) == 0 {
+        inst.flags |= W32;
+    }
+    inst.flags = set_cond(inst.flags, ((binst >> 12) & 0b1111) as u8);
+    inst.ccmp.nzcv = binst & 0b1111;
+    inst.rn = regRn(binst);
+    return inst;
+}
+
+/// Rotate right into flags (RMIF) and evaluate into flags (SETF8, SETF16).
+/// Add/subtract with carry shares the encoding space but is not decoded.
+fn rotate_evaluate_into_flags(binst: u32) -> Inst {
+    let mut inst = UNKNOWN_INST;
+
+    // RMIF: 1 0 1 11010000 imm6 00001 Rn 0 mask
+    if (binst & 0xFFE07C10) == 0xBA000400 {
+        inst.op = Op::A64_RMIF;
+        inst.rmif.ror = (binst >> 15) & 0b111111;
+        inst.rmif.mask = binst & 0b1111;
+        inst.rn = regRn(binst);
+        return inst;
+    }
+    // SETF8, SETF16: 0 0 1 11010000 000000 sz 0010 Rn 0 1101
+    if (binst & 0xFFFFBC1F) == 0x3A00080D {
+        inst.op = if (binst >> 14) & 1 == 0 { Op::A64_SETF8 } else { Op::A64_SETF16 };
+        inst.flags |= W32;
+        inst.rn = regRn(binst);
+        return inst;
+    }
+    return UNKNOWN_INST;
+}
+
+/// Data-processing (3 source) -- Inst.ra := Ra
+///
+/// W32 is set for the 32-bit MADD and MSUB only. The long multiplies always
+/// produce a 64-bit result from W sources, see operand_widths.
+fn data_proc_3src(binst: u32) -> Inst {
+    let mut inst = UNKNOWN_INST;
+    let sf = (binst >> 31) & 1;
+    let op54 = (binst >> 29) & 0b11;
+    let op31 = (binst >> 21) & 0b111;
+    let o0 = (binst >> 15) & 1;
+    let ra = ((binst >> 10) & 0b11111) as u8;
+
+    if op54 != 0 || (sf == 0 && op31 != 0b000) {
+        return UNKNOWN_INST;
+    }
+    let no_addend = ra == ZERO_REG;
+    inst.op = match (op31, o0) {
+        (0b000, 0) => if no_addend { Op::A64_MUL } else { Op::A64_MADD },
+        (0b000, 1) => if no_addend { Op::A64_MNEG } else { Op::A64_MSUB },
+        (0b001, 0) => if no_addend { Op::A64_SMULL } else { Op::A64_SMADDL },
+        (0b001, 1) => if no_addend { Op::A64_SMNEGL } else { Op::A64_SMSUBL },
+        (0b010, 0) => Op::A64_SMULH,
+        (0b101, 0) => if no_addend { Op::A64_UMULL } else { Op::A64_UMADDL },
+        (0b101, 1) => if no_addend { Op::A64_UMNEGL } else { Op::A64_UMSUBL },
+        (0b110, 0) => Op::A64_UMULH,
+        _ => return UNKNOWN_INST,
+    };
+    if sf == 0 {
+        inst.flags |= W32;
+    }
+    inst.rd = regRd(binst);
+    inst.rn = regRn(binst);
+    inst.rm = regRm(binst);
+    inst.ra = ra;
+    return inst;
+}
+
+/// Decodes the registers and the shifted register operand shared by
+/// the logical and add/subtract (shifted register) instructions.
+fn shifted_register(binst: u32) -> Option<Inst> {
+    let mut inst = UNKNOWN_INST;
+    let sf = (binst >> 31) & 1;
+    let amount = (binst >> 10) & 0b111111;
+
+    if sf == 0 {
+        if amount >= 32 {
+            return None;
+        }
+        inst.flags |= W32;
+    }
+    inst.shift = InstShift { typ: (binst >> 22) & 0b11, amount };
+    inst.rd = regRd(binst);
+    inst.rn = regRn(binst);
+    inst.rm = regRm(binst);
+    return Some(inst);
+}
+
+/// Logical (shifted register)
+fn logical_shifted(binst: u32) -> Inst {
+    let opc = (binst >> 29) & 0b11;
+    let N = (binst >> 21) & 1;
+    let mut inst = match shifted_register(binst) {
+        Some(inst) => inst,
+        None => return UNKNOWN_INST,
+    };
+    let no_shift = inst.shift.typ == Shift::SH_LSL as u32 && inst.shift.amount == 0;
+
+    inst.op = match (opc, N) {
+        (0b00, 0) => Op::A64_AND_SHIFTED,
+        (0b00, _) => Op::A64_BIC,
+        (0b01, 0) if no_shift && inst.rn == ZERO_REG => Op::A64_MOV_REG,
+        (0b01, 0) => Op::A64_ORR_SHIFTED,
+        (0b01, _) if inst.rn == ZERO_REG => Op::A64_MVN,
+        (0b01, _) => Op::A64_ORN,
+        (0b10, 0) => Op::A64_EOR_SHIFTED,
+        (0b10, _) => Op::A64_EON,
+        (_, 0) if inst.rd == ZERO_REG => Op::A64_TST_SHIFTED,
+        (_, 0) => Op::A64_AND_SHIFTED,
+        (_, _) => Op::A64_BIC,
+    };
+    if opc == 0b11 {
+        inst.flags |= SET_FLAGS;
+    }
+    return inst;
+}
+
+/// Add/subtract (shifted register)
+fn add_sub_shifted(binst: u32) -> Inst {
+    let op = (binst >> 30) & 1;
+    let S = (binst >> 29) & 1;
+    let mut inst = match shifted_register(binst) {
+        Some(inst) if inst.shift.typ != Shift::SH_ROR as u32 => inst,
+        _ => return UNKNOWN_INST,
+    };
+
+    inst.op = match (op, S) {
+        (0, 1) if inst.rd == ZERO_REG => Op::A64_CMN_SHIFTED,
+        (0, _) => Op::A64_ADD_SHIFTED,
+        (1, 1) if inst.rd == ZERO_REG => Op::A64_CMP_SHIFTED,
+        (1, _) if inst.rn == ZERO_REG => Op::A64_NEG,
+        (_, _) => Op::A64_SUB_SHIFTED,
+    };
+    if S == 1 {
+        inst.flags |= SET_FLAGS;
+    }
+    return inst;
+}
+
+/// Data-processing (1 source)
+fn data_proc_1src(binst: u32) -> Inst {
+    let sf = (binst >> 31) & 1;
+    let S = (binst >> 29) & 1;
+    let opcode2 = (binst >> 16) & 0b11111;
+    let opcode = (binst >> 10) & 0b111111;
+
+    if S == 0 && sf == 1 && opcode2 == 0b00001 {
+        return pointer_auth(binst, opcode);
+    }
+    if S == 1 || opcode2 != 0 {
+        return UNKNOWN_INST;
+    }
+
+    let mut inst = UNKNOWN_INST;
+    inst.op = match opcode {
+        0b000100 => Op::A64_CLZ,
+        0b000101 => Op::A64_CLS,
+        _ => return UNKNOWN_INST,
+    };
+    if sf == 0 {
+        inst.flags |= W32;
+    }
+    inst.rd = regRd(binst);
+    inst.rn = regRn(binst);
+    return inst;
+}
+
+/// PACxx, AUTxx and XPACx in the data-processing (1 source) encoding, where
+/// the low three bits of opcode select the instruction and key.
+fn pointer_auth(binst: u32, opcode: u32) -> Inst {
+    let mut inst = UNKNOWN_INST;
+    let ops = [
+        Op::A64_PACIA, Op::A64_PACIB, Op::A64_PACDA, Op::A64_PACDB,
+        Op::A64_AUTIA, Op::A64_AUTIB, Op::A64_AUTDA, Op::A64_AUTDB,
+    ];
+    let rn_is_zr = regRn(binst) == ZERO_REG;
+
+    inst.rd = regRd(binst);
+    match opcode {
+        0b000000..=0b000111 => {
+            inst.op = ops[opcode as usize].clone();
+            inst.rn = regRnSP(binst);
+        }
+        0b001000..=0b001111 if rn_is_zr => { // PACIZA, ..., AUTDZB
+            inst.op = ops[(opcode & 0b111) as usize].clone();
+            inst.rn = ZERO_REG;
+        }
+        0b010000 if rn_is_zr => inst.op = Op::A64_XPACI,
+        0b010001 if rn_is_zr => inst.op = Op::A64_XPACD,
+        _ => return UNKNOWN_INST,
+    }
+
+    return inst;
+}
+
+/// Loads and Stores
+///
+/// The transfer register Rt is stored in Inst.rd, the base register in
+/// Inst.rn. The access size and sign extension are stored as ExtendType in
+/// the flags (see fad_get_mem_extend); loads and stores of SIMD&FP registers
+/// store the FPSize there instead.
+///
+/// Writes into inst, which is UNKNOWN_INST on entry. The common literal, pair
+/// and unsigned immediate forms are decoded in place, the others moved in.
+fn loads_and_stores_into(binst: u32, inst: &mut Inst) {
+    match load_store_class(binst) {
+        Some(InstClass::LoadLiteral) => load_literal_into(binst, inst),
+        Some(InstClass::LoadStorePair) => load_store_pair_into(binst, inst),
+        Some(InstClass::AtomicMemory) => move_into(inst, atomic_memory_op(binst)),
+        Some(InstClass::CompareAndSwap) => move_into(inst, compare_and_swap(binst)),
+        Some(InstClass::LoadStoreOrdered) => move_into(inst, load_store_ordered(binst)),
+        Some(InstClass::LdaprStlrUnscaled) => move_into(inst, ldapr_stlr_unscaled(binst)),
+        Some(InstClass::LoadStoreUnsignedImm) => load_store_unsigned_imm_into(binst, inst),
+        _ => {}
+    }
+}
+
+/// The instruction class of a Loads and Stores encoding: op0 (bits 28..31),
+/// op1 (bit 26), op2 (bits 23..24), op3 (bits 16..21) and op4 (bits 10..11).
+fn load_store_class(binst: u32) -> Option<InstClass> {
+    let op0 = (binst >> 28) & 0b1111;
+    let op1 = (binst >> 26) & 1;
+    let op2 = (binst >> 23) & 0b11;
+    let op3 = (binst >> 16) & 0b111111;
+    let op4 = (binst >> 10) & 0b11;
+    let op3_high = op3 >> 5;
+
+    return match (op0 & 0b0011, op2 >> 1) {
+        (0b0001, 0) => Some(InstClass::LoadLiteral),
+        (0b0010, _) => Some(InstClass::LoadStorePair),
+        (0b0011, 1) => Some(InstClass::LoadStoreUnsignedImm),
+        (0b0011, 0) => Some(match (op3_high, op4) {
+            (1, 0b00) => InstClass::AtomicMemory,
+            (1, 0b10) => InstClass::LoadStoreRegisterOffset,
+            (1, _) => InstClass::LoadStorePac,
+            (_, 0b00) => InstClass::LoadStoreUnscaledImm,
+            (_, 0b01) => InstClass::LoadStorePostIndexed,
+            (_, 0b10) => InstClass::LoadStoreUnprivileged,
+            (_, _) => InstClass::LoadStorePreIndexed,
+        }),
+        (0b0000, _) if op1 == 1 => if op0 & 0b1000 == 0 { Some(InstClass::SimdLoadStoreStructures) } else { None },
+        (0b0000, _) if op3_high == 1 && (op2 == 0b01 || (op2 == 0b00 && (op0 & 0b1000) == 0)) => Some(InstClass::CompareAndSwap),
+        (0b0000, _) if op2 == 0b00 => Some(InstClass::LoadStoreExclusive),
+        (0b0000, _) if op2 == 0b01 => Some(InstClass::LoadStoreOrdered),
+        (0b0001, 1) if op1 == 0 && op3_high == 0 && op4 == 0b00 => Some(InstClass::LdaprStlrUnscaled),
+        (0b0001, 1) if op0 == 0b1101 && op1 == 0 && op3_high == 1 => Some(InstClass::MemoryTags),
+        (0b0001, 1) if op1 == 0 && op3_high == 0 && op4 == 0b01 => Some(InstClass::MemoryCopySet),
+        _ => None,
+    };
+}
+
+/// Load/store no-allocate pair (offset), load/store register pair (post-indexed,
+/// offset, pre-indexed) -- the second transfer register Rt2 is stored in Inst.rt2.
+fn load_store_pair_into(binst: u32, inst: &mut Inst) {
+    let opc = (binst >> 30) & 0b11;
+    let simd = (binst >> 26) & 1 == 1;
+    let idx = (binst >> 23) & 0b11;
+    let load = (binst >> 22) & 1 == 1;
+
+    let scale = match (simd, opc) {
+        (false, 0b00) => {
+            inst.flags |= W32;
+            inst.flags = set_mem_extend(inst.flags, ExtendType::UXTW);
+            2
+        }
+        (false, 0b01) if load && idx != 0b00 => { // LDPSW
+            inst.flags = set_mem_extend(inst.flags, ExtendType::SXTW);
+            2
+        }
+        (false, 0b10) => {
+            inst.flags = set_mem_extend(inst.flags, ExtendType::UXTX);
+            3
+        }
+        (true, 0b00) => {
+            inst.flags = set_mem_extend(inst.flags, FPSize::FSZ_S);
+            2
+        }
+        (true, 0b01) => {
+            inst.flags = set_mem_extend(inst.flags, FPSize::FSZ_D);
+            3
+        }
+        (true, 0b10) => {
+            inst.flags = set_mem_extend(inst.flags, FPSize::FSZ_Q);
+            4
+        }
+        _ => return reset_inst(inst), // includes STGP
+    };
+
+    let (mode, nontemporal) = match idx {
+        0b00 => (AddrMode::AM_OFF_IMM, true),
+        0b01 => (AddrMode::AM_POST, false),
+        0b10 => (AddrMode::AM_OFF_IMM, false),
+        _ => (AddrMode::AM_PRE, false),
+    };
+    inst.op = match (nontemporal, simd, load) {
+        (true, false, true) => Op::A64_LDNP,
+        (true, false, false) => Op::A64_STNP,
+        (true, true, true) => Op::A64_LDNP_FP,
+        (true, true, false) => Op::A64_STNP_FP,
+        (false, false, true) => Op::A64_LDP,
+        (false, false, false) => Op::A64_STP,
+        (false, true, true) => Op::A64_LDP_FP,
+        (false, true, false) => Op::A64_STP_FP,
+    };
+    inst.flags = set_addrmode(inst.flags, mode);
+    inst.offset = sext(((binst >> 15) & 0b1111111) as u64, 7) << scale;
+    inst.rd = regRd(binst);
+    inst.rt2 = ((binst >> 10) & 0b11111) as u8;
+    inst.rn = regRnSP(binst);
+}
+
+/// Atomic memory operations -- Inst.rs := Rs, Inst.rd := Rt, Inst.rn := Xn|SP
+///
+/// The access size is stored as the memory extension (UXTB ... UXTX), the
+/// ordering (A, R bits) in Inst.ldst_order. LDAPR lives in this encoding too.
+///
+/// Later extensions allocated further instructions in this encoding: the
+/// ARMv8.7 LS64 single-copy atomic 64-byte loads and stores (LD64B, ST64B,
+/// ST64BV, ST64BV0) at o3:opc values LSE leaves unallocated, and the
+/// floating-point atomics with V = 1. These are refused with an error instead
+/// of being mistaken for an LSE atomic.
+fn atomic_memory_op(binst: u32) -> Inst {
+    let V = (binst >> 26) & 1;
+    let o3 = (binst >> 15) & 1;
+    let opc = (binst >> 12) & 0b111;
+
+    if V == 1 || (o3 == 1 && opc != 0b000 && opc != 0b100) {
+        return errinst("LS64/unsupported atomic extension".to_string());
+    }
+
+    let mut inst = UNKNOWN_INST;
+    let size = (binst >> 30) as u8;
+    let A = (binst >> 23) & 1;
+    let R = (binst >> 22) & 1;
+    let rs = ((binst >> 16) & 0b11111) as u8;
+
+    inst.op = match (o3, opc) {
+        (0, 0b000) => Op::A64_LDADD,
+        (0, 0b001) => Op::A64_LDCLR,
+        (0, 0b010) => Op::A64_LDEOR,
+        (0, 0b011) => Op::A64_LDSET,
+        (0, 0b100) => Op::A64_LDSMAX,
+        (0, 0b101) => Op::A64_LDSMIN,
+        (0, 0b110) => Op::A64_LDUMAX,
+        (0, 0b111) => Op::A64_LDUMIN,
+        (1, 0b000) => Op::A64_SWP,
+        _ if A == 1 && R == 0 && rs == 0b11111 => Op::A64_LDAPR,
+        _ => return UNKNOWN_INST,
+    };
+    if inst.op == Op::A64_LDAPR {
+        inst.ldst_order.load = MemOrdering::MO_ACQUIRE_PC as u16;
+    } else {
+        inst.rs = rs;
+        if A == 1 {
+            inst.ldst_order.load = MemOrdering::MO_ACQUIRE as u16;
+        }
+        if R == 1 {
+            inst.ldst_order.store = MemOrdering::MO_RELEASE as u16;
+        }
+    }
+    if size != Size::SZ_X {
+        inst.flags |= W32;
+    }
+    inst.flags = set_mem_extend(inst.flags, size); // UXTB ... UXTX
+    inst.flags = set_addrmode(inst.flags, AddrMode::AM_SIMPLE);
+    inst.rd = regRd(binst);
+    inst.rn = regRnSP(binst);
+    return inst;
+}
+
+/// Compare and swap (CAS) and compare and swap pair (CASP), where o2 = 0
+/// selects the latter.
+fn compare_and_swap(binst: u32) -> Inst {
+    if (binst >> 10) & 0b11111 != 0b11111 {
+        return UNKNOWN_INST; // Rt2
+    }
+
+    let mut inst = UNKNOWN_INST;
+    let pair = (binst >> 23) & 1 == 0;
+    let L = (binst >> 22) & 1;
+    let o0 = (binst >> 15) & 1;
+    let rs = ((binst >> 16) & 0b11111) as u8;
+    let rt = regRd(binst);
+
+    let size = if pair {
+        if rs & 1 != 0 {
+            return field_errinst("Rs", rs as u32);
+        }
+        if rt & 1 != 0 {
+            return field_errinst("Rt", rt as u32);
+        }
+        inst.op = Op::A64_CASP;
+        Size::SZ_W + ((binst >> 30) & 1) as u8
+    } else {
+        inst.op = Op::A64_CAS;
+        (binst >> 30) as u8
+    };
+
+    if L == 1 {
+        inst.ldst_order.load = MemOrdering::MO_ACQUIRE as u16;
+    }
+    if o0 == 1 {
+        inst.ldst_order.store = MemOrdering::MO_RELEASE as u16;
+    }
+    if size != Size::SZ_X {
+        inst.flags |= W32;
+    }
+    inst.flags = set_mem_extend(inst.flags, size);
+    inst.flags = set_addrmode(inst.flags, AddrMode::AM_SIMPLE);
+    inst.rs = rs;
+    inst.rd = rt;
+    inst.rn = regRnSP(binst);
+    return inst;
+}
+
+/// Load/store ordered -- LDAR, STLR and their LOR (limited ordering region)
+/// counterparts LDLAR, STLLR, decoded as LDR and STR with Inst.ldst_order
+/// set. The Rs and Rt2 fields are ignored, like LLVM does.
+fn load_store_ordered(binst: u32) -> Inst {
+    let mut inst = UNKNOWN_INST;
+    let size = (binst >> 30) as u8;
+    let L = (binst >> 22) & 1;
+    let o0 = (binst >> 15) & 1;
+
+    if L == 1 {
+        inst.op = Op::A64_LDR;
+        inst.ldst_order.load = if o0 == 1 { MemOrdering::MO_ACQUIRE } else { MemOrdering::MO_LO_ACQUIRE } as u16;
+    } else {
+        inst.op = Op::A64_STR;
+        inst.ldst_order.store = if o0 == 1 { MemOrdering::MO_RELEASE } else { MemOrdering::MO_LO_RELEASE } as u16;
+    }
+    if size != Size::SZ_X {
+        inst.flags |= W32;
+    }
+    inst.flags = set_mem_extend(inst.flags, size); // UXTB ... UXTX
+    inst.flags = set_addrmode(inst.flags, AddrMode::AM_SIMPLE);
+    inst.rd = regRd(binst);
+    inst.rn = regRnSP(binst);
+    return inst;
+}
+
+/// Load/store register (unsigned immediate) -- Inst.rd := Rt, Inst.rn := Xn|SP,
+/// Inst.offset := imm12 scaled by the access size
+///
+/// The access size and signedness are stored as the memory extension like for
+/// the literal loads: UXTB ... UXTX, SXTB ... SXTW for LDRS*, FSZ_B ... FSZ_Q
+/// for the SIMD&FP registers. PRFM keeps the prefetch operation in Inst.rd.
+fn load_store_unsigned_imm_into(binst: u32, inst: &mut Inst) {
+    let size = (binst >> 30) as u8;
+    let simd = (binst >> 26) & 1 == 1;
+    let opc = (binst >> 22) & 0b11;
+
+    let scale = if simd {
+        let (ext, scale) = match (size, opc >> 1) {
+            (_, 0) => (size, size), // FSZ_B ... FSZ_D
+            (Size::SZ_B, 1) => (FPSize::FSZ_Q, 4),
+            _ => return reset_inst(inst),
+        };
+        inst.op = if opc & 1 == 1 { Op::A64_LDR_FP } else { Op::A64_STR_FP };
+        inst.flags = set_mem_extend(inst.flags, ext);
+        scale
+    } else {
+        let (ext, w32) = match (opc, size) {
+            (0b00 | 0b01, _) => (size, size != Size::SZ_X), // UXTB ... UXTX
+            (0b10, Size::SZ_B) => (ExtendType::SXTB, false),
+            (0b10, Size::SZ_H) => (ExtendType::SXTH, false),
+            (0b10, Size::SZ_W) => (ExtendType::SXTW, false),
+            (0b10, _) => (Size::SZ_X, false), // PRFM
+            (0b11, Size::SZ_B) => (ExtendType::SXTB, true),
+            (0b11, Size::SZ_H) => (ExtendType::SXTH, true),
+            _ => return reset_inst(inst),
+        };
+        inst.op = match (opc, size) {
+            (0b00, _) => Op::A64_STR,
+            (0b10, Size::SZ_X) => Op::A64_PRFM,
+            _ => Op::A64_LDR,
+        };
+        if w32 {
+            inst.flags |= W32;
+        }
+        if inst.op != Op::A64_PRFM {
+            inst.flags = set_mem_extend(inst.flags, ext);
+        }
+        size
+    };
+    inst.flags = set_addrmode(inst.flags, AddrMode::AM_OFF_IMM);
+    inst.offset = (((binst >> 10) & 0xFFF) as i64) << scale;
+    inst.rd = regRd(binst);
+    inst.rn = regRnSP(binst);
+}
+
+/// LDAPR/STLR (unscaled immediate) -- the RCpc ordered LDAPUR* and STLUR*
+/// with a signed 9-bit offset, decoded as LDR and STR with Inst.ldst_order
+/// set like LDAPR and STLR.
+///
+/// STLUR is a plain Store-Release, like STLR: MO_LO_RELEASE is the
+/// LORegion release of STLLR (FEAT_LOR), which STLUR does not have.
+fn ldapr_stlr_unscaled(binst: u32) -> Inst {
+    let mut inst = UNKNOWN_INST;
+    let size = (binst >> 30) as u8;
+    let opc = (binst >> 22) & 0b11;
+
+    let (ext, w32) = match (opc, size) {
+        (0b00 | 0b01, _) => (size, size != Size::SZ_X), // UXTB ... UXTX
+        (0b10, Size::SZ_B) => (ExtendType::SXTB, false),
+        (0b10, Size::SZ_H) => (ExtendType::SXTH, false),
+        (0b10, Size::SZ_W) => (ExtendType::SXTW, false),
+        (0b11, Size::SZ_B) => (ExtendType::SXTB, true),
+        (0b11, Size::SZ_H) => (ExtendType::SXTH, true),
+        _ => return UNKNOWN_INST,
+    };
+    if opc == 0b00 {
+        inst.op = Op::A64_STR;
+        inst.ldst_order.store = MemOrdering::MO_RELEASE as u16;
+    } else {
+        inst.op = Op::A64_LDR;
+        inst.ldst_order.load = MemOrdering::MO_ACQUIRE_PC as u16;
+    }
+    if w32 {
+        inst.flags |= W32;
+    }
+    inst.flags = set_mem_extend(inst.flags, ext);
+    inst.flags = set_addrmode(inst.flags, AddrMode::AM_OFF_IMM);
+    inst.offset = sext(((binst >> 12) & 0x1FF) as u64, 9);
+    inst.rd = regRd(binst);
+    inst.rn = regRnSP(binst);
+    return inst;
+}
+
+/// Load register (literal) -- LDR, LDRSW, PRFM
+fn load_literal_into(binst: u32, inst: &mut Inst) {
+    let opc = (binst >> 30) & 0b11;
+    let simd = (binst >> 26) & 1 == 1;
+
+    inst.flags = set_addrmode(inst.flags, AddrMode::AM_LITERAL);
+    inst.offset = 4 * sext(((binst >> 5) & 0x7FFFF) as u64, 19);
+    inst.rd = regRd(binst);
+
+    if simd {
+        let size = match opc {
+            0b00 => FPSize::FSZ_S,
+            0b01 => FPSize::FSZ_D,
+            0b10 => FPSize::FSZ_Q,
+            _ => return reset_inst(inst),
+        };
+        inst.op = Op::A64_LDR_FP;
+        inst.flags = set_mem_extend(inst.flags, size);
+        return;
+    }
+
+    match opc {
+        0b00 => {
+            inst.op = Op::A64_LDR;
+            inst.flags |= W32;
+            inst.flags = set_mem_extend(inst.flags, ExtendType::UXTW);
+        }
+        0b01 => {
+            inst.op = Op::A64_LDR;
+            inst.flags = set_mem_extend(inst.flags, ExtendType::UXTX);
+        }
+        0b10 => { // LDRSW
+            inst.op = Op::A64_LDR;
+            inst.flags = set_mem_extend(inst.flags, ExtendType::SXTW);
+        }
+        _ => inst.op = Op::A64_PRFM, // prefetch operation in Rt
+    }
+}
+
+/// Data Processing -- Scalar Floating-Point and Advanced SIMD
+///
+/// Scalar FP instructions store their precision in the flags (see
+/// fad_get_prec), vector instructions their VectorArrangement (see
+/// fad_get_vec_arrangement). Rd, Rn and Rm are V registers.
+fn data_proc_simd_fp(binst: u32) -> Inst {
+    return match simd_fp_class(binst) {
+        Some(InstClass::FpDataProc2Src) => fp_data_proc_2src(binst),
+        Some(InstClass::FpDataProc1Src) => fp_data_proc_1src(binst),
+        Some(InstClass::FpImm) => fp_imm(binst),
+        Some(InstClass::CryptoAes) => crypto_aes(binst),
+        Some(InstClass::SimdThreeSameFp16) => simd_three_same_fp16(binst),
+        Some(InstClass::SimdTwoRegMisc) => simd_two_reg_misc(binst),
+        Some(InstClass::SimdAcrossLanes) => simd_across_lanes(binst),
+        Some(InstClass::SimdThreeSame) => simd_three_same(binst),
+        Some(InstClass::SimdThreeDifferent) => simd_three_different(binst),
+        Some(InstClass::SimdThreeSameExtra) => simd_three_same_extra(binst),
+        Some(InstClass::SimdCopy) => simd_copy(binst),
+        Some(InstClass::SimdModifiedImm) => simd_modified_imm(binst),
+        Some(InstClass::SimdShiftImm) => simd_shift_imm(binst),
+        Some(InstClass::SimdIndexedElement) => simd_indexed_element(binst),
+        Some(InstClass::CryptoSha3Reg | InstClass::CryptoSha2Reg) => crypto_sha(binst),
+        Some(InstClass::SimdScalarThreeSameFp16) => simd_scalar_three_same_fp16(binst),
+        Some(InstClass::SimdScalarThreeSame) => simd_scalar_three_same(binst),
+        Some(InstClass::SimdScalarPairwise) => simd_scalar_pairwise(binst),
+        _ => UNKNOWN_INST,
+    };
+}
+
+/// The instruction class of a Data Processing -- Scalar Floating-Point and
+/// Advanced SIMD encoding: op0 (bits 28..31), op1 (bits 23..24), op2 (bits
+/// 19..22) and op3 (bits 10..18).
+fn simd_fp_class(binst: u32) -> Option<InstClass> {
+    let op0 = (binst >> 28) & 0b1111;
+    let op1 = (binst >> 23) & 0b11;
+    let op2 = (binst >> 19) & 0b1111;
+    let op3 = (binst >> 10) & 0b111111111;
+
+    if (op0 & 0b0101) == 0b0001 {
+        return fp_class(binst);
+    }
+
+    // Cryptographic AES: 0100
+    if op0 == 0b0100 && (op1 & 0b10) == 0 && (op2 & 0b0111) == 0b0101 && (op3 & 0b110000011) == 0b10 {
+        return Some(InstClass::CryptoAes);
+    }
+    // Advanced SIMD (vector): 0xx0
+    if (op0 & 0b1001) == 0b0000 && (op1 & 0b10) == 0 {
+        if (op2 & 0b1100) == 0b1000 && (op3 & 0b000110001) == 0b000000001 {
+            return Some(InstClass::SimdThreeSameFp16);
+        }
+        if (op2 & 0b0111) == 0b0100 && (op3 & 0b110000011) == 0b000000010 {
+            return Some(InstClass::SimdTwoRegMisc);
+        }
+        if (op2 & 0b0111) == 0b0110 && (op3 & 0b110000011) == 0b000000010 {
+            return Some(InstClass::SimdAcrossLanes);
+        }
+        if (op2 & 0b0100) == 0b0100 && (op3 & 1) == 1 {
+            return Some(InstClass::SimdThreeSame);
+        }
+        if (op2 & 0b0100) == 0b0100 && (op3 & 0b11) == 0b00 {
+            return Some(InstClass::SimdThreeDifferent);
+        }
+        if (op2 & 0b0100) == 0 && (op3 & 0b000100001) == 0b000100001 {
+            return Some(InstClass::SimdThreeSameExtra);
+        }
+        if op1 == 0b00 && (op2 & 0b1100) == 0 && (op3 & 0b000100001) == 0b000000001 {
+            return Some(InstClass::SimdCopy);
+        }
+    }
+    if (op0 & 0b1001) == 0b0000 && op1 == 0b10 && op2 == 0 && (op3 & 1) == 1 {
+        return Some(InstClass::SimdModifiedImm);
+    }
+    if (op0 & 0b1001) == 0b0000 && op1 == 0b10 && op2 != 0 && (op3 & 1) == 1 {
+        return Some(InstClass::SimdShiftImm);
+    }
+    if (op0 & 0b1001) == 0b0000 && (op1 & 0b10) == 0b10 && (op3 & 1) == 0 {
+        return Some(InstClass::SimdIndexedElement);
+    }
+    // Cryptographic three-register SHA and two-register SHA: 0101
+    if op0 == 0b0101 && (op1 & 0b10) == 0 && (op2 & 0b0100) == 0 && (op3 & 0b000100011) == 0 {
+        return Some(InstClass::CryptoSha3Reg);
+    }
+    if op0 == 0b0101 && (op1 & 0b10) == 0 && (op2 & 0b0111) == 0b0101 && (op3 & 0b110000011) == 0b10 {
+        return Some(InstClass::CryptoSha2Reg);
+    }
+    // Advanced SIMD (scalar): 01x1
+    if (op0 & 0b1101) == 0b0101 && (op1 & 0b10) == 0 {
+        if (op2 & 0b1100) == 0b1000 && (op3 & 0b000110001) == 0b000000001 {
+            return Some(InstClass::SimdScalarThreeSameFp16);
+        }
+        if (op2 & 0b0100) == 0b0100 && (op3 & 1) == 1 {
+            return Some(InstClass::SimdScalarThreeSame);
+        }
+        if (op2 & 0b0111) == 0b0110 && (op3 & 0b110000011) == 0b000000010 {
+            return Some(InstClass::SimdScalarPairwise);
+        }
+    }
+
+    return None;
+}
+
+/// The element size (Size::SZ_*) and index that the imm5 field of the
+/// Advanced SIMD copy instructions encodes: the lowest set bit selects the
+/// size, the bits above it the index. None for the reserved x0000.
+fn copy_element(imm5: u32) -> Option<(u8, u32)> {
+    let size = imm5.trailing_zeros();
+    if size > 3 {
+        return None;
+    }
+    return Some((size as u8, imm5 >> (size + 1)));
+}
+
+/// Advanced SIMD copy (0 Q op 01110000 imm5 0 imm4 1 Rn Rd) -- DUP (element),
+/// DUP (general), INS (element), SMOV and UMOV so far. imm5 gives the element
+/// size, see copy_element; Q selects the 64- or 128-bit arrangement of it,
+/// or for SMOV and UMOV the W or X destination. INS (element) takes the
+/// source index from imm4, scaled like the one in imm5.
+fn simd_copy(binst: u32) -> Inst {
+    let mut inst = UNKNOWN_INST;
+    let Q = (binst >> 30) & 1;
+    let op = (binst >> 29) & 1;
+    let imm5 = (binst >> 16) & 0b11111;
+    let imm4 = (binst >> 11) & 0b1111;
+
+    let Some((size, index)) = copy_element(imm5) else {
+        return UNKNOWN_INST;
+    };
+    if size == Size::SZ_X && Q == 0 {
+        return UNKNOWN_INST; // 1D
+    }
+    let mut arrangement = (size << 1) | Q as u8;
+    match (op, imm4) {
+        (0, 0b0000) => {
+            inst.op = Op::A64_DUP_ELEM;
+            inst.imm = index as u64;
+        }
+        (0, 0b0001) => {
+            inst.op = Op::A64_DUP_GPR;
+            if size != Size::SZ_X {
+                inst.flags |= W32;
+            }
+        }
+        (1, _) if Q == 1 => {
+            inst.op = Op::A64_INS_ELEM;
+            inst.ins_elem.dst = index;
+            inst.ins_elem.src = imm4 >> size;
+        }
+        (0, 0b0101) | (0, 0b0111) => {
+            // SMOV extends B and H elements to W, and S elements too to X;
+            // UMOV moves B, H and S elements to W and D elements to X.
+            inst.op = if imm4 == 0b0101 { Op::A64_SMOV } else { Op::A64_UMOV };
+            match (imm4, Q, size) {
+                (0b0101, 0, Size::SZ_B..=Size::SZ_H) | (0b0111, 0, Size::SZ_B..=Size::SZ_W) => inst.flags |= W32,
+                (0b0101, 1, Size::SZ_B..=Size::SZ_W) | (0b0111, 1, Size::SZ_X) => {}
+                _ => return UNKNOWN_INST,
+            }
+            inst.imm = index as u64;
+            arrangement = (size << 1) | 1;
+        }
+        _ => return UNKNOWN_INST,
+    }
+    inst.flags = set_vec_arrangement(inst.flags, arrangement);
+    inst.rd = regRd(binst);
+    inst.rn = regRn(binst);
+    return inst;
+}
+
+/// Cryptographic AES (0100 1110 size 10100 opcode 10 Rn Rd)
+fn crypto_aes(binst: u32) -> Inst {
+    let mut inst = UNKNOWN_INST;
+    let size = (binst >> 22) & 0b11;
+
+    if size != 0 {
+        return UNKNOWN_INST;
+    }
+    inst.op = match (binst >> 12) & 0b11111 {
+        0b00100 => Op::A64_AESE,
+        0b00101 => Op::A64_AESD,
+        0b00110 => Op::A64_AESMC,
+        0b00111 => Op::A64_AESIMC,
+        _ => return UNKNOWN_INST,
+    };
+    inst.flags = set_vec_arrangement(inst.flags, VectorArrangement::VA_16B);
+    inst.rd = regRd(binst);
+    inst.rn = regRn(binst);
+    return inst;
+}
+
+/// Cryptographic three-register SHA (0101 1110 size 0 Rm 0 opcode 00 Rn Rd)
+/// and two-register SHA (0101 1110 size 10100 opcode 10 Rn Rd). All operate
+/// on 4S vectors or their lowest S element.
+fn crypto_sha(binst: u32) -> Inst {
+    let mut inst = UNKNOWN_INST;
+    let size = (binst >> 22) & 0b11;
+
+    if size != 0 {
+        return UNKNOWN_INST;
+    }
+    if (binst >> 21) & 1 == 0 {
+        inst.op = match (binst >> 12) & 0b111 {
+            0b000 => Op::A64_SHA1C,
+            0b001 => Op::A64_SHA1P,
+            0b010 => Op::A64_SHA1M,
+            0b011 => Op::A64_SHA1SU0,
+            0b100 => Op::A64_SHA256H,
+            0b101 => Op::A64_SHA256H2,
+            0b110 => Op::A64_SHA256SU1,
+            _ => return UNKNOWN_INST,
+        };
+        inst.rm = regRm(binst);
+    } else {
+        inst.op = match (binst >> 12) & 0b11111 {
+            0b00000 => Op::A64_SHA1H,
+            0b00001 => Op::A64_SHA1SU1,
+            0b00010 => Op::A64_SHA256SU0,
+            _ => return UNKNOWN_INST,
+        };
+    }
+    inst.flags = set_vec_arrangement(inst.flags, VectorArrangement::VA_4S);
+    inst.rd = regRd(binst);
+    inst.rn = regRn(binst);
+    return inst;
+}
+
+/// Scalar Floating-Point: conversions, data-processing, compare, select
+/// The instruction class of a scalar floating-point encoding, op0 = x0x1.
+fn fp_class(binst: u32) -> Option<InstClass> {
+    let op1 = (binst >> 23) & 0b11;
+    let op2 = (binst >> 19) & 0b1111;
+    let op3 = (binst >> 10) & 0b111111111;
+
+    if (op1 & 0b10) != 0 {
+        return Some(InstClass::FpDataProc3Src);
+    }
+    if (op2 & 0b0100) == 0 {
+        return Some(InstClass::FpFixedConversion);
+    }
+    return match op3 {
+        _ if (op3 & 0b11) == 0b10 => Some(InstClass::FpDataProc2Src),
+        _ if (op3 & 0b11) == 0b01 => Some(InstClass::FpConditionalCompare),
+        _ if (op3 & 0b11) == 0b11 => Some(InstClass::FpConditionalSelect),
+        _ if (op3 & 0b111) == 0b100 => Some(InstClass::FpImm),
+        _ if (op3 & 0b1111) == 0b1000 => Some(InstClass::FpCompare),
+        _ if (op3 & 0b11111) == 0b10000 => Some(InstClass::FpDataProc1Src),
+        _ if (op3 & 0b111111) == 0 => Some(InstClass::FpIntegerConversion),
+        _ => None,
+    };
+}
+/// Floating-point immediate -- FMOV (scalar, immediate)
+fn fp_imm(binst: u32) -> Inst {
+    let mut inst = UNKNOWN_INST;
+    let M = (binst >> 31) & 1;
+    let S = (binst >> 29) & 1;
+    let imm5 = (binst >> 5) & 0b11111;
+
+    let prec = match fp_type(binst) {
+        Some(prec) if M == 0 && S == 0 && imm5 == 0 => prec,
+        _ => return UNKNOWN_INST,
+    };
+    inst.op = Op::A64_FMOV_IMM;
+    inst.flags = set_prec(inst.flags, prec);
+    inst.fimm = vfp_expand_imm(((binst >> 13) & 0xFF) as u8);
+    inst.rd = regRd(binst);
+    return inst;
+}
+
+/// VFPExpandImm: the value of the 8-bit floating-point immediate
+/// a:b:cd:efgh of FMOV, ±(16 + efgh) / 16 × 2^e with e in -3..4. It is
+/// exact in every precision, half included.
+pub fn vfp_expand_imm(imm8: u8) -> f64 {
+    let sign = if imm8 & 0x80 != 0 { -1.0 } else { 1.0 };
+    let cd = ((imm8 >> 4) & 0b11) as i32;
+    let exponent = if imm8 & 0x40 == 0 { cd + 1 } else { cd - 3 };
+    let fraction = (imm8 & 0xF) as f64;
+    return sign * (16.0 + fraction) / 16.0 * 2f64.powi(exponent);
+}
+
+/// Floating-point data-processing (1 source) -- BFCVT and the rounds to
+/// integral only so far
+fn fp_data_proc_1src(binst: u32) -> Inst {
+    let mut inst = UNKNOWN_INST;
+    // M 0 S 11110 ftype 1 opcode 10000 Rn Rd
+    if binst & 0xFFFFFC00 == 0x1E634000 {
+        inst.op = Op::A64_BFCVT;
+        inst.rd = regRd(binst);
+        inst.rn = regRn(binst);
+        return inst;
+    }
+    let M = (binst >> 31) & 1;
+    let S = (binst >> 29) & 1;
+    let opcode = (binst >> 15) & 0b111111;
+
+    let prec = match fp_type(binst) {
+        Some(prec) if M == 0 && S == 0 => prec,
+        _ => return UNKNOWN_INST,
+    };
+    let (exact, mode, bits) = match opcode {
+        0b001000 => (false, FPRounding::FPR_TIE_EVEN, 0), // FRINTN
+        0b001001 => (false, FPRounding::FPR_POS_INF, 0), // FRINTP
+        0b001010 => (false, FPRounding::FPR_NEG_INF, 0), // FRINTM
+        0b001011 => (false, FPRounding::FPR_ZERO, 0), // FRINTZ
+        0b001100 => (false, FPRounding::FPR_TIE_AWAY, 0), // FRINTA
+        0b001110 => (true, FPRounding::FPR_CURRENT, 0), // FRINTX
+        0b001111 => (false, FPRounding::FPR_CURRENT, 0), // FRINTI
+        0b010000..=0b010011 if prec != FPSize::FSZ_H => {
+            // FRINT32Z, FRINT32X, FRINT64Z, FRINT64X
+            let exact = opcode & 1 != 0;
+            let mode = if exact { FPRounding::FPR_CURRENT } else { FPRounding::FPR_ZERO };
+            (exact, mode, if opcode & 0b10 == 0 { 32 } else { 64 })
+        }
+        _ => return UNKNOWN_INST,
+    };
+    inst.op = if exact { Op::A64_FRINTX } else { Op::A64_FRINT };
+    inst.frint.mode = mode as u32;
+    inst.frint.bits = bits;
+    inst.flags = set_prec(inst.flags, prec);
+    inst.rd = regRd(binst);
+    inst.rn = regRn(binst);
+    return inst;
+}
+
+/// Floating-point data-processing (2 source)
+fn fp_data_proc_2src(binst: u32) -> Inst {
+    let mut inst = UNKNOWN_INST;
+    let M = (binst >> 31) & 1;
+    let S = (binst >> 29) & 1;
+    let opcode = (binst >> 12) & 0b1111;
+
+    let prec = match fp_type(binst) {
+        Some(prec) if M == 0 && S == 0 => prec,
+        _ => return UNKNOWN_INST,
+    };
+
+    inst.op = match opcode {
+        0b0000 => Op::A64_FMUL,
+        0b0001 => Op::A64_FDIV,
+        0b0010 => Op::A64_FADD,
+        0b0011 => Op::A64_FSUB,
+        0b0100 => Op::A64_FMAX,
+        0b0101 => Op::A64_FMIN,
+        0b0110 => Op::A64_FMAXNM,
+        0b0111 => Op::A64_FMINNM,
+        0b1000 => Op::A64_FNMUL,
+        _ => return UNKNOWN_INST,
+    };
+    inst.flags = set_prec(inst.flags, prec);
+    inst.rd = regRd(binst);
+    inst.rn = regRn(binst);
+    inst.rm = regRm(binst);
+    return inst;
+}
+
+/// The ftype field of scalar FP instructions. Half precision (11) requires
+/// ARMv8.2-FP16.
+fn fp_type(binst: u32) -> Option<u8> {
+    return match (binst >> 22) & 0b11 {
+        0b00 => Some(FPSize::FSZ_S),
+        0b01 => Some(FPSize::FSZ_D),
+        0b11 => Some(FPSize::FSZ_H),
+        _ => None,
+    };
+}
+
+/// Advanced SIMD three same
+fn simd_three_same(binst: u32) -> Inst {
+    let opcode = (binst >> 11) & 0b11111;
+    if opcode == 0b00011 {
+        return simd_logical(binst);
+    }
+
+    if opcode >= 0b11000 {
+        let sz = (binst >> 22) & 1;
+        let Q = (binst >> 30) & 1;
+        let va = match (sz, Q) {
+            (0, _) => (FPSize::FSZ_S << 1) | Q as u8,
+            (_, 1) => VectorArrangement::VA_2D,
+            _ => return UNKNOWN_INST, // 1D
+        };
+        if sz == 0 {
+            // FP16 widening multiply-add: half-precision sources, single-precision destination
+            let op = match ((binst >> 29) & 1, (binst >> 23) & 1, opcode) {
+                (0, 0, 0b11101) => Some(Op::A64_FMLAL_VEC),
+                (0, 1, 0b11101) => Some(Op::A64_FMLSL_VEC),
+                (1, 0, 0b11001) => Some(Op::A64_FMLAL2_VEC),
+                (1, 1, 0b11001) => Some(Op::A64_FMLSL2_VEC),
+                _ => None,
+            };
+            if let Some(op) = op {
+                let mut inst = UNKNOWN_INST;
+                inst.op = op;
+                inst.flags = set_vec_arrangement(inst.flags, va);
+                inst.rd = regRd(binst);
+                inst.rn = regRn(binst);
+                inst.rm = regRm(binst);
+                return inst;
+            }
+        }
+        return simd_three_same_fp(binst, opcode, va);
+    }
+
+    let mut inst = UNKNOWN_INST;
+    let Q = (binst >> 30) & 1;
+    let U = (binst >> 29) & 1;
+    let size = ((binst >> 22) & 0b11) as u8;
+    let signed = if U == 0 { SIMD_SIGNED } else { 0 };
+
+    match (U, opcode) {
+        (_, 0b00000) if size != 0b11 => { // SHADD, UHADD
+            inst.op = Op::A64_HADD;
+            inst.flags |= signed;
+        }
+        (_, 0b00001) | (_, 0b00101) if size != 0b11 || Q == 1 => { // SQADD, UQADD, SQSUB, UQSUB
+            inst.op = if opcode == 0b00001 { Op::A64_QADD } else { Op::A64_QSUB };
+            inst.flags |= signed;
+        }
+        (_, 0b00010) if size != 0b11 => { // SRHADD, URHADD
+            inst.op = Op::A64_HADD;
+            inst.flags |= signed | SIMD_ROUND;
+        }
+        (_, 0b00100) if size != 0b11 => { // SHSUB, UHSUB
+            inst.op = Op::A64_HSUB;
+            inst.flags |= signed;
+        }
+        (_, 0b01000) | (_, 0b01010) if size != 0b11 || Q == 1 => { // SSHL, USHL, SRSHL, URSHL
+            inst.op = Op::A64_SHL_REG;
+            inst.flags |= signed | if opcode == 0b01010 { SIMD_ROUND } else { 0 };
+        }
+        (_, 0b01001) | (_, 0b01011) if size != 0b11 || Q == 1 => { // SQSHL, UQSHL, SQRSHL, UQRSHL
+            inst.op = Op::A64_QSHL_REG;
+            inst.flags |= signed | if opcode == 0b01011 { SIMD_ROUND } else { 0 };
+        }
+        (_, 0b01110) if size != 0b11 => { // SABD, UABD
+            inst.op = Op::A64_ABD;
+            inst.flags |= signed;
+        }
+        (_, 0b01111) if size != 0b11 => { // SABA, UABA
+            inst.op = Op::A64_ABA;
+            inst.flags |= signed;
+        }
+        (_, 0b01100) | (_, 0b01101) | (_, 0b10100) | (_, 0b10101) if size != 0b11 => { // SMAX, SMIN, SMAXP, SMINP
+            inst.op = match opcode {
+                0b01100 => Op::A64_MAX_VEC,
+                0b01101 => Op::A64_MIN_VEC,
+                0b10100 => Op::A64_MAXP,
+                _ => Op::A64_MINP,
+            };
+            inst.flags |= signed;
+        }
+        (1, 0b10011) if size == 0b00 => inst.op = Op::A64_PMUL,
+        _ => return UNKNOWN_INST,
+    }
+    inst.flags = set_vec_arrangement(inst.flags, (size << 1) | Q as u8);
+    inst.rd = regRd(binst);
+    inst.rn = regRn(binst);
+    inst.rm = regRm(binst);
+    return inst;
+}
+
+/// Advanced SIMD three same, logical -- opcode 00011, where size selects
+/// the operation instead of the element size. The arrangement is always
+/// 8B or 16B. ORR with Rn == Rm is the MOV alias.
+///
+/// The bitwise selects keep their selector in different operands: BSL in
+/// Vd, BIT and BIF in Vm, inserting the bits of Vn into Vd where it is one
+/// (BIT) or zero (BIF).
+fn simd_logical(binst: u32) -> Inst {
+    let mut inst = UNKNOWN_INST;
+    let Q = (binst >> 30) & 1;
+    let U = (binst >> 29) & 1;
+    let size = (binst >> 22) & 0b11;
+
+    inst.rd = regRd(binst);
+    inst.rn = regRn(binst);
+    inst.rm = regRm(binst);
+    inst.op = match (U, size) {
+        (0, 0b00) => Op::A64_AND_VEC,
+        (0, 0b01) => Op::A64_BIC_VEC_REG,
+        (0, 0b10) if inst.rn == inst.rm => Op::A64_MOV_VEC,
+        (0, 0b10) => Op::A64_ORR_VEC_REG,
+        (0, _) => Op::A64_ORN_VEC,
+        (_, 0b00) => Op::A64_EOR_VEC,
+        (_, 0b01) => Op::A64_BSL,
+        (_, 0b10) => Op::A64_BIT,
+        _ => Op::A64_BIF,
+    };
+    inst.flags = set_vec_arrangement(inst.flags, VectorArrangement::VA_8B | Q as u8);
+    return inst;
+}
+
+/// Advanced SIMD three different -- long, wide and narrow operations, with
+/// the arrangement of the narrow operands stored in the flags.
+fn simd_three_different(binst: u32) -> Inst {
+    let mut inst = UNKNOWN_INST;
+    let Q = (binst >> 30) & 1;
+    let U = (binst >> 29) & 1;
+    let size = ((binst >> 22) & 0b11) as u8;
+    let opcode = (binst >> 12) & 0b1111;
+    let signed = if U == 0 { SIMD_SIGNED } else { 0 };
+
+    match (U, opcode) {
+        (_, 0b0100) if size != 0b11 => { // ADDHN, RADDHN
+            inst.op = Op::A64_ADDHN;
+            inst.flags |= if U == 1 { SIMD_ROUND } else { 0 };
+        }
+        (_, 0b0110) if size != 0b11 => { // SUBHN, RSUBHN
+            inst.op = Op::A64_SUBHN;
+            inst.flags |= if U == 1 { SIMD_ROUND } else { 0 };
+        }
+        (_, 0b0101) if size != 0b11 => { // SABAL, UABAL
+            inst.op = Op::A64_ABAL;
+            inst.flags |= signed;
+        }
+        (_, 0b0111) if size != 0b11 => { // SABDL, UABDL
+            inst.op = Op::A64_ABDL;
+            inst.flags |= signed;
+        }
+        // 8B -> 8H, or 1D -> 1Q with the ARMv8 Crypto extension
+        (0, 0b1110) if size == 0b00 || size == 0b11 => inst.op = Op::A64_PMULL,
+        _ => return UNKNOWN_INST,
+    }
+    inst.flags = set_vec_arrangement(inst.flags, (size << 1) | Q as u8);
+    inst.rd = regRd(binst);
+    inst.rn = regRn(binst);
+    inst.rm = regRm(binst);
+    return inst;
+}
+
+/// Advanced SIMD three same (FP16), the half-precision counterpart of the
+/// floating-point half of the three same encoding. Its three-bit opcode
+/// matches the lower bits of the latter's.
+fn simd_three_same_fp16(binst: u32) -> Inst {
+    let opcode = 0b11000 | ((binst >> 11) & 0b111);
+    let Q = (binst >> 30) & 1;
+    return simd_three_same_fp(binst, opcode, (FPSize::FSZ_H << 1) | Q as u8);
+}
+
+/// Advanced SIMD scalar three same. Only the floating-point half and the
+/// saturating QADD and QSUB are decoded; the result uses the opcode of the
+/// vector form with SIMD_SCALAR set and the precision in Inst.flags.vec.
+fn simd_scalar_three_same(binst: u32) -> Inst {
+    let opcode = (binst >> 11) & 0b11111;
+    if opcode == 0b00001 || opcode == 0b00101 {
+        let size = ((binst >> 22) & 0b11) as u8;
+        let inst = simd_three_same(binst);
+        return Inst { flags: set_vec_arrangement(inst.flags, size << 1) | SIMD_SCALAR, ..inst };
+    }
+    if opcode < 0b11000 {
+        return UNKNOWN_INST;
+    }
+    let prec = if (binst >> 22) & 1 == 0 { FPSize::FSZ_S } else { FPSize::FSZ_D };
+    return simd_scalar_fp(simd_three_same_fp(binst, opcode, prec << 1));
+}
+
+/// Advanced SIMD scalar three same FP16
+fn simd_scalar_three_same_fp16(binst: u32) -> Inst {
+    let opcode = 0b11000 | ((binst >> 11) & 0b111);
+    return simd_scalar_fp(simd_three_same_fp(binst, opcode, FPSize::FSZ_H << 1));
+}
+
+/// Advanced SIMD scalar pairwise -- reduces the two elements of Vn to the
+/// scalar Vd, with SIMD_SCALAR set and Inst.flags.vec := precision << 1 like
+/// the other scalar ops. U == 0 selects half precision for the FP ops.
+fn simd_scalar_pairwise(binst: u32) -> Inst {
+    let mut inst = UNKNOWN_INST;
+    let U = (binst >> 29) & 1;
+    let size = ((binst >> 22) & 0b11) as u8;
+    let opcode = (binst >> 12) & 0b11111;
+
+    let prec = if opcode == 0b11011 {
+        if U == 1 || size != 0b11 {
+            return UNKNOWN_INST;
+        }
+        inst.op = Op::A64_ADDP;
+        FPSize::FSZ_D
+    } else {
+        inst.op = match (size >> 1, opcode) {
+            (0, 0b01100) => Op::A64_FMAXNMP,
+            (0, 0b01101) => Op::A64_FADDP,
+            (0, 0b01111) => Op::A64_FMAXP,
+            (1, 0b01100) => Op::A64_FMINNMP,
+            (1, 0b01111) => Op::A64_FMINP,
+            _ => return UNKNOWN_INST,
+        };
+        match (U, size & 1) {
+            (0, 0) => FPSize::FSZ_H,
+            (0, _) => return UNKNOWN_INST,
+            (_, 0) => FPSize::FSZ_S,
+            _ => FPSize::FSZ_D,
+        }
+    };
+    inst.flags = set_vec_arrangement(inst.flags, prec << 1) | SIMD_SCALAR;
+    inst.rd = regRd(binst);
+    inst.rn = regRn(binst);
+    return inst;
+}
+
+/// Marks a decoded vector instruction as its scalar form, provided that one
+/// is allocated: the pairwise and arithmetic operations other than FABD and
+/// FMULX only exist as vectors (or in the scalar floating-point group).
+fn simd_scalar_fp(mut inst: Inst) -> Inst {
+    match inst.op {
+        Op::A64_FABD_VEC | Op::A64_FMULX_VEC | Op::A64_FRECPS_VEC | Op::A64_FRSQRTS_VEC => {}
+        Op::A64_FCMEQ_REG | Op::A64_FCMGE_REG | Op::A64_FCMGT_REG | Op::A64_FACGE | Op::A64_FACGT => {}
+        _ => return UNKNOWN_INST,
+    }
+    inst.flags |= SIMD_SCALAR;
+    return inst;
+}
+
+/// Floating-point vector <op> vector, selected by U:a:opcode where a is the
+/// upper bit of the size field.
+fn simd_three_same_fp(binst: u32, opcode: u32, va: u8) -> Inst {
+    let mut inst = UNKNOWN_INST;
+    let U = (binst >> 29) & 1;
+    let a = (binst >> 23) & 1;
+
+    inst.op = match (U, a, opcode) {
+        (0, 0, 0b11000) => Op::A64_FMAXNM_VEC,
+        (0, 0, 0b11001) => Op::A64_FMLA_VEC,
+        (0, 0, 0b11010) => Op::A64_FADD_VEC,
+        (0, 0, 0b11011) => Op::A64_FMULX_VEC,
+        (0, 0, 0b11100) => Op::A64_FCMEQ_REG,
+        (0, 0, 0b11110) => Op::A64_FMAX_VEC,
+        (0, 0, 0b11111) => Op::A64_FRECPS_VEC,
+        (0, 1, 0b11000) => Op::A64_FMINNM_VEC,
+        (0, 1, 0b11001) => Op::A64_FMLS_VEC,
+        (0, 1, 0b11010) => Op::A64_FSUB_VEC,
+        (0, 1, 0b11110) => Op::A64_FMIN_VEC,
+        (0, 1, 0b11111) => Op::A64_FRSQRTS_VEC,
+        (1, 0, 0b11000) => Op::A64_FMAXNMP_VEC,
+        (1, 0, 0b11010) => Op::A64_FADDP_VEC,
+        (1, 0, 0b11011) => Op::A64_FMUL_VEC,
+        (1, 0, 0b11100) => Op::A64_FCMGE_REG,
+        (1, 0, 0b11101) => Op::A64_FACGE,
+        (1, 0, 0b11110) => Op::A64_FMAXP_VEC,
+        (1, 0, 0b11111) => Op::A64_FDIV_VEC,
+        (1, 1, 0b11000) => Op::A64_FMINNMP_VEC,
+        (1, 1, 0b11010) => Op::A64_FABD_VEC,
+        (1, 1, 0b11100) => Op::A64_FCMGT_REG,
+        (1, 1, 0b11101) => Op::A64_FACGT,
+        (1, 1, 0b11110) => Op::A64_FMINP_VEC,
+        _ => return UNKNOWN_INST,
+    };
+    inst.flags = set_vec_arrangement(inst.flags, va);
+    inst.rd = regRd(binst);
+    inst.rn = regRn(binst);
+    inst.rm = regRm(binst);
+    return inst;
+}
+
+/// Advanced SIMD three same (extra) -- SQRDMLAH, SQRDMLSH and the BFloat16
+/// BFDOT, BFMLALB/T and BFMMLA only so far
+fn simd_three_same_extra(binst: u32) -> Inst {
+    let mut inst = UNKNOWN_INST;
+    let Q = (binst >> 30) & 1;
+    let U = (binst >> 29) & 1;
+    let size = (binst >> 22) & 0b11;
+    let opcode = (binst >> 11) & 0b1111;
+
+    let va = match (U, size, opcode) {
+        (1, 0b01 | 0b10, 0b0000 | 0b0001) => {
+            inst.op = if opcode == 0b0000 { Op::A64_SQRDMLAH_VEC } else { Op::A64_SQRDMLSH_VEC };
+            inst.flags |= SIMD_ROUND;
+            ((size as u8) << 1) | Q as u8
+        }
+        (1, 0b01, 0b1111) => {
+            inst.op = Op::A64_BFDOT_VEC;
+            (FPSize::FSZ_S << 1) | Q as u8
+        }
+        (1, 0b01, 0b1101) if Q == 1 => {
+            inst.op = Op::A64_BFMMLA;
+            VectorArrangement::VA_4S
+        }
+        (1, 0b11, 0b1111) => {
+            inst.op = if Q == 0 { Op::A64_BFMLALB_VEC } else { Op::A64_BFMLALT_VEC };
+            VectorArrangement::VA_4S
+        }
+        _ => return UNKNOWN_INST,
+    };
+    inst.flags = set_vec_arrangement(inst.flags, va);
+    inst.rd = regRd(binst);
+    inst.rn = regRn(binst);
+    inst.rm = regRm(binst);
+    return inst;
+}
+
+/// Advanced SIMD across lanes -- SMAXV/UMAXV and SMINV/UMINV only so far
+fn simd_across_lanes(binst: u32) -> Inst {
+    let mut inst = UNKNOWN_INST;
+    let Q = (binst >> 30) & 1;
+    let U = (binst >> 29) & 1;
+    let size = ((binst >> 22) & 0b11) as u8;
+    let opcode = (binst >> 12) & 0b11111;
+
+    inst.op = match opcode {
+        0b01010 => Op::A64_MAXV,
+        0b11010 => Op::A64_MINV,
+        _ => return UNKNOWN_INST,
+    };
+    if size == 0b11 || (size == 0b10 && Q == 0) {
+        return UNKNOWN_INST; // 1D, 2S
+    }
+    if U == 0 {
+        inst.flags |= SIMD_SIGNED;
+    }
+    inst.flags |= SIMD_SCALAR;
+    inst.flags = set_vec_arrangement(inst.flags, (size << 1) | Q as u8);
+    inst.rd = regRd(binst);
+    inst.rn = regRn(binst);
+    return inst;
+}
+
+/// Advanced SIMD two-register miscellaneous
+fn simd_two_reg_misc(binst: u32) -> Inst {
+    let mut inst = UNKNOWN_INST;
+    let Q = (binst >> 30) & 1;
+    let U = (binst >> 29) & 1;
+    let mut size = ((binst >> 22) & 0b11) as u8;
+    let opcode = (binst >> 12) & 0b11111;
+
+    match (U, opcode) {
+        // The reversed containers are 64, 16, 32 bits and single bytes: the
+        // elements must be strictly smaller than them.
+        (0, 0b00000) => inst.op = Op::A64_REV64_VEC,
+        (0, 0b00001) => inst.op = Op::A64_REV16_VEC,
+        (1, 0b00000) => inst.op = Op::A64_REV32_VEC,
+        (1, 0b00101) if size == 0b00 => inst.op = Op::A64_NOT_VEC,
+        (1, 0b00101) => {
+            if size != 0b01 {
+                return errinst(format!("RBIT_VEC: reserved size field {:#04b}", size));
+            }
+            inst.op = Op::A64_RBIT_VEC;
+            size = 0b00; // size encodes the opcode, the elements are bytes
+        }
+        (_, 0b00010) if size != 0b11 => { // SADDLP, UADDLP
+            inst.op = Op::A64_ADDLP;
+            inst.flags |= if U == 0 { SIMD_SIGNED } else { 0 };
+        }
+        (_, 0b00110) if size != 0b11 => { // SADALP, UADALP
+            inst.op = Op::A64_ADALP;
+            inst.flags |= if U == 0 { SIMD_SIGNED } else { 0 };
+        }
+        (0, 0b01011) if size != 0b11 || Q == 1 => inst.op = Op::A64_ABS_VEC,
+        (_, 0b10100) if size != 0b11 => { // SQXTN, UQXTN
+            inst.op = Op::A64_QXTN;
+            inst.flags |= if U == 0 { SIMD_SIGNED } else { 0 };
+        }
+        (1, 0b10010) if size != 0b11 => inst.op = Op::A64_SQXTUN,
+        (1, 0b10011) if size != 0b11 => { // SHLL, shifting by the element size
+            inst.op = Op::A64_SHLL;
+            inst.imm = 8 << size;
+        }
+        // sz = size<0> selects between half/single and single/double
+        // precision; size is the one of the narrow elements from here on.
+        (0, 0b10110 | 0b10111) | (1, 0b10110) if size & 0b10 == 0 => {
+            inst.op = match (U, opcode) {
+                (0, 0b10111) => Op::A64_FCVTL,
+                (0, _) => Op::A64_FCVTN,
+                _ if size == 0 => return UNKNOWN_INST, // FCVTXN has no half-precision result
+                _ => {
+                    inst.frint.mode = FPRounding::FPR_ODD as u32;
+                    Op::A64_FCVTXN
+                }
+            };
+            size += FPSize::FSZ_H;
+        }
+        // FRINTN, FRINTM, FRINTP, FRINTZ, FRINTA, FRINTX, FRINTI and the
+        // FRINT32/64 forms; sz = size<0> selects single or double precision.
+        (_, 0b11000 | 0b11001 | 0b11110 | 0b11111) => {
+            let o2 = size >> 1;
+            let (exact, mode, bits) = match (U, o2, opcode) {
+                (0, 0, 0b11000) => (false, FPRounding::FPR_TIE_EVEN, 0),
+                (0, 0, 0b11001) => (false, FPRounding::FPR_NEG_INF, 0),
+                (0, 1, 0b11000) => (false, FPRounding::FPR_POS_INF, 0),
+                (0, 1, 0b11001) => (false, FPRounding::FPR_ZERO, 0),
+                (1, 0, 0b11000) => (false, FPRounding::FPR_TIE_AWAY, 0),
+                (1, 0, 0b11001) => (true, FPRounding::FPR_CURRENT, 0),
+                (1, 1, 0b11001) => (false, FPRounding::FPR_CURRENT, 0),
+                (0, 0, 0b11110) => (false, FPRounding::FPR_ZERO, 32),
+                (1, 0, 0b11110) => (true, FPRounding::FPR_CURRENT, 32),
+                (0, 0, 0b11111) => (false, FPRounding::FPR_ZERO, 64),
+                (1, 0, 0b11111) => (true, FPRounding::FPR_CURRENT, 64),
+                _ => return UNKNOWN_INST,
+            };
+            size = FPSize::FSZ_S + (size & 1);
+            if size == FPSize::FSZ_D && Q == 0 {
+                return UNKNOWN_INST; // 1D
+            }
+            inst.op = if exact { Op::A64_FRINTX_VEC } else { Op::A64_FRINT_VEC };
+            inst.frint.mode = mode as u32;
+            inst.frint.bits = bits;
+        }
+        // URECPE, URSQRTE: size<1> is set and sz = size<0> is reserved
+        (_, 0b11100) if size & 0b10 != 0 => {
+            inst.op = if U == 0 { Op::A64_URECPE } else { Op::A64_URSQRTE };
+            if size == 0b11 {
+                return errinst(format!("{:?}: reserved sz field, only 2s and 4s", inst.op));
+            }
+            size = Size::SZ_W;
+        }
+        (0, 0b10110) if size == 0b10 => { // BFCVTN, BFCVTN2
+            inst.op = Op::A64_BFCVTN;
+            size = FPSize::FSZ_H;
+        }
+        _ => return UNKNOWN_INST,
+    }
+    let max_size = match inst.op {
+        Op::A64_REV64_VEC => 0b10,
+        Op::A64_REV32_VEC => 0b01,
+        Op::A64_REV16_VEC => 0b00,
+        _ => 0b11,
+    };
+    if size > max_size {
+        return errinst(format!("{:?}: reserved element size {}", inst.op, 8 << size));
+    }
+    inst.flags = set_vec_arrangement(inst.flags, (size << 1) | Q as u8);
+    inst.rd = regRd(binst);
+    inst.rn = regRn(binst);
+    return inst;
+}
+
+/// Advanced SIMD vector x indexed element -- Inst.imm := index, Inst.rm := Vm
+///
+/// The index is split into the H, L and M bits. With 16-bit elements, it is
+/// H:L:M and Vm one of V0-V15; with 32-bit elements it is H:L and M is the
+/// upper bit of Vm; with 64-bit elements it is H alone.
+fn simd_indexed_element(binst: u32) -> Inst {
+    let mut inst = UNKNOWN_INST;
+    let Q = (binst >> 30) & 1;
+    let U = (binst >> 29) & 1;
+    let size = ((binst >> 22) & 0b11) as u8;
+    let L = (binst >> 21) & 1;
+    let M = (binst >> 20) & 1;
+    let opcode = (binst >> 12) & 0b1111;
+    let H = (binst >> 11) & 1;
+
+    if U == 0 && opcode == 0b1111 && size & 1 == 1 {
+        return simd_bf16_indexed_element(binst);
+    }
+
+    match (U, opcode) {
+        (0, 0b1000) => inst.op = Op::A64_MUL_ELEM,
+        (0, 0b0001) => inst.op = Op::A64_FMLA_ELEM,
+        (0, 0b0101) => inst.op = Op::A64_FMLS_ELEM,
+        (1, 0b0000) => inst.op = Op::A64_MLA_ELEM,
+        (1, 0b0100) => inst.op = Op::A64_MLS_ELEM,
+        (0, 0b0000) => inst.op = Op::A64_FMLAL_ELEM,
+        (0, 0b0100) => inst.op = Op::A64_FMLSL_ELEM,
+        (1, 0b1000) => inst.op = Op::A64_FMLAL2_ELEM,
+        (1, 0b1100) => inst.op = Op::A64_FMLSL2_ELEM,
+        (_, 0b0010) | (_, 0b0110) | (_, 0b1010) => { // SMLAL, UMLAL, SMLSL, UMLSL, SMULL, UMULL
+            inst.op = match opcode {
+                0b0010 => Op::A64_MLAL_ELEM,
+                0b0110 => Op::A64_MLSL_ELEM,
+                _ => Op::A64_MULL_ELEM,
+            };
+            if U == 0 {
+                inst.flags |= SIMD_SIGNED;
+            }
+        }
+        (0, 0b0011) => inst.op = Op::A64_SQDMLAL_ELEM,
+        (0, 0b0111) => inst.op = Op::A64_SQDMLSL_ELEM,
+        (0, 0b1011) => inst.op = Op::A64_SQDMULL_ELEM,
+        (1, 0b1101) | (1, 0b1111) => {
+            inst.op = if opcode == 0b1101 { Op::A64_SQRDMLAH_ELEM } else { Op::A64_SQRDMLSH_ELEM };
+            inst.flags |= SIMD_ROUND;
+        }
+        _ => return UNKNOWN_INST,
+    }
+    // FMLAL and friends index a half-precision element but encode size = 10
+    let half_index = matches!(inst.op, Op::A64_FMLAL_ELEM | Op::A64_FMLSL_ELEM | Op::A64_FMLAL2_ELEM | Op::A64_FMLSL2_ELEM);
+    // FMLA and FMLS encode half precision as size = 00, single and double
+    // precision as 10 and 11
+    let fp = matches!(inst.op, Op::A64_FMLA_ELEM | Op::A64_FMLS_ELEM);
+    let size = match size {
+        0b00 if fp => FPSize::FSZ_H,
+        0b01 if fp => return UNKNOWN_INST,
+        _ => size,
+    };
+    let (index, rm) = match size {
+        0b10 if half_index => ((H << 2) | (L << 1) | M, (binst >> 16) & 0b1111),
+        _ if half_index => return UNKNOWN_INST,
+        0b01 => ((H << 2) | (L << 1) | M, (binst >> 16) & 0b1111),
+        0b10 => ((H << 1) | L, (binst >> 16) & 0b11111),
+        0b11 if fp && L == 0 && Q == 1 => (H, (binst >> 16) & 0b11111),
+        _ => return UNKNOWN_INST,
+    };
+    inst.flags = set_vec_arrangement(inst.flags, (size << 1) | Q as u8);
+    inst.imm = index as u64;
+    inst.rd = regRd(binst);
+    inst.rn = regRn(binst);
+    inst.rm = rm as u8;
+    return inst;
+}
+
+/// The BFloat16 BFDOT and BFMLALB/T (by element)
+fn simd_bf16_indexed_element(binst: u32) -> Inst {
+    let mut inst = UNKNOWN_INST;
+    let Q = (binst >> 30) & 1;
+    let L = (binst >> 21) & 1;
+    let M = (binst >> 20) & 1;
+    let H = (binst >> 11) & 1;
+
+    if (binst >> 23) & 1 == 0 {
+        inst.op = Op::A64_BFDOT_ELEM;
+        inst.flags = set_vec_arrangement(inst.flags, (FPSize::FSZ_S << 1) | Q as u8);
+        inst.imm = ((H << 1) | L) as u64;
+        inst.rm = regRm(binst);
+    } else {
+        inst.op = if Q == 0 { Op::A64_BFMLALB_ELEM } else { Op::A64_BFMLALT_ELEM };
+        inst.flags = set_vec_arrangement(inst.flags, VectorArrangement::VA_4S);
+        inst.imm = ((H << 2) | (L << 1) | M) as u64;
+        inst.rm = ((binst >> 16) & 0b1111) as u8;
+    }
+    inst.rd = regRd(binst);
+    inst.rn = regRn(binst);
+    return inst;
+}
+
+/// Advanced SIMD modified immediate -- MOVI, MVNI and FMOV (vector,
+/// immediate), see A64_MOVI. cmode selects the lane size and how the 8-bit
+/// immediate a:b:c:d:e:f:g:h is placed in it (AdvSIMDExpandImm); ORR and
+/// BIC are not decoded yet. cmode 1111 is FMOV, whose lane value is the
+/// VFPExpandImm of imm8: single precision, half precision with o2 set, and
+/// double precision with op set (2D only).
+fn simd_modified_imm(binst: u32) -> Inst {
+    let mut inst = UNKNOWN_INST;
+    let Q = (binst >> 30) & 1;
+    let op = (binst >> 29) & 1;
+    let cmode = (binst >> 12) & 0b1111;
+    let o2 = (binst >> 11) & 1;
+    let imm8 = ((((binst >> 16) & 0b111) << 5) | ((binst >> 5) & 0b11111)) as u64;
+
+    if cmode == 0b1111 {
+        let size = match (op, o2, Q) {
+            (0, 0, _) => FPSize::FSZ_S,
+            (0, 1, _) => FPSize::FSZ_H,
+            (1, 0, 1) => FPSize::FSZ_D,
+            _ => return UNKNOWN_INST,
+        };
+        inst.op = Op::A64_FMOV_VEC;
+        inst.fimm = vfp_expand_imm(imm8 as u8);
+        inst.flags = set_vec_arrangement(inst.flags, (size << 1) | Q as u8);
+        inst.rd = regRd(binst);
+        return inst;
+    }
+    if o2 != 0 {
+        return UNKNOWN_INST;
+    }
+    let (size, imm) = match cmode {
+        0b0000 | 0b0010 | 0b0100 | 0b0110 => (Size::SZ_W, imm8 << (8 * (cmode >> 1))),
+        0b1000 | 0b1010 => (Size::SZ_H, imm8 << (8 * ((cmode >> 1) & 1))),
+        0b1100 => (Size::SZ_W, (imm8 << 8) | 0xFF), // MSL #8, shifting ones in
+        0b1101 => (Size::SZ_W, (imm8 << 16) | 0xFFFF), // MSL #16
+        0b1110 if op == 0 => (Size::SZ_B, imm8),
+        0b1110 => { // every bit of imm8 becomes a byte of ones
+            let bytes = (0..8).filter(|i| imm8 & (1 << i) != 0).fold(0u64, |acc, i| acc | (0xFF << (8 * i)));
+            (Size::SZ_X, bytes)
+        }
+        _ => return UNKNOWN_INST, // ORR, BIC
+    };
+
+    inst.op = Op::A64_MOVI;
+    inst.imm = if op == 1 && cmode != 0b1110 { !imm & (u64::MAX >> (64 - (8 << size))) } else { imm }; // MVNI
+    inst.flags = set_vec_arrangement(inst.flags, (size << 1) | Q as u8);
+    inst.rd = regRd(binst);
+    return inst;
+}
+
+/// The 128-bit value MOVI (MVNI) and FMOV (vector, immediate) write to Vd:
+/// the lane constant replicated to every lane of the arrangement. The upper
+/// 64 bits are zero for the 64-bit arrangements, e.g. for MOVI Dd, #imm.
+/// Other instructions yield 0.
+pub fn simd_imm_u128(inst: &Inst) -> u128 {
+    let va = fad_get_vec_arrangement(inst.flags);
+    let lane_bits = vec_elem_bits(va);
+    let lane = match inst.op {
+        Op::A64_MOVI => inst.imm,
+        Op::A64_FMOV_VEC => match lane_bits {
+            64 => inst.fimm.to_bits(),
+            32 => (inst.fimm as f32).to_bits() as u64,
+            _ => { // half precision; the 8-bit FP immediates are exact in it
+                let bits = inst.fimm.to_bits();
+                let exponent = ((bits >> 52) & 0x7FF) as i64 - 1023 + 15;
+                ((bits >> 63) << 15) | ((exponent as u64 & 0x1F) << 10) | ((bits >> 42) & 0x3FF)
+            }
+        },
+        _ => return 0,
+    };
+
+    let mut value: u128 = 0;
+    for i in 0..vec_lane_count(va) {
+        value |= (lane as u128) << (i * lane_bits);
+    }
+    return value;
+}
+
+/// Advanced SIMD shift by immediate
+///
+/// The element size is given by the highest set bit of immh, the shift
+/// amount (stored in Inst.imm) by the remaining bits of immh:immb.
+fn simd_shift_imm(binst: u32) -> Inst {
+    let mut inst = UNKNOWN_INST;
+    let Q = (binst >> 30) & 1;
+    let U = (binst >> 29) & 1;
+    let immh = (binst >> 19) & 0b1111;
+    let immhb = (binst >> 16) & 0b1111111;
+    let opcode = (binst >> 11) & 0b11111;
+
+    let size = highest_bit(immh) as u8;
+    let esize = 8 << size;
+
+    match opcode {
+        0b10000..=0b10011 => { // narrowing right shifts
+            inst.op = match (U, opcode >> 1) {
+                (0, 0b1000) => Op::A64_SHRN,
+                (1, 0b1000) => Op::A64_SQSHRUN,
+                _ => Op::A64_QSHRN,
+            };
+            if opcode & 1 == 1 {
+                inst.flags |= SIMD_ROUND;
+            }
+            inst.imm = (2 * esize - immhb) as u64;
+        }
+        0b10100 => { // SSHLL, USHLL
+            inst.op = Op::A64_SHLL;
+            inst.imm = (immhb - esize) as u64;
+        }
+        0b01000 if U == 1 => {
+            inst.op = Op::A64_SRI;
+            inst.imm = (2 * esize - immhb) as u64;
+        }
+        0b01010 if U == 1 => {
+            inst.op = Op::A64_SLI;
+            inst.imm = (immhb - esize) as u64;
+        }
+        0b01100 if U == 1 => {
+            inst.op = Op::A64_SQSHLU;
+            inst.imm = (immhb - esize) as u64;
+        }
+        0b01110 => { // SQSHL, UQSHL
+            inst.op = Op::A64_QSHL_IMM;
+            inst.imm = (immhb - esize) as u64;
+        }
+        _ => return UNKNOWN_INST,
+    }
+
+    if matches!(inst.op, Op::A64_SLI | Op::A64_SRI | Op::A64_SQSHLU | Op::A64_QSHL_IMM) {
+        if size == FPSize::FSZ_D && Q == 0 {
+            return UNKNOWN_INST; // 1D is scalar only
+        }
+    } else if size == FPSize::FSZ_D {
+        return UNKNOWN_INST; // no 128-bit elements to widen to / narrow from
+    }
+    if U == 0 && inst.op != Op::A64_SHRN {
+        inst.flags |= SIMD_SIGNED;
+    }
+    inst.flags = set_vec_arrangement(inst.flags, (size << 1) | Q as u8);
+    inst.rd = regRd(binst);
+    inst.rn = regRn(binst);
+    return inst;
+}
+
+/// Can MOVZ or MOVN produce the value, i.e. is it a single (inverted) halfword?
+fn is_move_wide_imm(imm: u64, w32: bool) -> bool {
+    let mask = if w32 { 0xFFFFFFFF } else { u64::MAX };
+    let halfword = |x: u64| (0..4).any(|hw| x & !(0xFFFF << (16 * hw)) == 0);
+    return halfword(imm & mask) || halfword(!imm & mask);
+}
+
+/// Returns the 0-based index of the highest bit. Should be compiled down
+/// to a single native instruction.
+fn highest_bit(mut x: u32) -> i32 {
+    let mut n = 0;
+    while x != 1 {
+        x >>= 1;
+        n += 1;
+    }
+    return n;
+}
+
+/// Rotate the len-bit number x n places to the right. Based on the first
+/// example at https://en.wikipedia.org/wiki/Bitwise_operation#Circular_shifts
+/// (except turned around, to make it rotate right).
+pub(crate) fn ror(x: u64, n: u32, len: u32) -> u64 {
+    if n == 0 {
+        return x; // x << len would overflow for len == 64
+    }
+    let raw = (x >> n) | (x << (len - n));
+    if len == 64 {
+        return raw;
+    }
+    return raw & ((1u64 << len) - 1); // truncate left side to len bits
+}
+
+
+/// Implementation of the A64 pseudocode function DecodeBitMasks (pp. 1683-1684).
+///
+/// The logical immediate instructions encode 32-bit or 64-bit masks using merely
+/// 12 or 13 bits. We want the decoded mask in our Inst.imm field. We only need
+/// the "wmask" of DecodeBitMasks, so return only that. Reserved encodings
+/// yield None.
+fn decode_bitmask(immN: u8, imms: u8, immr: u8, w32: bool) -> Option<u64> {
+    let M: u32 = if w32 { 32 } else { 64 };
+
+    // Guarantee it's only the number of bits in the pseudocode signature.
+    let immN = immN & 1;
+    let imms = imms & 0b111111;
+    let immr = immr & 0b111111;
 
     // length of bitmask (1..6)
-    let len = highest_bit(((immN << 6) | ((!imms) & 0b111111)) as u32);
+    let pattern = ((immN << 6) | ((!imms) & 0b111111)) as u32;
+    if pattern == 0 {
+        return None;
+    }
+    let len = highest_bit(pattern);
+    if len < 1 || (w32 && len > 5) {
+        return None;
+    }
 
     // 1..6 consecutive ones, basis of pattern
     let mut levels = 0;
-    for i in 0..len {
+    for _ in 0..len {
         levels = (levels << 1) | 1;
     }
 
     let S: u32 = (imms & levels) as u32;
     let R: u32 = (immr & levels) as u32;
+    if S == levels as u32 {
+        return None; // all ones is reserved
+    }
     let esize = 1 << len; // 2, 4, 8, 16, 32, 64
 
     // welem: pattern of 1s then zero-extended to esize
@@ -1515,15 +4548,16 @@ fn decode_bitmask(immN: u8, imms: u8, immr: u8, w32: bool) -> u64 {
     // wmask = Replicate(ROR(welem, R));
     welem = ror(welem, R, esize);
     let mut wmask = 0;
-    for i in (0..M).step_by(esize as usize) {
-        wmask = (wmask << esize) | welem;
+    for _ in (0..M).step_by(esize as usize) {
+        wmask = if esize == 64 { welem } else { (wmask << esize) | welem };
     }
 
-    return wmask;
+    return Some(wmask);
 }
 
-fn find_bfm_alias(op: Op, w32: bool, rd: u8, rn: u8, immr: u8, imms: u8) -> Inst {
-    let mut inst = UNKNOWN_INST;
+/// Writes the preferred alias of SBFM, BFM or UBFM into inst, which is
+/// UNKNOWN_INST on entry, apart from the W32 flag.
+fn find_bfm_alias(op: Op, w32: bool, rd: u8, rn: u8, immr: u8, imms: u8, inst: &mut Inst) {
     let all_ones: u8 = if w32 { 31 } else { 63 }; // u8
     let bits: u8 = if w32 { 32 } else { 64 }; // u8
 
@@ -1538,13 +4572,13 @@ fn find_bfm_alias(op: Op, w32: bool, rd: u8, rn: u8, immr: u8, imms: u8) -> Inst
             inst.op = A64_BFXIL;
             inst.bfm.lsb = immr as u32;
             inst.bfm.width = (imms - immr + 1) as u32;
-            return inst;
+            return;
         }
 
         inst.op = if rn == ZERO_REG { A64_BFC } else { A64_BFI };
         inst.bfm.lsb = (bits - immr) as u32;
         inst.bfm.width = (imms + 1) as u32;
-        return inst;
+        return;
     }
 
     let sign = op == A64_SBFM;
@@ -1552,39 +4586,40 @@ fn find_bfm_alias(op: Op, w32: bool, rd: u8, rn: u8, immr: u8, imms: u8) -> Inst
     if !sign && imms as u8 + 1 == immr as u8 && imms as u8 != all_ones {
         inst.op = A64_LSL_IMM;
         inst.imm = (all_ones - imms) as u64;
-        return inst;
+        return;
     }
 
     if imms as u8 == all_ones {
         inst.op = if sign { A64_ASR_IMM } else { A64_LSR_IMM };
         inst.imm = immr as u64;
-        return inst;
+        return;
     }
 
     if imms < immr {
         inst.op = if sign { A64_SBFIZ } else { A64_UBFIZ };
         inst.bfm.lsb = (bits - immr) as u32;
         inst.bfm.width = (imms + 1) as u32;
-        return inst;
+        return;
     }
 
-    if immr == 0 {
+    if immr == 0 && (w32 || sign) {
         match imms {
             7 => {
                 inst.op = A64_EXTEND;
                 inst.extend.typ = if sign { SXTB } else { UXTB } as u32;
-                return inst;
+                return;
             }
             15 => {
                 inst.op = A64_EXTEND;
                 inst.extend.typ = if sign { SXTH } else { UXTH } as u32;
-                return inst;
+                return;
             }
-            31 => {
+            31 if sign => {
                 inst.op = A64_EXTEND;
                 inst.extend.typ = SXTW as u32;
-                return if sign { inst } else { panic!("find_bfm_alias: there is no UXTW instruction") };
+                return;
             }
+            // There is no UXTW instruction; UBFM Xd, Xn, #0, #31 is a plain UBFX.
             _ => {}
         }
     }
@@ -1592,5 +4627,1500 @@ fn find_bfm_alias(op: Op, w32: bool, rd: u8, rn: u8, immr: u8, imms: u8) -> Inst
     inst.op = if sign { A64_SBFX } else { A64_UBFX };
     inst.bfm.lsb = immr as u32;
     inst.bfm.width = (imms - immr + 1) as u32;
-    inst
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn group_of_top_level() {
+        assert_eq!(group_of(0x91004020), Group::DataProcImm); // add x0, x1, #16
+        assert_eq!(group_of(0xf9400801), Group::LoadStore); // ldr x1, [x0, #16]
+        assert_eq!(group_of(0x00001234), Group::Reserved); // udf #0x1234
+    }
+
+    #[test]
+    fn decode_keeps_raw_word_of_unknown() {
+        let inst = decode(0xc85f7c20); // ldxr x0, [x1]
+        assert_eq!(inst.op, Op::A64_UNKNOWN);
+        assert_eq!(inst.imm, 0xc85f7c20);
+
+        let inst = decode(0x00001234);
+        assert_eq!(inst.op, Op::A64_UDF);
+        assert_eq!(inst.imm, 0x1234);
+    }
+
+    #[test]
+    fn movn_inverts_within_the_register() {
+        let inst = decode(0x12800000); // mov w0, #-1 (movn w0, #0)
+        assert_eq!((inst.imm, inst.op), (0xffffffff, Op::A64_MOV_IMM));
+        assert_eq!(decode(0x12a00021).imm, 0xfffeffff); // mov w1, #-65537
+        assert_eq!(decode(0x92800000).imm, u64::MAX); // mov x0, #-1
+    }
+
+    #[test]
+    fn adds_from_sp_is_not_mov() {
+        let inst = decode(0xb10003e0); // adds x0, sp, #0
+        assert_eq!((inst.rd, inst.rn, inst.flags & SET_FLAGS, inst.op), (0, STACK_POINTER, SET_FLAGS, Op::A64_ADD_IMM));
+        assert_eq!(decode(0x910003e0).op, Op::A64_MOV_SP); // mov x0, sp
+    }
+
+    #[test]
+    fn decode_does_not_panic_on_edge_cases() {
+        assert_eq!(decode(0x91810420).op, Op::A64_UNKNOWN); // addg x0, x1, #16, #1
+        assert_eq!(decode(0xd3407c20).op, Op::A64_UBFX); // ubfx x0, x1, #0, #32
+        assert_eq!(decode(0x92400020).imm, 1); // and x0, x1, #1
+        assert_eq!(decode(0x9200f020).imm, 0x5555555555555555);
+        assert_eq!(decode(0x9240fc20).op, Op::A64_UNKNOWN); // reserved N:imms
+        assert_eq!(decode(0x9200fc20).op, Op::A64_UNKNOWN); // ---
+    }
+
+    // add x0, x1, #16; "abcd"; movz x2, #0x1234
+    const MIXED: [u8; 12] = [0x20, 0x40, 0x00, 0x91, b'a', b'b', b'c', b'd', 0x82, 0x46, 0x82, 0xd2];
+
+    #[test]
+    fn inst_iter_continues_after_data() {
+        let ops: Vec<Op> = InstIter::new(&MIXED).map(|inst| inst.op).collect();
+        assert_eq!(ops, vec![Op::A64_ADD_IMM, Op::A64_UNKNOWN, Op::A64_MOV_IMM]);
+
+        let ops: Vec<Op> = skip_unknown(InstIter::new(&MIXED)).map(|inst| inst.op).collect();
+        assert_eq!(ops, vec![Op::A64_ADD_IMM, Op::A64_MOV_IMM]);
+    }
+
+    #[test]
+    fn all_ops_in_order() {
+        let ops: Vec<Op> = all_ops().collect();
+        assert_eq!(ops.first(), Some(&Op::A64_UNKNOWN));
+        assert!(ops.iter().enumerate().all(|(i, op)| op.clone() as usize == i));
+        assert!(ops.contains(&Op::A64_ADD_IMM) && ops.contains(&Op::A64_LDR));
+    }
+
+    #[test]
+    fn decode_hex_forms() {
+        let expected = decode(0x91001020); // add x0, x1, #4
+        for text in ["0x91001020", "91001020", " 0X91001020\n", "20 10 00 91", "20,10,00,91", "0x20, 0x10, 0x00, 0x91"] {
+            assert_eq!(decode_hex(text), Ok(expected.clone()), "{:?}", text);
+        }
+        assert_eq!(decode_hex("0").map(|inst| inst.op), Ok(Op::A64_UDF));
+
+        assert_eq!(decode_hex("9100102g"), Err(ParseError::InvalidDigit('g')));
+        assert_eq!(decode_hex("910010200"), Err(ParseError::WrongLength(9)));
+        assert_eq!(decode_hex(""), Err(ParseError::WrongLength(0)));
+        assert_eq!(decode_hex("20 10 00"), Err(ParseError::WrongLength(3)));
+        assert_eq!(decode_hex("20 10 00 191"), Err(ParseError::WrongLength(3)));
+    }
+
+    #[test]
+    fn decode_reader_truncated_tail() {
+        let ops: Vec<Op> = decode_reader(&MIXED[..]).map(|inst| inst.op).collect();
+        assert_eq!(ops, vec![Op::A64_ADD_IMM, Op::A64_UNKNOWN, Op::A64_MOV_IMM]);
+
+        let insts: Vec<Inst> = decode_reader(&MIXED[..10]).collect();
+        assert_eq!(insts.len(), 3);
+        assert_eq!(insts[0].op, Op::A64_ADD_IMM);
+        assert_eq!(insts[1].op, Op::A64_UNKNOWN);
+        assert_eq!(insts[2].op, Op::A64_ERROR);
+        assert_eq!(insts[2].error.to_string(), "truncated instruction: 2 trailing bytes");
+    }
+
+    #[test]
+    fn decode_into_matches_decode() {
+        let nro = include_bytes!("../../../test/hello-world.nro");
+        let words = nro.chunks_exact(4).map(|word| u32::from_le_bytes(word.try_into().unwrap()));
+        let mut inst = errinst("stale".to_string());
+        for binst in words.chain((0..=u32::MAX).step_by(65521)) {
+            decode_into(binst, &mut inst);
+            assert_eq!(inst, decode(binst), "{:#010x}", binst);
+        }
+    }
+
+    #[test]
+    fn decode_into_reuses_the_error_buffer() {
+        let mut inst = errinst("x".repeat(64));
+        for binst in [0x91004020, 0xf9400801, 0xa9bf07e0, 0x12800000] { // add, ldr, stp, mov
+            decode_into(binst, &mut inst);
+            assert_eq!(inst, decode(binst));
+        }
+        match &inst.error {
+            DecodeError::Message(buf) => assert!(buf.is_empty() && buf.capacity() >= 64),
+            error => panic!("{:?}", error),
+        }
+    }
+
+    #[test]
+    fn concurrent_decoding_matches_serial() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Decoder>();
+        assert_send_sync::<Inst>();
+
+        let nro = include_bytes!("../../../test/hello-world.nro");
+        let decoder = Decoder { base_pc: 0x8000, features: FeatureSet::NONE, ..Decoder::default() };
+        let serial: Vec<(u64, Inst)> = decoder.decode_buffer(nro).collect();
+
+        let results: Vec<Vec<(u64, Inst)>> = std::thread::scope(|scope| {
+            let threads: Vec<_> = (0..4).map(|_| scope.spawn(|| decoder.decode_buffer(nro).collect())).collect();
+            threads.into_iter().map(|thread| thread.join().unwrap()).collect()
+        });
+        for result in results {
+            assert_eq!(result, serial);
+        }
+    }
+
+    #[test]
+    fn pointer_auth_hints_are_not_nops() {
+        let inst = decode(0xd503233f); // paciasp
+        assert_eq!(inst.op, Op::A64_PACIA);
+        assert_eq!((inst.rd, inst.rn), (30, STACK_POINTER));
+
+        let inst = decode(0xd50320ff); // xpaclri
+        assert_eq!(inst.op, Op::A64_XPACI);
+        assert_eq!(inst.rd, 30);
+
+        let inst = decode(0xd503211f); // pacia1716
+        assert_eq!(inst.op, Op::A64_PACIA);
+        assert_eq!((inst.rd, inst.rn), (17, 16));
+
+        let inst = decode(0xd503201f); // nop
+        assert_eq!(inst.op, Op::A64_HINT);
+        assert_eq!(inst.imm, 0);
+    }
+
+    #[test]
+    fn pointer_auth_data_proc() {
+        let inst = decode(0xdac10020); // pacia x0, x1
+        assert_eq!(inst.op, Op::A64_PACIA);
+        assert_eq!((inst.rd, inst.rn), (0, 1));
+
+        assert_eq!(decode(0xdac103e0).rn, STACK_POINTER); // pacia x0, sp
+
+        let inst = decode(0xdac123e2); // paciza x2
+        assert_eq!(inst.op, Op::A64_PACIA);
+        assert_eq!((inst.rd, inst.rn), (2, ZERO_REG));
+
+        let inst = decode(0xdac11c83); // autdb x3, x4
+        assert_eq!(inst.op, Op::A64_AUTDB);
+        assert_eq!((inst.rd, inst.rn), (3, 4));
+
+        let inst = decode(0xdac147e5); // xpacd x5
+        assert_eq!(inst.op, Op::A64_XPACD);
+        assert_eq!(inst.rd, 5);
+
+        assert_eq!(decode(0xdac12062).op, Op::A64_UNKNOWN); // paciza with Rn != 11111
+    }
+
+    #[test]
+    fn shifted_register_operand() {
+        let inst = decode(0x8b020c20); // add x0, x1, x2, lsl #3
+        assert_eq!(inst.op, Op::A64_ADD_SHIFTED);
+        assert_eq!((inst.rd, inst.rn, inst.rm), (0, 1, 2));
+        assert_eq!(inst.shift, InstShift { typ: Shift::SH_LSL as u32, amount: 3 });
+
+        let inst = decode(0xca62fc20); // eon x0, x1, x2, lsr #63
+        assert_eq!(inst.op, Op::A64_EON);
+        assert_eq!(inst.shift, InstShift { typ: Shift::SH_LSR as u32, amount: 63 });
+
+        let inst = decode(0xeac20c3f); // tst x1, x2, ror #3
+        assert_eq!(inst.op, Op::A64_TST_SHIFTED);
+        assert_eq!(inst.flags & SET_FLAGS, SET_FLAGS);
+        assert_eq!(inst.shift, InstShift { typ: Shift::SH_ROR as u32, amount: 3 });
+
+        let inst = decode(0x2aa10be0); // mvn w0, w1, asr #2
+        assert_eq!(inst.op, Op::A64_MVN);
+        assert_eq!(inst.flags & W32, W32);
+        assert_eq!(inst.shift, InstShift { typ: Shift::SH_ASR as u32, amount: 2 });
+
+        let inst = decode(0x6b010be0); // negs w0, w1, lsl #2
+        assert_eq!((inst.op, inst.flags & SET_FLAGS), (Op::A64_NEG, SET_FLAGS));
+        assert_eq!(decode(0x6b0103ff).op, Op::A64_CMP_SHIFTED); // cmp wzr, w1
+        assert_eq!(decode(0xea220020).op, Op::A64_BIC); // bics x0, x1, x2
+        assert_eq!(decode(0xaa0107e0).op, Op::A64_ORR_SHIFTED); // orr x0, xzr, x1, lsl #1
+        assert_eq!(decode(0xaa0103e0).op, Op::A64_MOV_REG); // mov x0, x1
+
+        assert_eq!(decode(0x0b028020).op, Op::A64_UNKNOWN); // add w0, w1, w2, lsl #32
+        assert_eq!(decode(0x8bc20c20).op, Op::A64_UNKNOWN); // add with shift type ROR
+    }
+
+    #[test]
+    fn load_literal() {
+        let inst = decode(0x58000040); // ldr x0, #8
+        assert_eq!(inst.op, Op::A64_LDR);
+        assert_eq!(fad_get_addrmode(inst.flags), AddrMode::AM_LITERAL);
+        assert_eq!(fad_get_mem_extend(inst.flags), ExtendType::UXTX);
+        assert_eq!((inst.rd, inst.offset), (0, 8));
+
+        let inst = decode(0x98ffffe2); // ldrsw x2, #-4
+        assert_eq!(inst.op, Op::A64_LDR);
+        assert_eq!(fad_get_mem_extend(inst.flags), ExtendType::SXTW);
+        assert_eq!((inst.rd, inst.offset), (2, -4));
+
+        let inst = decode(0x9c000083); // ldr q3, #16
+        assert_eq!(inst.op, Op::A64_LDR_FP);
+        assert_eq!(fad_get_mem_extend(inst.flags), FPSize::FSZ_Q);
+
+        assert_eq!(decode(0xd8000040).op, Op::A64_PRFM); // prfm pldl1keep, #8
+    }
+
+    #[test]
+    fn half_precision() {
+        let inst = decode(0x1ee22820); // fadd h0, h1, h2
+        assert_eq!(inst.op, Op::A64_FADD);
+        assert_eq!(fad_get_prec(inst.flags), FPSize::FSZ_H);
+        assert_eq!((inst.rd, inst.rn, inst.rm), (0, 1, 2));
+
+        let inst = decode(0x4e421420); // fadd v0.8h, v1.8h, v2.8h
+        assert_eq!(inst.op, Op::A64_FADD_VEC);
+        assert_eq!(fad_get_vec_arrangement(inst.flags), VectorArrangement::VA_8H);
+        assert_eq!((inst.rd, inst.rn, inst.rm), (0, 1, 2));
+
+        let inst = decode(0x2e420420); // fmaxnmp v0.4h, v1.4h, v2.4h
+        assert_eq!(inst.op, Op::A64_FMAXNMP_VEC);
+        assert_eq!(fad_get_vec_arrangement(inst.flags), VectorArrangement::VA_4H);
+
+        assert_eq!(decode(0x6ec22420).op, Op::A64_FCMGT_REG); // fcmgt v0.8h, v1.8h, v2.8h
+    }
+
+    #[test]
+    fn single_and_double_precision() {
+        let inst = decode(0x1e222820); // fadd s0, s1, s2
+        assert_eq!(inst.op, Op::A64_FADD);
+        assert_eq!(fad_get_prec(inst.flags), FPSize::FSZ_S);
+
+        let inst = decode(0x1e658883); // fnmul d3, d4, d5
+        assert_eq!(inst.op, Op::A64_FNMUL);
+        assert_eq!(fad_get_prec(inst.flags), FPSize::FSZ_D);
+        assert_eq!((inst.rd, inst.rn, inst.rm), (3, 4, 5));
+
+        let inst = decode(0x4e22d420); // fadd v0.4s, v1.4s, v2.4s
+        assert_eq!(fad_get_vec_arrangement(inst.flags), VectorArrangement::VA_4S);
+        let inst = decode(0x4ee2cc20); // fmls v0.2d, v1.2d, v2.2d
+        assert_eq!(inst.op, Op::A64_FMLS_VEC);
+        assert_eq!(fad_get_vec_arrangement(inst.flags), VectorArrangement::VA_2D);
+        assert_eq!(decode(0x2e22fc20).op, Op::A64_FDIV_VEC); // fdiv v0.2s, v1.2s, v2.2s
+
+        assert_eq!(decode(0x0e62d420).op, Op::A64_UNKNOWN); // fadd with 1D arrangement
+        assert_eq!(decode(0x1ea22820).op, Op::A64_UNKNOWN); // fadd with ftype 10
+    }
+
+    #[test]
+    fn absolute_difference_fp() {
+        let inst = decode(0x7ea2d420); // fabd s0, s1, s2
+        assert_eq!(inst.op, Op::A64_FABD_VEC);
+        assert_eq!(inst.flags & SIMD_SCALAR, SIMD_SCALAR);
+        assert_eq!(fad_size_from_vec_arrangement(fad_get_vec_arrangement(inst.flags)), FPSize::FSZ_S);
+        assert_eq!((inst.rd, inst.rn, inst.rm), (0, 1, 2));
+
+        let inst = decode(0x6ea2d420); // fabd v0.4s, v1.4s, v2.4s
+        assert_eq!(inst.op, Op::A64_FABD_VEC);
+        assert_eq!(inst.flags & SIMD_SCALAR, 0);
+        assert_eq!(fad_get_vec_arrangement(inst.flags), VectorArrangement::VA_4S);
+
+        let inst = decode(0x7ee5d483); // fabd d3, d4, d5
+        assert_eq!(fad_size_from_vec_arrangement(fad_get_vec_arrangement(inst.flags)), FPSize::FSZ_D);
+        let inst = decode(0x7ec21420); // fabd h0, h1, h2
+        assert_eq!((inst.op, inst.flags & SIMD_SCALAR), (Op::A64_FABD_VEC, SIMD_SCALAR));
+        assert_eq!(fad_size_from_vec_arrangement(fad_get_vec_arrangement(inst.flags)), FPSize::FSZ_H);
+        assert_eq!(decode(0x6ec21420).op, Op::A64_FABD_VEC); // fabd v0.8h, v1.8h, v2.8h
+
+        assert_eq!(decode(0x5e62e420).op, Op::A64_FCMEQ_REG); // fcmeq d0, d1, d2
+        assert_eq!(decode(0x5e22d420).op, Op::A64_UNKNOWN); // fadd has no scalar SIMD form
+    }
+
+    #[test]
+    fn narrowing_shifts() {
+        use VectorArrangement::*;
+
+        let inst = decode(0x0f0c8420); // shrn v0.8b, v1.8h, #4
+        assert_eq!(inst.op, Op::A64_SHRN);
+        assert_eq!(fad_get_vec_arrangement(inst.flags), VA_8B);
+        assert_eq!(vec_widen_arrangement(fad_get_vec_arrangement(inst.flags)), Some(VA_8H));
+        assert_eq!((inst.rd, inst.rn, inst.imm), (0, 1, 4));
+        assert_eq!(inst.flags & (SIMD_ROUND | SIMD_SIGNED), 0);
+
+        let inst = decode(0x2f0e8420); // sqshrun v0.8b, v1.8h, #2
+        assert_eq!(inst.op, Op::A64_SQSHRUN);
+        assert_eq!(fad_get_vec_arrangement(inst.flags), VA_8B);
+        assert_eq!(inst.imm, 2);
+        assert_eq!(inst.flags & SIMD_ROUND, 0);
+
+        let inst = decode(0x4f0d8c20); // rshrn2 v0.16b, v1.8h, #3
+        assert_eq!(inst.op, Op::A64_SHRN);
+        assert_eq!(fad_get_vec_arrangement(inst.flags), VA_16B);
+        assert_eq!(vec_widen_arrangement(VA_16B), Some(VA_8H));
+        assert_eq!((inst.imm, inst.flags & SIMD_ROUND), (3, SIMD_ROUND));
+
+        let inst = decode(0x2f108c20); // sqrshrun v0.4h, v1.4s, #16
+        assert_eq!((inst.op, inst.imm), (Op::A64_SQSHRUN, 16));
+        assert_eq!(fad_get_vec_arrangement(inst.flags), VA_4H);
+        assert_eq!(inst.flags & SIMD_ROUND, SIMD_ROUND);
+
+        let inst = decode(0x2f209462); // uqshrn v2.2s, v3.2d, #32
+        assert_eq!((inst.op, inst.imm), (Op::A64_QSHRN, 32));
+        assert_eq!(fad_get_vec_arrangement(inst.flags), VA_2S);
+        assert_eq!(inst.flags & (SIMD_ROUND | SIMD_SIGNED), 0);
+
+        let inst = decode(0x0f0f9c62); // sqrshrn v2.8b, v3.8h, #1
+        assert_eq!((inst.op, inst.imm), (Op::A64_QSHRN, 1));
+        assert_eq!(inst.flags & (SIMD_ROUND | SIMD_SIGNED), SIMD_ROUND | SIMD_SIGNED);
+    }
+
+    #[test]
+    fn long_shifts() {
+        use VectorArrangement::*;
+
+        let inst = decode(0x0f0ba420); // sshll v0.8h, v1.8b, #3
+        assert_eq!((inst.op, inst.imm), (Op::A64_SHLL, 3));
+        assert_eq!(fad_get_vec_arrangement(inst.flags), VA_8B);
+        assert_eq!(inst.flags & SIMD_SIGNED, SIMD_SIGNED);
+
+        let inst = decode(0x6f20a420); // ushll2 v0.2d, v1.4s, #0
+        assert_eq!((inst.op, inst.imm), (Op::A64_SHLL, 0));
+        assert_eq!(fad_get_vec_arrangement(inst.flags), VA_4S);
+        assert_eq!(vec_widen_arrangement(VA_4S), Some(VA_2D));
+        assert_eq!(inst.flags & SIMD_SIGNED, 0);
+
+        let inst = decode(0x2e213820); // shll v0.8h, v1.8b, #8
+        assert_eq!((inst.op, inst.imm), (Op::A64_SHLL, 8));
+        assert_eq!(fad_get_vec_arrangement(inst.flags), VA_8B);
+
+        let inst = decode(0x6ea13820); // shll2 v0.2d, v1.4s, #32
+        assert_eq!((inst.op, inst.imm), (Op::A64_SHLL, 32));
+        assert_eq!(fad_get_vec_arrangement(inst.flags), VA_4S);
+
+        assert_eq!(decode(0x0f40a420).op, Op::A64_UNKNOWN); // sshll with immh = 1xxx
+    }
+
+    #[test]
+    fn absolute_difference_accumulate_long() {
+        use VectorArrangement::*;
+
+        let inst = decode(0x2e225020); // uabal v0.8h, v1.8b, v2.8b
+        assert_eq!((inst.rd, inst.rn, inst.rm, inst.op), (0, 1, 2, Op::A64_ABAL));
+        assert_eq!((fad_get_vec_arrangement(inst.flags), inst.flags & SIMD_SIGNED), (VA_8B, 0));
+        assert_eq!(vec_widen_arrangement(VA_8B), Some(VA_8H));
+        assert!(!vec_is_upper_half(VA_8B));
+
+        let inst = decode(0x4e225020); // sabal2 v0.8h, v1.16b, v2.16b
+        assert_eq!((inst.rd, inst.rn, inst.rm, inst.op), (0, 1, 2, Op::A64_ABAL));
+        assert_eq!((fad_get_vec_arrangement(inst.flags), inst.flags & SIMD_SIGNED), (VA_16B, SIMD_SIGNED));
+        assert_eq!(vec_widen_arrangement(VA_16B), Some(VA_8H));
+        assert!(vec_is_upper_half(VA_16B));
+        assert_eq!(decode(0x0ee25020).op, Op::A64_UNKNOWN); // sabal with size 11
+    }
+
+    #[test]
+    fn scalar_pairwise_reduce() {
+        let size = |inst: &Inst| fad_size_from_vec_arrangement(fad_get_vec_arrangement(inst.flags));
+
+        let inst = decode(0x7e30d820); // faddp s0, v1.2s
+        assert_eq!((inst.rd, inst.rn, inst.flags & SIMD_SCALAR, size(&inst), inst.op), (0, 1, SIMD_SCALAR, FPSize::FSZ_S, Op::A64_FADDP));
+        let inst = decode(0x7e70f820); // fmaxp d0, v1.2d
+        assert_eq!((inst.flags & SIMD_SCALAR, size(&inst), inst.op), (SIMD_SCALAR, FPSize::FSZ_D, Op::A64_FMAXP));
+        let inst = decode(0x7eb0f8a4); // fminp s4, v5.2s
+        assert_eq!((inst.rd, inst.rn, size(&inst), inst.op), (4, 5, FPSize::FSZ_S, Op::A64_FMINP));
+        let inst = decode(0x5eb0c862); // fminnmp h2, v3.2h
+        assert_eq!((size(&inst), required_features(&inst), inst.op), (FPSize::FSZ_H, FeatureSet::FP16, Op::A64_FMINNMP));
+        let inst = decode(0x5ef1b820); // addp d0, v1.2d
+        assert_eq!((inst.flags & SIMD_SCALAR, size(&inst), inst.op), (SIMD_SCALAR, FPSize::FSZ_D, Op::A64_ADDP));
+
+        assert_eq!(decode(0x6e21d420).op, Op::A64_FADDP_VEC); // faddp v0.4s, v1.4s, v1.4s
+        assert_eq!(decode(0x5e70d820).op, Op::A64_UNKNOWN); // faddp with U == 0 and sz == 1
+    }
+
+    #[test]
+    fn conditional_compare_immediate() {
+        let inst = decode(0xfa5f0800); // ccmp x0, #31, #0, eq
+        assert_eq!((inst.rn, inst.ccmp.imm5, inst.ccmp.nzcv, fad_get_cond(inst.flags), inst.op), (0, 31, 0, Cond::COND_EQ, Op::A64_CCMP_IMM));
+        let inst = decode(0x3a451824); // ccmn w1, #5, #4, ne
+        assert_eq!((inst.flags & W32, inst.ccmp.imm5, inst.ccmp.nzcv, fad_get_cond(inst.flags), inst.op), (W32, 5, 4, Cond::COND_NE, Op::A64_CCMN_IMM));
+        let inst = decode(0xfa43a04f); // ccmp x2, x3, #15, ge
+        assert_eq!((inst.rn, inst.rm, inst.ccmp.nzcv, fad_get_cond(inst.flags), inst.op), (2, 3, 15, Cond::COND_GE, Op::A64_CCMP_REG));
+        assert_eq!(decode(0xfa5f0c00).op, Op::A64_UNKNOWN); // ccmp with o2 set
+    }
+
+    #[test]
+    fn sp_and_zr_sentinels() {
+        let inst = decode(0x910043ff); // add sp, sp, #16
+        assert!(is_sp(inst.rd) && is_sp(inst.rn) && !is_zr(inst.rd));
+        let inst = decode(0xf100043f); // cmp x1, #1 (subs xzr, x1, #1)
+        assert!(is_zr(inst.rd) && !is_sp(inst.rd));
+        assert_eq!((STACK_POINTER, ZERO_REG), (100, 31));
+    }
+
+    #[test]
+    fn shift_by_register() {
+        let inst = decode(0x1ac22020); // lsl w0, w1, w2
+        assert_eq!((inst.flags & W32, inst.rd, inst.rn, inst.rm, inst.op), (W32, 0, 1, 2, Op::A64_LSLV));
+        assert_eq!(decode(0x9ac22c20).op, Op::A64_RORV); // ror x0, x1, x2
+        assert_eq!(decode(0x9ac22820).to_string(), "asr x0, x1, x2");
+        assert_eq!(decode(0x1ac52083).to_string(), "lsl w3, w4, w5");
+        assert_eq!(decode(0x3ac22020).op, Op::A64_UNKNOWN); // lslv with S == 1
+    }
+
+    #[test]
+    fn saturating_add_sub() {
+        let size = |inst: &Inst| fad_size_from_vec_arrangement(fad_get_vec_arrangement(inst.flags));
+
+        let inst = decode(0x5ee20c20); // sqadd d0, d1, d2
+        assert_eq!((inst.rd, inst.rn, inst.rm, inst.flags & SIMD_SCALAR, size(&inst), inst.op), (0, 1, 2, SIMD_SCALAR, FPSize::FSZ_D, Op::A64_QADD));
+        assert_eq!(inst.flags & SIMD_SIGNED, SIMD_SIGNED);
+        let inst = decode(0x0e220c20); // sqadd v0.8b, v1.8b, v2.8b
+        assert_eq!((inst.flags & SIMD_SCALAR, fad_get_vec_arrangement(inst.flags), inst.op), (0, VectorArrangement::VA_8B, Op::A64_QADD));
+        let inst = decode(0x7e252c83); // uqsub b3, b4, b5
+        assert_eq!((inst.flags & (SIMD_SCALAR | SIMD_SIGNED), size(&inst), inst.op), (SIMD_SCALAR, FPSize::FSZ_B, Op::A64_QSUB));
+        let inst = decode(0x5e622c20); // sqsub h0, h1, h2
+        assert_eq!((size(&inst), inst.op), (FPSize::FSZ_H, Op::A64_QSUB));
+
+        assert_eq!(decode(0x6ee20c20).op, Op::A64_QADD); // uqadd v0.2d, v1.2d, v2.2d
+        assert_eq!(decode(0x0ee20c20).op, Op::A64_UNKNOWN); // sqadd with arrangement 1D
+    }
+
+    #[test]
+    fn rounding_doubling_multiply_accumulate() {
+        use VectorArrangement::*;
+
+        let inst = decode(0x2e428420); // sqrdmlah v0.4h, v1.4h, v2.4h
+        assert_eq!(required_features(&inst), FeatureSet::RDM);
+        assert_eq!((inst.rd, inst.rn, inst.rm, inst.op), (0, 1, 2, Op::A64_SQRDMLAH_VEC));
+        assert_eq!((fad_get_vec_arrangement(inst.flags), inst.flags & SIMD_ROUND), (VA_4H, SIMD_ROUND));
+        let inst = decode(0x2e828c20); // sqrdmlsh v0.2s, v1.2s, v2.2s
+        assert_eq!((fad_get_vec_arrangement(inst.flags), inst.flags & SIMD_ROUND, inst.op), (VA_2S, SIMD_ROUND, Op::A64_SQRDMLSH_VEC));
+
+        let inst = decode(0x6fa2d020); // sqrdmlah v0.4s, v1.4s, v2.s[1]
+        assert_eq!((inst.rd, inst.rn, inst.rm, inst.imm, inst.op), (0, 1, 2, 1, Op::A64_SQRDMLAH_ELEM));
+        assert_eq!((fad_get_vec_arrangement(inst.flags), inst.flags & SIMD_ROUND), (VA_4S, SIMD_ROUND));
+        let inst = decode(0x6f75f883); // sqrdmlsh v3.8h, v4.8h, v5.h[7]
+        assert_eq!((inst.rm, inst.imm, fad_get_vec_arrangement(inst.flags), inst.op), (5, 7, VA_8H, Op::A64_SQRDMLSH_ELEM));
+
+        assert_eq!(decode(0x2e028420).op, Op::A64_UNKNOWN); // sqrdmlah with size 00
+        assert_eq!(decode_with_features(0x2e428420, FeatureSet::NONE).error.to_string(), "requires FEAT_RDM");
+    }
+
+    #[test]
+    fn shift_and_insert() {
+        use VectorArrangement::*;
+
+        let inst = decode(0x6f285420); // sli v0.4s, v1.4s, #8
+        assert_eq!((inst.rd, inst.rn, inst.imm, fad_get_vec_arrangement(inst.flags), inst.op), (0, 1, 8, VA_4S, Op::A64_SLI));
+        let inst = decode(0x6f1d4462); // sri v2.8h, v3.8h, #3
+        assert_eq!((inst.rd, inst.rn, inst.imm, fad_get_vec_arrangement(inst.flags), inst.op), (2, 3, 3, VA_8H, Op::A64_SRI));
+        let inst = decode(0x6f4044a4); // sri v4.2d, v5.2d, #64
+        assert_eq!((inst.imm, fad_get_vec_arrangement(inst.flags), inst.op), (64, VA_2D, Op::A64_SRI));
+        assert_eq!(decode(0x2f405420).op, Op::A64_UNKNOWN); // sli v0.1d, v1.1d, #0
+        assert_eq!(decode(0x0f285420).op, Op::A64_UNKNOWN); // sli needs U == 1
+    }
+
+    #[test]
+    fn exception_generation() {
+        let inst = decode(0xd4000001); // svc #0
+        assert_eq!((inst.op, inst.imm), (Op::A64_SVC, 0));
+        let inst = decode(0xd4200020); // brk #1
+        assert_eq!((inst.op, inst.imm), (Op::A64_BRK, 1));
+        let inst = decode(0xd45fffe0); // hlt #0xffff
+        assert_eq!((inst.op, inst.imm), (Op::A64_HLT, 0xffff));
+        assert_eq!(decode(0xd4a00003).op, Op::A64_DCPS3); // dcps3
+        assert_eq!(decode(0xd4000005).op, Op::A64_UNKNOWN); // svc with op2 = 001
+    }
+
+    #[test]
+    fn system_register_moves() {
+        let inst = decode(0xd53bd040); // mrs x0, tpidr_el0
+        assert_eq!((inst.rd, inst.imm, inst.op), (0, 0xde82, Op::A64_MRS));
+        let inst = decode(0xd51b4401); // msr fpcr, x1
+        assert_eq!((inst.rd, inst.imm, inst.op), (1, 0xda20, Op::A64_MSR_REG));
+        assert_eq!(decode(0xd508001f).op, Op::A64_UNKNOWN); // sys #0, c0, c0, #0
+    }
+
+    #[test]
+    fn load_store_pair() {
+        let inst = decode(0xa9bf07e0); // stp x0, x1, [sp, #-16]!
+        assert_eq!(inst.op, Op::A64_STP);
+        assert_eq!(fad_get_addrmode(inst.flags), AddrMode::AM_PRE);
+        assert_eq!(fad_get_mem_extend(inst.flags), ExtendType::UXTX);
+        assert_eq!((inst.rd, inst.rt2, inst.rn, inst.offset), (0, 1, STACK_POINTER, -16));
+
+        let inst = decode(0xa8c27bfd); // ldp x29, x30, [sp], #32
+        assert_eq!(inst.op, Op::A64_LDP);
+        assert_eq!(fad_get_addrmode(inst.flags), AddrMode::AM_POST);
+        assert_eq!((inst.rd, inst.rt2, inst.offset), (29, 30, 32));
+
+        let inst = decode(0x697f0c82); // ldpsw x2, x3, [x4, #-8]
+        assert_eq!(inst.op, Op::A64_LDP);
+        assert_eq!(fad_get_addrmode(inst.flags), AddrMode::AM_OFF_IMM);
+        assert_eq!(fad_get_mem_extend(inst.flags), ExtendType::SXTW);
+        assert_eq!((inst.rn, inst.offset), (4, -8));
+
+        let inst = decode(0x29410c82); // ldp w2, w3, [x4, #8]
+        assert_eq!(inst.flags & W32, W32);
+        assert_eq!(inst.offset, 8);
+
+        let inst = decode(0xad0207e0); // stp q0, q1, [sp, #64]
+        assert_eq!(inst.op, Op::A64_STP_FP);
+        assert_eq!(fad_get_mem_extend(inst.flags), FPSize::FSZ_Q);
+        assert_eq!(inst.offset, 64);
+
+        let inst = decode(0xa8410440); // ldnp x0, x1, [x2, #16]
+        assert_eq!((inst.op, inst.offset), (Op::A64_LDNP, 16));
+        let inst = decode(0x2c3f8440); // stnp s0, s1, [x2, #-4]
+        assert_eq!((inst.op, inst.offset), (Op::A64_STNP_FP, -4));
+
+        assert_eq!(decode(0x69000440).op, Op::A64_UNKNOWN); // stgp x0, x1, [x2]
+    }
+
+    #[test]
+    fn absolute_difference() {
+        use VectorArrangement::*;
+
+        let inst = decode(0x6e227420); // uabd v0.16b, v1.16b, v2.16b
+        assert_eq!(inst.op, Op::A64_ABD);
+        assert_eq!(fad_get_vec_arrangement(inst.flags), VA_16B);
+        assert_eq!(inst.flags & SIMD_SIGNED, 0);
+        assert_eq!((inst.rd, inst.rn, inst.rm), (0, 1, 2));
+
+        let inst = decode(0x0e227020); // sabdl v0.8h, v1.8b, v2.8b
+        assert_eq!(inst.op, Op::A64_ABDL);
+        assert_eq!(fad_get_vec_arrangement(inst.flags), VA_8B);
+        assert_eq!(vec_widen_arrangement(VA_8B), Some(VA_8H));
+        assert_eq!(inst.flags & SIMD_SIGNED, SIMD_SIGNED);
+
+        let inst = decode(0x4ea27c20); // saba v0.4s, v1.4s, v2.4s
+        assert_eq!((inst.op, inst.flags & SIMD_SIGNED), (Op::A64_ABA, SIMD_SIGNED));
+        let inst = decode(0x6e625020); // uabal2 v0.4s, v1.8h, v2.8h
+        assert_eq!((inst.op, inst.flags & SIMD_SIGNED), (Op::A64_ABAL, 0));
+        assert_eq!(fad_get_vec_arrangement(inst.flags), VA_8H);
+
+        let inst = decode(0x4ee0b820); // abs v0.2d, v1.2d
+        assert_eq!(inst.op, Op::A64_ABS_VEC);
+        assert_eq!(fad_get_vec_arrangement(inst.flags), VA_2D);
+        assert_eq!(decode(0x0ee0b820).op, Op::A64_UNKNOWN); // abs with 1D
+        assert_eq!(decode(0x6ee27420).op, Op::A64_UNKNOWN); // uabd with 2D
+    }
+
+    #[test]
+    fn high_narrow_add_sub() {
+        use VectorArrangement::*;
+
+        let inst = decode(0x0e224020); // addhn v0.8b, v1.8h, v2.8h
+        assert_eq!((inst.op, inst.rd, inst.rn, inst.rm), (Op::A64_ADDHN, 0, 1, 2));
+        assert_eq!(fad_get_vec_arrangement(inst.flags), VA_8B);
+        assert_eq!(vec_widen_arrangement(VA_8B), Some(VA_8H));
+        assert_eq!(inst.flags & SIMD_ROUND, 0);
+
+        let inst = decode(0x6e226020); // rsubhn2 v0.16b, v1.8h, v2.8h
+        assert_eq!((inst.op, fad_get_vec_arrangement(inst.flags)), (Op::A64_SUBHN, VA_16B));
+        assert_eq!(vec_widen_arrangement(VA_16B), Some(VA_8H));
+        assert_eq!(inst.flags & SIMD_ROUND, SIMD_ROUND);
+
+        let inst = decode(0x2ea24020); // raddhn v0.2s, v1.2d, v2.2d
+        assert_eq!((inst.op, inst.flags & SIMD_ROUND), (Op::A64_ADDHN, SIMD_ROUND));
+        assert_eq!(fad_get_vec_arrangement(inst.flags), VA_2S);
+        assert_eq!(decode(0x0ee24020).op, Op::A64_UNKNOWN); // addhn with size 11
+    }
+
+    #[test]
+    fn halving_add_sub() {
+        use VectorArrangement::*;
+
+        let inst = decode(0x6e221420); // urhadd v0.16b, v1.16b, v2.16b
+        assert_eq!((inst.op, inst.rd, inst.rn, inst.rm), (Op::A64_HADD, 0, 1, 2));
+        assert_eq!(fad_get_vec_arrangement(inst.flags), VA_16B);
+        assert_eq!(inst.flags & (SIMD_SIGNED | SIMD_ROUND), SIMD_ROUND);
+
+        let inst = decode(0x4e622420); // shsub v0.8h, v1.8h, v2.8h
+        assert_eq!(inst.op, Op::A64_HSUB);
+        assert_eq!(fad_get_vec_arrangement(inst.flags), VA_8H);
+        assert_eq!(inst.flags & (SIMD_SIGNED | SIMD_ROUND), SIMD_SIGNED);
+
+        let inst = decode(0x0ea50483); // shadd v3.2s, v4.2s, v5.2s
+        assert_eq!((inst.op, inst.flags & (SIMD_SIGNED | SIMD_ROUND)), (Op::A64_HADD, SIMD_SIGNED));
+        assert_eq!(fad_get_vec_arrangement(inst.flags), VA_2S);
+        assert_eq!((decode(0x2e622420).op, decode(0x2e622420).flags & SIMD_SIGNED), (Op::A64_HSUB, 0)); // uhsub v0.4h
+        assert_eq!(decode(0x4ee20420).op, Op::A64_UNKNOWN); // shadd with 2D
+    }
+
+    #[test]
+    fn branches() {
+        let inst = decode(0x97fffffe); // bl #-8
+        assert_eq!((inst.op, inst.offset), (Op::A64_BL, -8));
+        assert_eq!((decode(0x14000040).op, decode(0x14000040).offset), (Op::A64_B, 256));
+
+        let inst = decode(0x54000061); // b.ne #12
+        assert_eq!((inst.op, fad_get_cond(inst.flags), inst.offset), (Op::A64_BCOND, 1, 12));
+
+        let inst = decode(0x34ffffe3); // cbz w3, #-4
+        assert_eq!((inst.op, inst.rd, inst.offset), (Op::A64_CBZ, 3, -4));
+        assert_eq!(inst.flags & W32, W32);
+
+        let inst = decode(0xb7400045); // tbnz x5, #40, #8
+        assert_eq!((inst.op, inst.rd, inst.tbz.bit, inst.tbz.offset), (Op::A64_TBNZ, 5, 40, 8));
+        assert_eq!(inst.flags & W32, 0);
+
+        assert_eq!((decode(0xd63f0100).op, decode(0xd63f0100).rn), (Op::A64_BLR, 8)); // blr x8
+        assert_eq!((decode(0xd61f0200).op, decode(0xd61f0200).rn), (Op::A64_BR, 16)); // br x16
+        assert_eq!((decode(0xd65f03c0).op, decode(0xd65f03c0).rn), (Op::A64_RET, 30)); // ret
+        assert_eq!(decode(0xd65f0bff).op, Op::A64_UNKNOWN); // retaa
+    }
+
+    #[test]
+    fn multiply_by_element() {
+        use VectorArrangement::*;
+
+        let inst = decode(0x6fa20820); // mla v0.4s, v1.4s, v2.s[3]
+        assert_eq!((inst.op, inst.rd, inst.rn, inst.rm, inst.imm), (Op::A64_MLA_ELEM, 0, 1, 2, 3));
+        assert_eq!(fad_get_vec_arrangement(inst.flags), VA_4S);
+
+        let inst = decode(0x6f720820); // mla v0.8h, v1.8h, v2.h[7]
+        assert_eq!((inst.op, inst.rm, inst.imm), (Op::A64_MLA_ELEM, 2, 7));
+        assert_eq!(fad_get_vec_arrangement(inst.flags), VA_8H);
+
+        let inst = decode(0x0f5f8883); // mul v3.4h, v4.4h, v15.h[5]
+        assert_eq!((inst.op, inst.rm, inst.imm), (Op::A64_MUL_ELEM, 15, 5));
+        let inst = decode(0x2fbf4020); // mls v0.2s, v1.2s, v31.s[1]
+        assert_eq!((inst.op, inst.rm, inst.imm), (Op::A64_MLS_ELEM, 31, 1));
+
+        let inst = decode(0x4f622820); // smlal2 v0.4s, v1.8h, v2.h[6]
+        assert_eq!((inst.op, inst.imm, inst.flags & SIMD_SIGNED), (Op::A64_MLAL_ELEM, 6, SIMD_SIGNED));
+        assert_eq!(fad_get_vec_arrangement(inst.flags), VA_8H);
+        let inst = decode(0x2f916820); // umlsl v0.2d, v1.2s, v17.s[2]
+        assert_eq!((inst.op, inst.rm, inst.imm, inst.flags & SIMD_SIGNED), (Op::A64_MLSL_ELEM, 17, 2, 0));
+
+        assert_eq!(decode(0x6f220820).op, Op::A64_UNKNOWN); // mla with 8-bit elements
+    }
+
+    #[test]
+    fn long_multiply_by_element() {
+        use VectorArrangement::*;
+
+        let inst = decode(0x0f72a020); // smull v0.4s, v1.4h, v2.h[3]
+        assert_eq!((inst.rd, inst.rn, inst.rm, inst.imm, inst.op), (0, 1, 2, 3, Op::A64_MULL_ELEM));
+        assert_eq!(inst.flags & SIMD_SIGNED, SIMD_SIGNED);
+        let va = fad_get_vec_arrangement(inst.flags);
+        assert_eq!((va, vec_widen_arrangement(va), vec_is_upper_half(va)), (VA_4H, Some(VA_4S), false));
+
+        let inst = decode(0x4f72a020); // smull2 v0.4s, v1.8h, v2.h[3]
+        assert_eq!((inst.imm, inst.op), (3, Op::A64_MULL_ELEM));
+        let va = fad_get_vec_arrangement(inst.flags);
+        assert_eq!((va, vec_widen_arrangement(va), vec_is_upper_half(va)), (VA_8H, Some(VA_4S), true));
+
+        let inst = decode(0x2fa2a820); // umull v0.2d, v1.2s, v2.s[3]
+        assert_eq!((inst.flags & SIMD_SIGNED, inst.imm, inst.op), (0, 3, Op::A64_MULL_ELEM));
+        assert_eq!(fad_get_vec_arrangement(inst.flags), VA_2S);
+
+        let inst = decode(0x0f72b020); // sqdmull v0.4s, v1.4h, v2.h[3]
+        assert_eq!((fad_get_vec_arrangement(inst.flags), inst.op), (VA_4H, Op::A64_SQDMULL_ELEM));
+        let inst = decode(0x4fa23020); // sqdmlal2 v0.2d, v1.4s, v2.s[1]
+        assert_eq!((fad_get_vec_arrangement(inst.flags), inst.imm, inst.op), (VA_4S, 1, Op::A64_SQDMLAL_ELEM));
+        let inst = decode(0x0f7f7820); // sqdmlsl v0.4s, v1.4h, v15.h[7]
+        assert_eq!((inst.rm, inst.imm, inst.op), (15, 7, Op::A64_SQDMLSL_ELEM));
+        assert_eq!(decode(0x2f72b020).op, Op::A64_UNKNOWN); // sqdmull encoding with U set
+    }
+
+    #[test]
+    fn simd_immediate_constants() {
+        use VectorArrangement::*;
+
+        let inst = decode(0x6f05e4a0); // movi v0.2d, #0xff00ff0000ff00ff
+        assert_eq!(simd_imm_u128(&inst), 0xff00ff0000ff00ff_ff00ff0000ff00ff);
+        assert_eq!(fad_get_vec_arrangement(inst.flags), VA_2D);
+        assert_eq!((inst.op, inst.rd, inst.imm), (Op::A64_MOVI, 0, 0xff00ff0000ff00ff));
+
+        let inst = decode(0x2f04e421); // movi d1, #0xff000000000000ff
+        assert_eq!((fad_get_vec_arrangement(inst.flags), inst.imm), (VA_1D, 0xff000000000000ff));
+        assert_eq!(simd_imm_u128(&inst), 0xff000000000000ff);
+
+        let inst = decode(0x4f004642); // movi v2.4s, #0x12, lsl #16
+        assert_eq!((fad_get_vec_arrangement(inst.flags), inst.imm), (VA_4S, 0x120000));
+        assert_eq!(simd_imm_u128(&inst), 0x00120000_00120000_00120000_00120000);
+        let inst = decode(0x6f01a683); // mvni v3.8h, #0x34, lsl #8
+        assert_eq!((fad_get_vec_arrangement(inst.flags), inst.imm), (VA_8H, 0xcbff));
+        let inst = decode(0x0f02c6c5); // movi v5.2s, #0x56, msl #8
+        assert_eq!(simd_imm_u128(&inst), 0x000056ff_000056ff);
+        assert_eq!(decode(0x6f03d706).imm, 0xff870000); // mvni v6.4s, #0x78, msl #16
+        assert_eq!(simd_imm_u128(&decode(0x4f05e564)), u128::from_le_bytes([0xab; 16])); // movi v4.16b, #0xab
+
+        assert_eq!(decode(0x4f001420).op, Op::A64_UNKNOWN); // orr v0.4s, #1
+        assert_eq!(simd_imm_u128(&decode(0x91004020)), 0); // add x0, x1, #16
+    }
+
+    #[test]
+    fn dup_element_and_general() {
+        use VectorArrangement::*;
+
+        let inst = decode(0x4e040c20); // dup v0.4s, w1
+        assert_eq!((inst.rd, inst.rn, fad_get_vec_arrangement(inst.flags), inst.flags & W32, inst.op), (0, 1, VA_4S, W32, Op::A64_DUP_GPR));
+        let inst = decode(0x4e080c20); // dup v0.2d, x1
+        assert_eq!((fad_get_vec_arrangement(inst.flags), inst.flags & W32, inst.op), (VA_2D, 0, Op::A64_DUP_GPR));
+        let inst = decode(0x0e010c62); // dup v2.8b, w3
+        assert_eq!((inst.rd, inst.rn, fad_get_vec_arrangement(inst.flags), inst.op), (2, 3, VA_8B, Op::A64_DUP_GPR));
+        let inst = decode(0x0e1e0c20); // dup v0.4h, w1
+        assert_eq!(fad_get_vec_arrangement(inst.flags), VA_4H);
+
+        let inst = decode(0x4e140420); // dup v0.4s, v1.s[2]
+        assert_eq!((inst.imm, fad_get_vec_arrangement(inst.flags), inst.op), (2, VA_4S, Op::A64_DUP_ELEM));
+        let inst = decode(0x4e1f0420); // dup v0.16b, v1.b[15]
+        assert_eq!((inst.imm, fad_get_vec_arrangement(inst.flags)), (15, VA_16B));
+
+        assert_eq!(decode(0x0e080c20).op, Op::A64_UNKNOWN); // dup v0.1d, x1
+        assert_eq!(decode(0x4e000c20).op, Op::A64_UNKNOWN); // imm5 = 00000
+    }
+
+    #[test]
+    fn move_element_to_general() {
+        use VectorArrangement::*;
+
+        let inst = decode(0x0e0a2c20); // smov w0, v1.h[2]
+        assert_eq!((inst.rd, inst.rn, inst.imm, inst.flags & W32, inst.op), (0, 1, 2, W32, Op::A64_SMOV));
+        assert_eq!(fad_get_vec_arrangement(inst.flags), VA_8H);
+        let inst = decode(0x4e012c20); // smov x0, v1.b[0]
+        assert_eq!((inst.imm, inst.flags & W32, inst.op), (0, 0, Op::A64_SMOV));
+        let inst = decode(0x4e183c20); // umov x0, v1.d[1] (mov)
+        assert_eq!((inst.imm, inst.flags & W32, fad_get_vec_arrangement(inst.flags), inst.op), (1, 0, VA_2D, Op::A64_UMOV));
+        let inst = decode(0x0e1c3c20); // umov w0, v1.s[3] (mov)
+        assert_eq!((inst.imm, inst.flags & W32, inst.op), (3, W32, Op::A64_UMOV));
+
+        assert_eq!(decode(0x0e042c20).op, Op::A64_UNKNOWN); // smov w0, v1.s[0]
+        assert_eq!(decode(0x4e042c20).op, Op::A64_SMOV); // smov x0, v1.s[0]
+        assert_eq!(decode(0x4e043c20).op, Op::A64_UNKNOWN); // umov x0, v1.s[0]
+        assert_eq!(decode(0x0e083c20).op, Op::A64_UNKNOWN); // umov w0, v1.d[0]
+    }
+
+    #[test]
+    fn insert_element() {
+        use VectorArrangement::*;
+
+        let inst = decode(0x6e0c6420); // ins v0.s[1], v1.s[3]
+        assert_eq!((inst.rd, inst.rn, inst.ins_elem.dst, inst.ins_elem.src, inst.op), (0, 1, 1, 3, Op::A64_INS_ELEM));
+        assert_eq!(vec_elem_bits(fad_get_vec_arrangement(inst.flags)), 32);
+        let inst = decode(0x6e1f0420); // ins v0.b[15], v1.b[0]
+        assert_eq!((inst.ins_elem.dst, inst.ins_elem.src, fad_get_vec_arrangement(inst.flags)), (15, 0, VA_16B));
+        let inst = decode(0x6e187c20); // ins v0.d[1], v1.d[1], imm4<2:0> ignored
+        assert_eq!((inst.ins_elem.dst, inst.ins_elem.src, fad_get_vec_arrangement(inst.flags)), (1, 1, VA_2D));
+        assert_eq!(decode(0x2e0c6420).op, Op::A64_UNKNOWN); // Q = 0
+    }
+
+    #[test]
+    fn shift_by_vector_register() {
+        use VectorArrangement::*;
+
+        let inst = decode(0x6ea24420); // ushl v0.4s, v1.4s, v2.4s
+        assert_eq!((inst.rd, inst.rn, inst.rm, fad_get_vec_arrangement(inst.flags), inst.flags & (SIMD_SIGNED | SIMD_ROUND), inst.op), (0, 1, 2, VA_4S, 0, Op::A64_SHL_REG));
+        let inst = decode(0x4ee25420); // srshl v0.2d, v1.2d, v2.2d
+        assert_eq!((fad_get_vec_arrangement(inst.flags), inst.flags & (SIMD_SIGNED | SIMD_ROUND), inst.op), (VA_2D, SIMD_SIGNED | SIMD_ROUND, Op::A64_SHL_REG));
+        let inst = decode(0x0e224420); // sshl v0.8b, v1.8b, v2.8b
+        assert_eq!((fad_get_vec_arrangement(inst.flags), inst.flags & (SIMD_SIGNED | SIMD_ROUND)), (VA_8B, SIMD_SIGNED));
+        assert_eq!(decode(0x0ee24420).op, Op::A64_UNKNOWN); // sshl v0.1d: only as a scalar
+    }
+
+    #[test]
+    fn saturating_extract_narrow() {
+        use VectorArrangement::*;
+
+        let inst = decode(0x0e214820); // sqxtn v0.8b, v1.8h
+        assert_eq!((inst.rd, inst.rn, fad_get_vec_arrangement(inst.flags), inst.flags & SIMD_SIGNED, inst.op), (0, 1, VA_8B, SIMD_SIGNED, Op::A64_QXTN));
+        let inst = decode(0x6e214820); // uqxtn2 v0.16b, v1.8h
+        assert!(vec_is_upper_half(fad_get_vec_arrangement(inst.flags)));
+        assert_eq!((vec_widen_arrangement(fad_get_vec_arrangement(inst.flags)), inst.flags & SIMD_SIGNED, inst.op), (Some(VA_8H), 0, Op::A64_QXTN));
+        let inst = decode(0x2e212820); // sqxtun v0.8b, v1.8h
+        assert_eq!((fad_get_vec_arrangement(inst.flags), inst.op), (VA_8B, Op::A64_SQXTUN));
+        let inst = decode(0x6ea12820); // sqxtun2 v0.4s, v1.2d
+        assert_eq!((fad_get_vec_arrangement(inst.flags), inst.op), (VA_4S, Op::A64_SQXTUN));
+        assert_eq!(decode(0x0ee14820).op, Op::A64_UNKNOWN); // no 128-bit source elements
+    }
+
+    #[test]
+    fn round_to_integral() {
+        use VectorArrangement::*;
+        let mode = |inst: &Inst| inst.frint.mode;
+
+        let inst = decode(0x1e284020); // frint32z s0, s1
+        assert_eq!((inst.rd, inst.rn, inst.frint.bits, mode(&inst), fad_get_prec(inst.flags)), (0, 1, 32, FPRounding::FPR_ZERO as u32, FPSize::FSZ_S));
+        assert_eq!((required_features(&inst), inst.op), (FeatureSet::FRINTTS, Op::A64_FRINT));
+        let inst = decode(0x1e69c020); // frint64x d0, d1
+        assert_eq!((inst.frint.bits, mode(&inst), fad_get_prec(inst.flags), inst.op), (64, FPRounding::FPR_CURRENT as u32, FPSize::FSZ_D, Op::A64_FRINTX));
+        let inst = decode(0x1e25c020); // frintz s0, s1
+        assert_eq!((inst.frint.bits, mode(&inst), required_features(&inst), inst.op), (0, FPRounding::FPR_ZERO as u32, FeatureSet::NONE, Op::A64_FRINT));
+        let inst = decode(0x1ee44020); // frintn h0, h1
+        assert_eq!((mode(&inst), required_features(&inst)), (FPRounding::FPR_TIE_EVEN as u32, FeatureSet::FP16));
+        assert_eq!(mode(&decode(0x1e664062)), FPRounding::FPR_TIE_AWAY as u32); // frinta d2, d3
+        assert_eq!(decode(0x1ee84020).op, Op::A64_UNKNOWN); // frint32z h0, h1
+        assert_eq!(decode_with_features(0x1e284020, FeatureSet::NONE).error.to_string(), "requires FEAT_FRINTTS");
+
+        let inst = decode(0x4e21e820); // frint32z v0.4s, v1.4s
+        assert_eq!((inst.frint.bits, mode(&inst), fad_get_vec_arrangement(inst.flags), inst.op), (32, FPRounding::FPR_ZERO as u32, VA_4S, Op::A64_FRINT_VEC));
+        let inst = decode(0x6e21e820); // frint32x v0.4s, v1.4s
+        assert_eq!((inst.frint.bits, inst.op), (32, Op::A64_FRINTX_VEC));
+        let inst = decode(0x4e61f820); // frint64z v0.2d, v1.2d
+        assert_eq!((inst.frint.bits, fad_get_vec_arrangement(inst.flags)), (64, VA_2D));
+        let inst = decode(0x0ea18820); // frintp v0.2s, v1.2s
+        assert_eq!((inst.frint.bits, mode(&inst), fad_get_vec_arrangement(inst.flags)), (0, FPRounding::FPR_POS_INF as u32, VA_2S));
+        assert_eq!(mode(&decode(0x6ee19820)), FPRounding::FPR_CURRENT as u32); // frinti v0.2d, v1.2d
+        assert_eq!(decode(0x0e61f820).op, Op::A64_UNKNOWN); // frint64z v0.1d
+    }
+
+    #[test]
+    fn saturating_shift_left() {
+        use VectorArrangement::*;
+        let signedness = |inst: &Inst| inst.flags & (SIMD_SIGNED | SIMD_ROUND);
+
+        let inst = decode(0x4f237420); // sqshl v0.4s, v1.4s, #3
+        assert_eq!((inst.rd, inst.rn, inst.imm, fad_get_vec_arrangement(inst.flags), signedness(&inst), inst.op), (0, 1, 3, VA_4S, SIMD_SIGNED, Op::A64_QSHL_IMM));
+        let inst = decode(0x6f7f7420); // uqshl v0.2d, v1.2d, #63
+        assert_eq!((inst.imm, fad_get_vec_arrangement(inst.flags), signedness(&inst), inst.op), (63, VA_2D, 0, Op::A64_QSHL_IMM));
+        let inst = decode(0x6f226420); // sqshlu v0.4s, v1.4s, #2
+        assert_eq!((inst.imm, fad_get_vec_arrangement(inst.flags), signedness(&inst), inst.op), (2, VA_4S, 0, Op::A64_SQSHLU));
+        assert_eq!(decode(0x2f4f6420).op, Op::A64_UNKNOWN); // sqshlu v0.1d: only as a scalar
+
+        let inst = decode(0x6ea24c20); // uqshl v0.4s, v1.4s, v2.4s
+        assert_eq!((inst.rm, fad_get_vec_arrangement(inst.flags), signedness(&inst), inst.op), (2, VA_4S, 0, Op::A64_QSHL_REG));
+        let inst = decode(0x0e625c20); // sqrshl v0.4h, v1.4h, v2.4h
+        assert_eq!((fad_get_vec_arrangement(inst.flags), signedness(&inst), inst.op), (VA_4H, SIMD_SIGNED | SIMD_ROUND, Op::A64_QSHL_REG));
+    }
+
+    #[test]
+    fn unsigned_reciprocal_estimates() {
+        use VectorArrangement::*;
+        let inst = decode(0x4ea1c820); // urecpe v0.4s, v1.4s
+        assert_eq!((inst.rd, inst.rn, fad_get_vec_arrangement(inst.flags), inst.op), (0, 1, VA_4S, Op::A64_URECPE));
+        let inst = decode(0x2ea1c820); // ursqrte v0.2s, v1.2s
+        assert_eq!((inst.rd, inst.rn, fad_get_vec_arrangement(inst.flags), inst.op), (0, 1, VA_2S, Op::A64_URSQRTE));
+        assert_eq!(decode(0x4ee1c820).op, Op::A64_ERROR); // urecpe v0.2d: sz = 1
+        assert_eq!(decode(0x6ee1c820).op, Op::A64_ERROR); // ursqrte v0.2d
+        assert_eq!(decode(0x4e61c820).op, Op::A64_UNKNOWN); // fcvtas v0.2d, v1.2d, not decoded yet
+    }
+
+    #[test]
+    fn aes_crypto() {
+        let inst = decode(0x4e284820); // aese v0.16b, v1.16b
+        assert_eq!((inst.rd, inst.rn, fad_get_vec_arrangement(inst.flags), required_features(&inst), inst.op), (0, 1, VectorArrangement::VA_16B, FeatureSet::AES, Op::A64_AESE));
+        assert_eq!(decode(0x4e285862).op, Op::A64_AESD); // aesd v2.16b, v3.16b
+        assert_eq!(decode(0x4e2868a4).op, Op::A64_AESMC); // aesmc v4.16b, v5.16b
+        assert_eq!(decode(0x4e2878e6).op, Op::A64_AESIMC); // aesimc v6.16b, v7.16b
+        assert_eq!(decode_with_features(0x4e284820, FeatureSet::SHA).error.to_string(), "requires FEAT_AES");
+
+        assert_eq!(decode(0x4e684820).op, Op::A64_UNKNOWN); // aese with size 01
+        assert_eq!(decode(0x4e280820).op, Op::A64_UNKNOWN); // opcode 00000
+    }
+
+    #[test]
+    fn sha_crypto() {
+        let inst = decode(0x5e024020); // sha256h q0, q1, v2.4s
+        assert_eq!((inst.rd, inst.rn, inst.rm, required_features(&inst), inst.op), (0, 1, 2, FeatureSet::SHA, Op::A64_SHA256H));
+        let inst = decode(0x5e050083); // sha1c q3, s4, v5.4s
+        assert_eq!((inst.rd, inst.rn, inst.rm, inst.op), (3, 4, 5, Op::A64_SHA1C));
+        let inst = decode(0x5e2808e6); // sha1h s6, s7
+        assert_eq!((inst.rd, inst.rn, inst.op), (6, 7, Op::A64_SHA1H));
+        assert_eq!(decode(0x5e282928).op, Op::A64_SHA256SU0); // sha256su0 v8.4s, v9.4s
+        assert_eq!(decode(0x5e026020).op, Op::A64_SHA256SU1); // sha256su1 v0.4s, v1.4s, v2.4s
+        assert_eq!(decode(0x5e281820).op, Op::A64_SHA1SU1); // sha1su1 v0.4s, v1.4s
+
+        assert_eq!(decode(0x5e027020).op, Op::A64_UNKNOWN); // three-register opcode 111
+        assert_eq!(decode(0x5e424020).op, Op::A64_UNKNOWN); // sha256h with size 01
+    }
+
+    #[test]
+    fn vector_fmov_immediate() {
+        use VectorArrangement::*;
+
+        let inst = decode(0x4f03f600); // fmov v0.4s, #1.0
+        assert_eq!(simd_imm_u128(&inst), 0x3f800000_3f800000_3f800000_3f800000);
+        assert_eq!((inst.rd, inst.fimm, fad_get_vec_arrangement(inst.flags), inst.op), (0, 1.0, VA_4S, Op::A64_FMOV_VEC));
+        let inst = decode(0x6f07f401); // fmov v1.2d, #-0.5
+        assert_eq!((inst.fimm, fad_get_vec_arrangement(inst.flags)), (-0.5, VA_2D));
+        let inst = decode(0x0f01ffe2); // fmov v2.4h, #31.0
+        assert_eq!((inst.fimm, fad_get_vec_arrangement(inst.flags), required_features(&inst)), (31.0, VA_4H, FeatureSet::FP16));
+        assert_eq!(simd_imm_u128(&inst), 0x4fc0_4fc0_4fc0_4fc0);
+        let inst = decode(0x0f02f403); // fmov v3.2s, #0.125
+        assert_eq!((inst.fimm, fad_get_vec_arrangement(inst.flags)), (0.125, VA_2S));
+
+        assert_eq!(decode(0x2f07f401).op, Op::A64_UNKNOWN); // fmov with op set and Q clear (1D)
+        assert_eq!(decode(0x1e2e1000).op, Op::A64_FMOV_IMM); // fmov s0, #1.0
+    }
+
+    #[test]
+    fn add_long_pairwise() {
+        use VectorArrangement::*;
+
+        let inst = decode(0x6e202820); // uaddlp v0.8h, v1.16b
+        assert_eq!((inst.op, inst.rd, inst.rn), (Op::A64_ADDLP, 0, 1));
+        assert_eq!(fad_get_vec_arrangement(inst.flags), VA_16B);
+        assert_eq!(vec_widen_arrangement(VA_16B), Some(VA_8H));
+        assert_eq!(inst.flags & SIMD_SIGNED, 0);
+
+        let inst = decode(0x4e606820); // sadalp v0.4s, v1.8h
+        assert_eq!((inst.op, inst.flags & SIMD_SIGNED), (Op::A64_ADALP, SIMD_SIGNED));
+        assert_eq!(fad_get_vec_arrangement(inst.flags), VA_8H);
+        assert_eq!(vec_widen_arrangement(VA_8H), Some(VA_4S));
+
+        let inst = decode(0x6ea06883); // uadalp v3.2d, v4.4s
+        assert_eq!((inst.op, fad_get_vec_arrangement(inst.flags)), (Op::A64_ADALP, VA_4S));
+        assert_eq!(decode(0x0ea02820).op, Op::A64_ADDLP); // saddlp v0.1d, v1.2s
+        assert_eq!(decode(0x4ee02820).op, Op::A64_UNKNOWN); // saddlp with size 11
+    }
+
+    #[test]
+    fn fp_immediates() {
+        for (imm8, value) in [(0x70, 1.0), (0x00, 2.0), (0x0f, 3.875), (0x7f, 1.9375), (0x40, 0.125), (0x80, -2.0), (0x3f, 31.0)] {
+            assert_eq!(vfp_expand_imm(imm8), value, "{:#04x}", imm8);
+        }
+
+        let inst = decode(0x1e2e1000); // fmov s0, #1.0
+        assert_eq!((inst.rd, inst.fimm, fad_get_prec(inst.flags), inst.op), (0, 1.0, FPSize::FSZ_S, Op::A64_FMOV_IMM));
+        let inst = decode(0x1e7f9001); // fmov d1, #-1.75
+        assert_eq!((inst.rd, inst.fimm, fad_get_prec(inst.flags)), (1, -1.75, FPSize::FSZ_D));
+        let inst = decode(0x1ee91002); // fmov h2, #0.1875
+        assert_eq!((inst.fimm, fad_get_prec(inst.flags)), (0.1875, FPSize::FSZ_H));
+        assert_eq!(decode(0x1e2e1020).op, Op::A64_UNKNOWN); // fmov s0, #1.0 with imm5 != 0
+    }
+
+    #[test]
+    fn unsigned_offset_loads_and_stores() {
+        let inst = decode(0xf9400801); // ldr x1, [x0, #16]
+        assert_eq!((inst.rd, inst.rn, inst.offset, inst.op), (1, 0, 16, Op::A64_LDR));
+        assert_eq!((fad_get_addrmode(inst.flags), fad_get_mem_extend(inst.flags), inst.flags & W32), (AddrMode::AM_OFF_IMM, ExtendType::UXTX, 0));
+        let inst = decode(0x39ffffe2); // ldrsb w2, [sp, #4095]
+        assert_eq!((inst.rn, inst.offset, fad_get_mem_extend(inst.flags), inst.flags & W32), (STACK_POINTER, 4095, ExtendType::SXTB, W32));
+        let inst = decode(0xb9800883); // ldrsw x3, [x4, #8]
+        assert_eq!((inst.offset, fad_get_mem_extend(inst.flags), inst.flags & W32), (8, ExtendType::SXTW, 0));
+        let inst = decode(0x790004c5); // strh w5, [x6, #2]
+        assert_eq!((inst.offset, fad_get_mem_extend(inst.flags), inst.op), (2, ExtendType::UXTH, Op::A64_STR));
+
+        let inst = decode(0x3dc00820); // ldr q0, [x1, #32]
+        assert_eq!((inst.offset, fad_get_mem_extend(inst.flags), inst.op), (32, FPSize::FSZ_Q, Op::A64_LDR_FP));
+        let inst = decode(0xfd47fca4); // ldr d4, [x5, #4088]
+        assert_eq!((inst.offset, fad_get_mem_extend(inst.flags), inst.op), (4088, FPSize::FSZ_D, Op::A64_LDR_FP));
+        let inst = decode(0x3d000462); // str b2, [x3, #1]
+        assert_eq!((inst.offset, fad_get_mem_extend(inst.flags), inst.op), (1, FPSize::FSZ_B, Op::A64_STR_FP));
+        let inst = decode(0xf9800400); // prfm pldl1keep, [x0, #8]
+        assert_eq!((inst.rd, inst.offset, inst.op), (0, 8, Op::A64_PRFM));
+        assert_eq!(decode(0xb9c00883).op, Op::A64_UNKNOWN); // ldrsw w3 does not exist
+    }
+
+    #[test]
+    fn bfloat16() {
+        use VectorArrangement::*;
+
+        let inst = decode(0x2e42fc20); // bfdot v0.2s, v1.4h, v2.4h
+        assert_eq!((inst.rd, inst.rn, inst.rm, inst.op), (0, 1, 2, Op::A64_BFDOT_VEC));
+        assert_eq!(fad_get_vec_arrangement(inst.flags), VA_2S);
+        assert_eq!(fad_get_vec_arrangement(decode(0x6e42fc20).flags), VA_4S); // bfdot v0.4s, v1.8h, v2.8h
+        let inst = decode(0x4f62f820); // bfdot v0.4s, v1.8h, v2.2h[3]
+        assert_eq!((inst.rm, inst.imm, inst.op), (2, 3, Op::A64_BFDOT_ELEM));
+        assert_eq!(fad_get_vec_arrangement(inst.flags), VA_4S);
+
+        assert_eq!(decode(0x6e42ec20).op, Op::A64_BFMMLA); // bfmmla v0.4s, v1.8h, v2.8h
+        assert_eq!(decode(0x2ec2fc20).op, Op::A64_BFMLALB_VEC); // bfmlalb v0.4s, v1.8h, v2.8h
+        assert_eq!(decode(0x6ec2fc20).op, Op::A64_BFMLALT_VEC); // bfmlalt v0.4s, v1.8h, v2.8h
+        let inst = decode(0x0ff2f820); // bfmlalb v0.4s, v1.8h, v2.h[7]
+        assert_eq!((inst.rm, inst.imm, inst.op), (2, 7, Op::A64_BFMLALB_ELEM));
+        let inst = decode(0x4feff020); // bfmlalt v0.4s, v1.8h, v15.h[2]
+        assert_eq!((inst.rm, inst.imm, inst.op), (15, 2, Op::A64_BFMLALT_ELEM));
+
+        let inst = decode(0x1e634020); // bfcvt h0, s1
+        assert_eq!((inst.rd, inst.rn, inst.op), (0, 1, Op::A64_BFCVT));
+        let inst = decode(0x4ea16820); // bfcvtn2 v0.8h, v1.4s
+        assert_eq!((fad_get_vec_arrangement(inst.flags), inst.op), (VA_8H, Op::A64_BFCVTN));
+
+        assert_eq!(required_features(&decode(0x2e42fc20)), FeatureSet::BF16);
+        let inst = decode_with_features(0x2e42fc20, FeatureSet::FP16);
+        assert_eq!((inst.op, inst.error.to_string()), (Op::A64_ERROR, "requires FEAT_BF16".to_string()));
+    }
+
+    #[test]
+    fn integer_max_min() {
+        use VectorArrangement::*;
+
+        let inst = decode(0x4ea26420); // smax v0.4s, v1.4s, v2.4s
+        assert_eq!((inst.rd, inst.rn, inst.rm, inst.op), (0, 1, 2, Op::A64_MAX_VEC));
+        assert_eq!((fad_get_vec_arrangement(inst.flags), inst.flags & (SIMD_SIGNED | SIMD_SCALAR)), (VA_4S, SIMD_SIGNED));
+        let inst = decode(0x2e226c20); // umin v0.8b, v1.8b, v2.8b
+        assert_eq!((fad_get_vec_arrangement(inst.flags), inst.flags & SIMD_SIGNED, inst.op), (VA_8B, 0, Op::A64_MIN_VEC));
+
+        let inst = decode(0x0e62a420); // smaxp v0.4h, v1.4h, v2.4h
+        assert_eq!((fad_get_vec_arrangement(inst.flags), inst.flags & (SIMD_SIGNED | SIMD_SCALAR), inst.op), (VA_4H, SIMD_SIGNED, Op::A64_MAXP));
+        let inst = decode(0x6e22ac20); // uminp v0.16b, v1.16b, v2.16b
+        assert_eq!((fad_get_vec_arrangement(inst.flags), inst.flags & (SIMD_SIGNED | SIMD_SCALAR), inst.op), (VA_16B, 0, Op::A64_MINP));
+
+        let inst = decode(0x6eb0a820); // umaxv s0, v1.4s
+        assert_eq!((inst.rd, inst.rn, inst.op), (0, 1, Op::A64_MAXV));
+        assert_eq!((fad_get_vec_arrangement(inst.flags), inst.flags & (SIMD_SIGNED | SIMD_SCALAR)), (VA_4S, SIMD_SCALAR));
+        let inst = decode(0x4e31a883); // sminv b3, v4.16b
+        assert_eq!((fad_get_vec_arrangement(inst.flags), inst.flags & (SIMD_SIGNED | SIMD_SCALAR), inst.op), (VA_16B, SIMD_SIGNED | SIMD_SCALAR, Op::A64_MINV));
+        assert_eq!(decode(0x2eb0a820).op, Op::A64_UNKNOWN); // umaxv with 2S
+        assert_eq!(decode(0x4ee26420).op, Op::A64_UNKNOWN); // smax with 64-bit elements
+    }
+
+    #[test]
+    fn vector_logical() {
+        use VectorArrangement::*;
+
+        let inst = decode(0x4ea11c20); // orr v0.16b, v1.16b, v1.16b
+        assert_eq!((inst.rd, inst.rn, inst.rm, fad_get_vec_arrangement(inst.flags), inst.op), (0, 1, 1, VA_16B, Op::A64_MOV_VEC));
+        let inst = decode(0x4e221c20); // and v0.16b, v1.16b, v2.16b
+        assert_eq!((inst.rd, inst.rn, inst.rm, fad_get_vec_arrangement(inst.flags), inst.op), (0, 1, 2, VA_16B, Op::A64_AND_VEC));
+        let inst = decode(0x0ea51c83); // orr v3.8b, v4.8b, v5.8b
+        assert_eq!((fad_get_vec_arrangement(inst.flags), inst.op), (VA_8B, Op::A64_ORR_VEC_REG));
+        assert_eq!(decode(0x4e621c20).op, Op::A64_BIC_VEC_REG); // bic v0.16b, v1.16b, v2.16b
+        assert_eq!(decode(0x0ee21c20).op, Op::A64_ORN_VEC); // orn v0.8b, v1.8b, v2.8b
+        assert_eq!(decode(0x6e221c20).op, Op::A64_EOR_VEC); // eor v0.16b, v1.16b, v2.16b
+
+        let inst = decode(0x6e205841); // not v1.16b, v2.16b
+        assert_eq!((inst.rd, inst.rn, fad_get_vec_arrangement(inst.flags), inst.op), (1, 2, VA_16B, Op::A64_NOT_VEC));
+        assert_eq!(decode(0x2e605841).op, Op::A64_RBIT_VEC); // rbit v1.8b, v2.8b
+    }
+
+    #[test]
+    fn bitwise_select() {
+        use VectorArrangement::*;
+
+        // size selects the operation: 01 BSL, 10 BIT, 11 BIF
+        let inst = decode(0x6e621c20); // bsl v0.16b, v1.16b, v2.16b
+        assert_eq!((inst.rd, inst.rn, inst.rm, fad_get_vec_arrangement(inst.flags), inst.op), (0, 1, 2, VA_16B, Op::A64_BSL));
+        let inst = decode(0x2ea21c20); // bit v0.8b, v1.8b, v2.8b
+        assert_eq!((inst.rd, inst.rn, inst.rm, fad_get_vec_arrangement(inst.flags), inst.op), (0, 1, 2, VA_8B, Op::A64_BIT));
+        let inst = decode(0x6ee51c83); // bif v3.16b, v4.16b, v5.16b
+        assert_eq!((fad_get_vec_arrangement(inst.flags), inst.op), (VA_16B, Op::A64_BIF));
+        assert_eq!(decode(0x2e221c20).op, Op::A64_EOR_VEC); // eor v0.8b, v1.8b, v2.8b
+    }
+
+    #[test]
+    fn fp16_widening_multiply_add() {
+        use VectorArrangement::*;
+
+        let inst = decode(0x4e22ec20); // fmlal v0.4s, v1.4h, v2.4h
+        assert_eq!((fad_get_vec_arrangement(inst.flags), required_features(&inst)), (VA_4S, FeatureSet::FHM));
+        assert_eq!((inst.rd, inst.rn, inst.rm, inst.op), (0, 1, 2, Op::A64_FMLAL_VEC));
+        assert_eq!(decode_with_features(0x4e22ec20, FeatureSet::FP16).error.to_string(), "requires FEAT_FHM");
+        assert_eq!(vec_widen_arrangement(VA_4H), Some(VA_4S));
+
+        // FMLAL2 reads the upper four halves of the 8H sources; llvm writes them as 4H
+        let inst = decode(0x6e22cc20); // fmlal2 v0.4s, v1.4h, v2.4h
+        assert_eq!((fad_get_vec_arrangement(inst.flags), inst.op), (VA_4S, Op::A64_FMLAL2_VEC));
+        let inst = decode(0x2ea2cc20); // fmlsl2 v0.2s, v1.2h, v2.2h
+        assert_eq!((fad_get_vec_arrangement(inst.flags), inst.op), (VA_2S, Op::A64_FMLSL2_VEC));
+        assert_eq!(decode(0x0ea2ec20).op, Op::A64_FMLSL_VEC); // fmlsl v0.2s, v1.2h, v2.2h
+        assert_eq!(decode(0x4e62cc20).op, Op::A64_FMLA_VEC); // fmla v0.2d, v1.2d, v2.2d
+        assert_eq!(decode(0x4e62ec20).op, Op::A64_UNKNOWN); // fmlal encoding with sz 1
+
+        let inst = decode(0x4fb20820); // fmlal v0.4s, v1.4h, v2.h[7]
+        assert_eq!((inst.rm, inst.imm, inst.op), (2, 7, Op::A64_FMLAL_ELEM));
+        assert_eq!(fad_get_vec_arrangement(inst.flags), VA_4S);
+        let inst = decode(0x2fbf8020); // fmlal2 v0.2s, v1.2h, v15.h[3]
+        assert_eq!((inst.rm, inst.imm, inst.op), (15, 3, Op::A64_FMLAL2_ELEM));
+        assert_eq!(fad_get_vec_arrangement(inst.flags), VA_2S);
+        let inst = decode(0x4f924820); // fmlsl v0.4s, v1.4h, v2.h[5]
+        assert_eq!((inst.imm, inst.op), (5, Op::A64_FMLSL_ELEM));
+        let inst = decode(0x6f92c020); // fmlsl2 v0.4s, v1.4h, v2.h[1]
+        assert_eq!((inst.imm, required_features(&inst), inst.op), (1, FeatureSet::FHM, Op::A64_FMLSL2_ELEM));
+        assert_eq!(required_features(&decode(0x4e62cc20)), FeatureSet::NONE); // fmla v0.2d, v1.2d, v2.2d
+        assert_eq!(decode(0x4f520820).op, Op::A64_UNKNOWN); // fmlal encoding with size 01
+
+        // The widening FMLAL by element against the non-widening FMLA
+        let inst = decode(0x4f920820); // fmlal v0.4s, v1.4h, v2.h[5]
+        assert_eq!((inst.rm, inst.imm, inst.op), (2, 5, Op::A64_FMLAL_ELEM));
+        assert_eq!(fad_get_vec_arrangement(inst.flags), vec_widen_arrangement(VA_4H).unwrap());
+        let inst = decode(0x4fa21020); // fmla v0.4s, v1.4s, v2.s[1]
+        assert_eq!((inst.rm, inst.imm, fad_get_vec_arrangement(inst.flags), inst.op), (2, 1, VA_4S, Op::A64_FMLA_ELEM));
+        let inst = decode(0x4f1f1820); // fmla v0.8h, v1.8h, v15.h[5]
+        assert_eq!(required_features(&inst), FeatureSet::FP16);
+        assert_eq!((inst.rm, inst.imm, fad_get_vec_arrangement(inst.flags), inst.op), (15, 5, VA_8H, Op::A64_FMLA_ELEM));
+        let inst = decode(0x4fc25820); // fmls v0.2d, v1.2d, v2.d[1]
+        assert_eq!((inst.imm, fad_get_vec_arrangement(inst.flags), inst.op), (1, VA_2D, Op::A64_FMLS_ELEM));
+        assert_eq!(decode(0x0fc21020).op, Op::A64_UNKNOWN); // fmla v0.1d
+    }
+
+    #[test]
+    fn fp_precision_conversions() {
+        use VectorArrangement::*;
+
+        let inst = decode(0x0e217820); // fcvtl v0.4s, v1.4h
+        assert_eq!((inst.op, inst.rd, inst.rn), (Op::A64_FCVTL, 0, 1));
+        assert_eq!(fad_get_vec_arrangement(inst.flags), VA_4H);
+        assert_eq!(vec_widen_arrangement(VA_4H), Some(VA_4S));
+
+        let inst = decode(0x0e216820); // fcvtn v0.4h, v1.4s
+        assert_eq!((inst.op, fad_get_vec_arrangement(inst.flags)), (Op::A64_FCVTN, VA_4H));
+        assert_eq!(inst.frint.mode, FPRounding::FPR_CURRENT as u32);
+
+        let inst = decode(0x4e617862); // fcvtl2 v2.2d, v3.4s
+        assert_eq!((inst.op, fad_get_vec_arrangement(inst.flags)), (Op::A64_FCVTL, VA_4S));
+        let inst = decode(0x4e616820); // fcvtn2 v0.4s, v1.2d
+        assert_eq!((inst.op, fad_get_vec_arrangement(inst.flags)), (Op::A64_FCVTN, VA_4S));
+
+        let inst = decode(0x2e616820); // fcvtxn v0.2s, v1.2d
+        assert_eq!((inst.op, fad_get_vec_arrangement(inst.flags)), (Op::A64_FCVTXN, VA_2S));
+        assert_eq!(inst.frint.mode, FPRounding::FPR_ODD as u32);
+        assert_eq!(decode(0x2e216820).op, Op::A64_UNKNOWN); // fcvtxn with sz = 0
+    }
+
+    #[test]
+    fn reverse_vector_containers() {
+        use VectorArrangement::*;
+
+        let inst = decode(0x4ea00820); // rev64 v0.4s, v1.4s
+        assert_eq!((inst.op, inst.rd, inst.rn), (Op::A64_REV64_VEC, 0, 1));
+        assert_eq!(fad_get_vec_arrangement(inst.flags), VA_4S);
+        let inst = decode(0x6e605820); // rbit v0.16b, v1.16b
+        assert_eq!(inst.op, Op::A64_RBIT_VEC);
+        assert_eq!(fad_get_vec_arrangement(inst.flags), VA_16B);
+        let inst = decode(0x2e600862); // rev32 v2.4h, v3.4h
+        assert_eq!(inst.op, Op::A64_REV32_VEC);
+        assert_eq!(fad_get_vec_arrangement(inst.flags), VA_4H);
+        assert_eq!(decode(0x0e201820).op, Op::A64_REV16_VEC); // rev16 v0.8b, v1.8b
+        assert_eq!(decode(0x2e205820).op, Op::A64_NOT_VEC); // not v0.8b, v1.8b
+
+        for binst in [
+            0x6ee00820, // rev32 v0.2d, v1.2d
+            0x6ea00820, // rev32 v0.4s, v1.4s
+            0x4ee00820, // rev64 v0.2d, v1.2d
+            0x0e601820, // rev16 v0.4h, v1.4h
+            0x6ea05820, // rbit with size 10
+        ] {
+            assert_eq!(decode(binst).op, Op::A64_ERROR, "{:#010x}", binst);
+        }
+    }
+
+    #[test]
+    fn polynomial_multiply() {
+        use VectorArrangement::*;
+
+        let inst = decode(0x6e229c20); // pmul v0.16b, v1.16b, v2.16b
+        assert_eq!(inst.op, Op::A64_PMUL);
+        assert_eq!(fad_get_vec_arrangement(inst.flags), VA_16B);
+        assert_eq!((inst.rd, inst.rn, inst.rm), (0, 1, 2));
+        assert_eq!(decode(0x6e629c20).op, Op::A64_UNKNOWN); // pmul with 8H
+
+        let inst = decode(0x0ee2e020); // pmull v0.1q, v1.1d, v2.1d
+        assert_eq!(inst.op, Op::A64_PMULL);
+        assert_eq!(fad_get_vec_arrangement(inst.flags), VA_1D);
+        assert_eq!(vec_widen_arrangement(VA_1D), None);
+        assert_eq!(fad_get_vec_arrangement(decode(0x4ee2e020).flags), VA_2D); // pmull2 v0.1q, v1.2d, v2.2d
+
+        let inst = decode(0x4e25e083); // pmull2 v3.8h, v4.16b, v5.16b
+        assert_eq!(inst.op, Op::A64_PMULL);
+        assert_eq!(fad_get_vec_arrangement(inst.flags), VA_16B);
+        assert_eq!(vec_widen_arrangement(VA_16B), Some(VA_8H));
+        assert_eq!((inst.rd, inst.rn, inst.rm), (3, 4, 5));
+        assert_eq!(decode(0x0e62e020).op, Op::A64_UNKNOWN); // pmull with 4H sources
+    }
+
+    #[test]
+    fn unsupported_atomic_extensions() {
+        for binst in [
+            0xf83f9020, // st64b x0, [x1]
+            0xf83fd062, // ld64b x2, [x3]
+            0xf824b020, // st64bv x4, x0, [x1]
+            0xf824a020, // st64bv0 x4, x0, [x1]
+        ] {
+            let inst = decode(binst);
+            assert_eq!(inst.op, Op::A64_ERROR, "{:#010x}", binst);
+            assert_eq!(inst.error.to_string(), "LS64/unsupported atomic extension");
+        }
+
+        assert_ne!(decode(0xf8200041).op, Op::A64_ERROR); // ldadd x0, x1, [x2]
+    }
+
+    #[test]
+    fn data_proc_3src() {
+        let inst = decode(0x1b020c20); // madd w0, w1, w2, w3
+        assert_eq!((inst.op, inst.rd, inst.rn, inst.rm, inst.ra), (Op::A64_MADD, 0, 1, 2, 3));
+        assert_eq!(inst.flags & W32, W32);
+
+        let inst = decode(0x9b220c20); // smaddl x0, w1, w2, x3
+        assert_eq!((inst.op, inst.ra, inst.flags & W32), (Op::A64_SMADDL, 3, 0));
+        assert_eq!(decode(0x9b067ca4).op, Op::A64_MUL); // mul x4, x5, x6
+        assert_eq!(decode(0x9ba2fc20).op, Op::A64_UMNEGL); // umnegl x0, w1, w2
+        assert_eq!(decode(0x9bcb7d49).op, Op::A64_UMULH); // umulh x9, x10, x11
+        assert_eq!(decode(0x1b220c20).op, Op::A64_UNKNOWN); // smaddl with sf = 0
+        assert_eq!(decode(0x9b628c20).op, Op::A64_UNKNOWN); // op31 = 011
+    }
+
+    #[test]
+    fn atomic_memory_ops() {
+        let inst = decode(0xb8e00041); // ldaddal w0, w1, [x2]
+        assert_eq!((inst.op, inst.rs, inst.rd, inst.rn), (Op::A64_LDADD, 0, 1, 2));
+        assert_eq!(inst.ldst_order.load, MemOrdering::MO_ACQUIRE as u16);
+        assert_eq!(inst.ldst_order.store, MemOrdering::MO_RELEASE as u16);
+        assert_eq!(fad_get_mem_extend(inst.flags), ExtendType::UXTW);
+        assert_eq!(inst.flags & W32, W32);
+
+        let inst = decode(0xf8a383e4); // swpa x3, x4, [sp]
+        assert_eq!((inst.op, inst.rs, inst.rd, inst.rn), (Op::A64_SWP, 3, 4, STACK_POINTER));
+        assert_eq!(inst.ldst_order.store, MemOrdering::MO_NONE as u16);
+
+        let inst = decode(0x78674128); // ldsmaxlh w7, w8, [x9]
+        assert_eq!((inst.op, fad_get_mem_extend(inst.flags)), (Op::A64_LDSMAX, ExtendType::UXTH));
+        assert_eq!(inst.ldst_order.load, MemOrdering::MO_NONE as u16);
+
+        let inst = decode(0x38bfc0c5); // ldaprb w5, [x6]
+        assert_eq!((inst.op, inst.rd, inst.rn), (Op::A64_LDAPR, 5, 6));
+        assert_eq!(inst.ldst_order.load, MemOrdering::MO_ACQUIRE_PC as u16);
+        assert_eq!(fad_get_mem_extend(inst.flags), ExtendType::UXTB);
+        assert_eq!(decode(0x38bec0c5).op, Op::A64_UNKNOWN); // LDAPRB with Rs != 31
+    }
+
+    #[test]
+    fn flags_from_register() {
+        let inst = decode(0xba018422); // rmif x1, #3, #2
+        assert_eq!((inst.op, inst.rn, inst.rmif.ror, inst.rmif.mask), (Op::A64_RMIF, 1, 3, 2));
+        let inst = decode(0xba1f87cf); // rmif x30, #63, #15
+        assert_eq!((inst.rn, inst.rmif.ror, inst.rmif.mask), (30, 63, 15));
+
+        let inst = decode(0x3a00084d); // setf8 w2
+        assert_eq!((inst.op, inst.rn, inst.flags & W32), (Op::A64_SETF8, 2, W32));
+        assert_eq!((decode(0x3a004bcd).op, decode(0x3a004bcd).rn), (Op::A64_SETF16, 30)); // setf16 w30
+        assert_eq!(decode(0x3a00084c).op, Op::A64_UNKNOWN); // setf8 with mask 1100
+        assert_eq!(decode(0xba018432).op, Op::A64_UNKNOWN); // rmif with o2 = 1
+    }
+
+    #[test]
+    fn compare_and_swap() {
+        let inst = decode(0x48207c82); // casp x0, x1, x2, x3, [x4]
+        assert_eq!((inst.op, inst.rs, inst.rd, inst.rn), (Op::A64_CASP, 0, 2, 4));
+        assert_eq!((inst.flags & W32, fad_get_mem_extend(inst.flags)), (0, ExtendType::UXTX));
+        assert_eq!(inst.ldst_order.load, MemOrdering::MO_NONE as u16);
+
+        let inst = decode(0x0862ffe4); // caspal w2, w3, w4, w5, [sp]
+        assert_eq!((inst.op, inst.rs, inst.rd, inst.rn), (Op::A64_CASP, 2, 4, STACK_POINTER));
+        assert_eq!(inst.flags & W32, W32);
+        assert_eq!(inst.ldst_order.load, MemOrdering::MO_ACQUIRE as u16);
+        assert_eq!(inst.ldst_order.store, MemOrdering::MO_RELEASE as u16);
+
+        let inst = decode(0x48a0fc41); // caslh w0, w1, [x2]
+        assert_eq!((inst.op, inst.rs, inst.rd, inst.rn), (Op::A64_CAS, 0, 1, 2));
+        assert_eq!(fad_get_mem_extend(inst.flags), ExtendType::UXTH);
+        assert_eq!(inst.ldst_order.store, MemOrdering::MO_RELEASE as u16);
+        assert_ne!(decode(0xc85f7c20).op, Op::A64_CAS); // ldxr x0, [x1]
+    }
+
+    #[test]
+    fn reserved_field_errors() {
+        let reserved = |field, value| DecodeError::Reserved { field, value };
+
+        let inst = decode(0x72c00020); // movk w0, #1, lsl #32
+        assert_eq!((inst.op, inst.error), (Op::A64_ERROR, reserved("hw", 2)));
+        assert_eq!(decode(0x13c20c20).error, reserved("N", 1)); // extr w0, w1, w2, #3 with N = 1
+        assert_eq!(decode(0x93820c20).error, reserved("N", 0)); // extr x0, x1, x2, #3 with N = 0
+        assert_eq!(decode(0x13231820).error, reserved("immr", 35)); // sbfm w0, w1, #35, #6
+        assert_eq!(decode(0x1303a020).error, reserved("imms", 40)); // sbfm w0, w1, #3, #40
+        assert_eq!(decode(0x48217c82).error, reserved("Rs", 1)); // casp x1, x2, x2, x3, [x4]
+        assert_eq!(decode(0x48207c83).error, reserved("Rt", 3)); // casp x0, x1, x3, x4, [x4]
+        assert_eq!(format!("{}", decode(0x72c00020)), "error: reserved hw field value 2");
+    }
+
+    #[test]
+    fn ordered_loads_and_stores() {
+        let inst = decode(0x08df7c20); // ldlarb w0, [x1]
+        assert_eq!((inst.op, inst.rd, inst.rn), (Op::A64_LDR, 0, 1));
+        assert_eq!(inst.ldst_order.load, MemOrdering::MO_LO_ACQUIRE as u16);
+        assert_eq!((fad_get_mem_extend(inst.flags), inst.flags & W32), (ExtendType::UXTB, W32));
+        assert_eq!(fad_get_addrmode(inst.flags), AddrMode::AM_SIMPLE);
+
+        let inst = decode(0xc89f7fe2); // stllr x2, [sp]
+        assert_eq!((inst.op, inst.rd, inst.rn), (Op::A64_STR, 2, STACK_POINTER));
+        assert_eq!(inst.ldst_order.store, MemOrdering::MO_LO_RELEASE as u16);
+        assert_eq!((fad_get_mem_extend(inst.flags), inst.flags & W32), (ExtendType::UXTX, 0));
+
+        let inst = decode(0x88dffc83); // ldar w3, [x4]
+        assert_eq!((inst.op, inst.ldst_order.load), (Op::A64_LDR, MemOrdering::MO_ACQUIRE as u16));
+        let inst = decode(0x489ffcc5); // stlrh w5, [x6]
+        assert_eq!((inst.op, inst.ldst_order.store), (Op::A64_STR, MemOrdering::MO_RELEASE as u16));
+        assert_eq!(fad_get_mem_extend(inst.flags), ExtendType::UXTH);
+
+        assert_eq!(required_features(&decode(0x08df7c20)), FeatureSet::LOR);
+        assert_eq!(required_features(&decode(0x88dffc83)), FeatureSet::NONE);
+    }
+
+    #[test]
+    fn ordered_unscaled_loads_and_stores() {
+        let inst = decode(0x19dfd020); // ldapursb w0, [x1, #-3]
+        assert_eq!((inst.op, inst.rd, inst.rn, inst.offset), (Op::A64_LDR, 0, 1, -3));
+        assert_eq!(fad_get_addrmode(inst.flags), AddrMode::AM_OFF_IMM);
+        assert_eq!(fad_get_mem_extend(inst.flags), ExtendType::SXTB);
+        assert_eq!(inst.flags & W32, W32);
+        assert_eq!(inst.ldst_order.load, MemOrdering::MO_ACQUIRE_PC as u16);
+
+        let inst = decode(0x198ff3e2); // ldapursb x2, [sp, #255]
+        assert_eq!((inst.rn, inst.offset, inst.flags & W32), (STACK_POINTER, 255, 0));
+        assert_eq!(fad_get_mem_extend(inst.flags), ExtendType::SXTB);
+
+        let inst = decode(0xd9500083); // ldapur x3, [x4, #-256]
+        assert_eq!((inst.offset, fad_get_mem_extend(inst.flags)), (-256, ExtendType::UXTX));
+        assert_eq!(fad_get_mem_extend(decode(0x99804107).flags), ExtendType::SXTW); // ldapursw x7, [x8, #4]
+
+        let inst = decode(0x590020c5); // stlurh w5, [x6, #2]
+        assert_eq!((inst.op, inst.offset, fad_get_mem_extend(inst.flags)), (Op::A64_STR, 2, ExtendType::UXTH));
+        assert_eq!(inst.ldst_order.store, MemOrdering::MO_RELEASE as u16);
+        assert_eq!(inst.ldst_order.load, MemOrdering::MO_NONE as u16);
+
+        assert_eq!(decode(0x99c04107).op, Op::A64_UNKNOWN); // LDAPURS to Wt of a word
+        assert_eq!(decode(0xd9804107).op, Op::A64_UNKNOWN); // LDAPURS of a doubleword
+    }
+
+    #[test]
+    fn custom_unknown_handler() {
+        // A made-up coprocessor instruction in the unallocated top-level group.
+        fn coprocessor(binst: u32) -> Option<Inst> {
+            if binst >> 16 != 0x0600 {
+                return None;
+            }
+            let mut inst = decode(0xd503201f); // nop
+            inst.imm = (binst & 0xFFFF) as u64;
+            return Some(inst);
+        }
+
+        let mut decoder = Decoder::new();
+        assert_eq!(decoder.decode(0x06001234).op, Op::A64_UNKNOWN);
+
+        decoder.set_unknown_handler(coprocessor);
+        let inst = decoder.decode(0x06001234);
+        assert_eq!((inst.op, inst.imm), (Op::A64_HINT, 0x1234));
+        assert_eq!(decoder.decode(0x07001234), decode(0x07001234));
+        assert_eq!(decoder.decode(0x91004020), decode(0x91004020)); // add x0, x1, #16
+        assert_eq!(decode(0x06001234).op, Op::A64_UNKNOWN);
+    }
+
+    #[test]
+    fn decoder_buffer_addresses() {
+        let decoder = Decoder { features: FeatureSet::NONE, base_pc: 0x8000, ..Decoder::new() };
+        let mut code = MIXED.to_vec();
+        code.extend_from_slice(&0xb8e00041u32.to_le_bytes()); // ldaddal w0, w1, [x2]
+        code.extend_from_slice(&[0x1f, 0x20]);
+
+        let insts: Vec<(u64, Op)> = decoder.decode_buffer(&code).map(|(pc, inst)| (pc, inst.op)).collect();
+        assert_eq!(insts, vec![
+            (0x8000, Op::A64_ADD_IMM),
+            (0x8004, Op::A64_UNKNOWN),
+            (0x8008, Op::A64_MOV_IMM),
+            (0x800c, Op::A64_ERROR), // requires FEAT_LSE
+            (0x8010, Op::A64_ERROR), // truncated
+        ]);
+        assert_eq!(Decoder::default().decode(0xb8e00041), decode(0xb8e00041));
+    }
+
+    #[test]
+    fn data_recovery_spans() {
+        let mut code = MIXED.to_vec();
+        for word in [0x00000000u32, 0xffffffff, 0xdeadbeef, 0x00000001, 0x91004020, 0xd65f03c0] { // 4 data words; add, ret
+            code.extend_from_slice(&word.to_le_bytes());
+        }
+        code.extend_from_slice(&[0x1f, 0x20]);
+
+        let spans = decode_with_data_recovery(&code, 0x1000);
+        let ops = |span: &Span| match span {
+            Span::Code(insts) => insts.iter().map(|(pc, inst)| (*pc, inst.op.clone())).collect(),
+            Span::Data(..) => vec![],
+        };
+        assert_eq!(spans.len(), 4);
+        assert_eq!(ops(&spans[0]), vec![(0x1000, Op::A64_ADD_IMM), (0x1004, Op::A64_UNKNOWN), (0x1008, Op::A64_MOV_IMM)]);
+        assert_eq!(spans[1], Span::Data(0x100c, &code[12..28]));
+        assert_eq!(ops(&spans[2]), vec![(0x101c, Op::A64_ADD_IMM), (0x1020, Op::A64_RET)]);
+        assert_eq!(spans[3], Span::Data(0x1024, &code[36..]));
+
+        let mut decoder = Decoder { base_pc: 0x1000, ..Decoder::new() };
+        decoder.options.data_run = 1;
+        let spans = decoder.decode_with_data_recovery(&code[..12]);
+        assert_eq!(spans[1], Span::Data(0x1004, &MIXED[4..8]));
+        assert_eq!(ops(&spans[2]), vec![(0x1008, Op::A64_MOV_IMM)]);
+        assert!(decode_with_data_recovery(&[], 0).is_empty());
+    }
+
+    #[test]
+    fn feature_gating() {
+        let no_lse = FeatureSet::RCPC | FeatureSet::FP16 | FeatureSet::SHA;
+        let inst = decode_with_features(0xb8e00041, no_lse); // ldaddal w0, w1, [x2]
+        assert_eq!((inst.op, inst.error.to_string()), (Op::A64_ERROR, "requires FEAT_LSE".to_string()));
+        assert_eq!(decode_with_features(0xb8e00041, FeatureSet::ALL).op, Op::A64_LDADD);
+
+        let inst = decode_with_features(0x38bfc0c5, FeatureSet::NONE); // ldaprb w5, [x6]
+        assert_eq!(inst.error.to_string(), "requires FEAT_LRCPC");
+        let inst = decode_with_features(0x590020c5, FeatureSet::NONE); // stlurh w5, [x6, #2]
+        assert_eq!(inst.error.to_string(), "requires FEAT_LRCPC");
+
+        let inst = decode_with_features(0x1ee22820, FeatureSet::LSE); // fadd h0, h1, h2
+        assert_eq!(inst.error.to_string(), "requires FEAT_FP16");
+        let inst = decode_with_features(0x7ec21420, FeatureSet::NONE); // fabd h0, h1, h2
+        assert_eq!(inst.error.to_string(), "requires FEAT_FP16");
+        let inst = decode_with_features(0x4e421420, FeatureSet::NONE); // fadd v0.8h, v1.8h, v2.8h
+        assert_eq!(inst.error.to_string(), "requires FEAT_FP16");
+
+        for binst in [
+            0x1e222820, // fadd s0, s1, s2
+            0x7ea2d420, // fabd s0, s1, s2
+            0x4e22d420, // fadd v0.4s, v1.4s, v2.4s
+            0x91004020, // add x0, x1, #16
+            0xf9400801, // unknown
+        ] {
+            assert_eq!(decode_with_features(binst, FeatureSet::NONE), decode(binst), "{:#010x}", binst);
+        }
+    }
+
+    #[test]
+    fn vec_arrangement_lanes() {
+        use VectorArrangement::*;
+
+        let expected = [
+            (VA_8B, 8, 8), (VA_16B, 16, 8), (VA_4H, 4, 16), (VA_8H, 8, 16),
+            (VA_2S, 2, 32), (VA_4S, 4, 32), (VA_1D, 1, 64), (VA_2D, 2, 64),
+        ];
+        for (va, lanes, bits) in expected {
+            assert_eq!((vec_lane_count(va), vec_elem_bits(va)), (lanes, bits), "arrangement {:#05b}", va);
+        }
+    }
+
+    #[test]
+    fn strict_unallocated_option() {
+        let mut decoder = Decoder::new();
+        for binst in [0x02000000, 0x32800000, 0x00010000] { // unallocated group, movn with opc = 01, reserved group
+            assert!(is_unallocated(binst), "{:#010x}", binst);
+            assert_eq!(decoder.decode(binst).op, Op::A64_UNKNOWN);
+        }
+        decoder.options.strict_unallocated = true;
+        let inst = decoder.decode(0x32800000);
+        assert_eq!((inst.op, inst.error.to_string()), (Op::A64_ERROR, "unallocated encoding".to_string()));
+        assert_eq!(decoder.decode(0x02000000).op, Op::A64_ERROR);
+
+        // allocated but not decoded, or decoded: unchanged
+        assert!(!is_unallocated(0x91810420)); // addg x0, x1, #16, #1
+        assert_eq!(decoder.decode(0x91810420).op, Op::A64_UNKNOWN);
+        assert_eq!(decoder.decode(0xc85f7c20).op, Op::A64_UNKNOWN); // ldxr x0, [x1]
+        assert_eq!(decoder.decode(0x91004020).op, Op::A64_ADD_IMM);
+        assert_eq!(decoder.decode(0x00000000).op, Op::A64_UDF);
+    }
+
+    #[test]
+    fn raw_fields() {
+        let fields = extract_fields(0x91004020, Group::DataProcImm); // add x0, x1, #16
+        assert_eq!((fields["sf"], fields["sh"], fields["imm12"], fields["Rn"], fields["Rd"]), (1, 0, 16, 1, 0));
+        assert_eq!(fields.len(), 7);
+
+        let fields = extract_fields(0x92400c20, Group::DataProcImm); // and x0, x1, #0xf
+        assert_eq!((fields["opc"], fields["N"], fields["immr"], fields["imms"]), (0, 1, 0, 3));
+        let fields = extract_fields(0xf2a00020, Group::DataProcImm); // movk x0, #1, lsl #16
+        assert_eq!((fields["opc"], fields["hw"], fields["imm16"]), (0b11, 1, 1));
+        let fields = extract_fields(0xf9400801, group_of(0xf9400801)); // ldr x1, [x0, #16]
+        assert_eq!((fields["op0"], fields["op2"]), (0b1111, 0b10));
+    }
+
+    #[test]
+    fn explain_unknown_reasons() {
+        assert_eq!(explain_unknown(0x91810420), "DataProcImm / add/subtract (immediate, with tags): ADDG, SUBG not supported");
+        assert_eq!(explain_unknown(0x32800000), "DataProcImm / move wide (immediate): unallocated opc field");
+        assert_eq!(explain_unknown(0x52c00020), "DataProcImm / reserved hw field value 2");
+        assert_eq!(explain_unknown(0xc85f7c20), "LoadStore / load/store exclusive: not supported yet"); // ldxr x0, [x1]
+        assert_eq!(explain_unknown(0x04000000), "group SVE: decoder not yet implemented");
+        assert_eq!(explain_unknown(0x02000000), "group Unallocated: unallocated encoding");
+        assert_eq!(explain_unknown(0x91004020), "DataProcImm: not unknown, decodes to A64_ADD_IMM");
+    }
+
+    #[test]
+    fn explain_unknown_names_the_instruction_class() {
+        assert_eq!(explain_unknown(0xd69f03e0), "BranchExcSys / unconditional branch (register): opc field: only BR, BLR and RET are supported"); // eret
+        assert_eq!(explain_unknown(0xd5033f9f), "BranchExcSys / barriers: not supported yet"); // dsb sy
+        assert_eq!(explain_unknown(0x9a820020), "DataProcReg / conditional select: not supported yet"); // csel x0, x1, x2, eq
+        assert_eq!(explain_unknown(0x9ac20820), "DataProcReg / data-processing (2 source): opcode field: only the shifts by register are supported"); // udiv x0, x1, x2
+        assert_eq!(explain_unknown(0x1a200000), "group DataProcReg: no instruction class, unallocated encoding");
+        assert_eq!(explain_unknown(0xf8400020), "LoadStore / load/store register (unscaled immediate): not supported yet"); // ldur x0, [x1]
+        assert_eq!(explain_unknown(0x4e61c820), "DataProcSimdFp / advanced SIMD two-register miscellaneous: reserved or unsupported U, size, opcode fields"); // fcvtas v0.2d, v1.2d
+        assert_eq!(explain_unknown(0x1e612000), "DataProcSimdFp / floating-point compare: not supported yet"); // fcmp d0, d1
+    }
+}
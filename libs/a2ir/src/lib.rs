@@ -1,5 +1,16 @@
-mod aarch64_reader;
+// The decoder mirrors the naming of the A64 reference manual and the C
+// decoder it was ported from (A64_ADD_IMM, regRd, ...).
+#![allow(non_snake_case, non_camel_case_types, non_upper_case_globals)]
+#![allow(clippy::needless_return, clippy::identity_op, clippy::unnecessary_cast)]
+
+pub mod aarch64_reader;
+pub mod aarch64_analysis;
+pub mod aarch64_display;
+pub mod aarch64_encoder;
+pub mod aarch64_diagnostics;
+#[cfg(feature = "emu")]
+pub mod aarch64_emu;
 
 pub fn convertProgram() {
     // TODO: give it some abstracted form of an executable
-}
\ No newline at end of file
+}
@@ -0,0 +1,34 @@
+//! Decodes every word of corpus.txt and compares its Display against the
+//! reference text. All mismatches are reported at once.
+
+use a2ir::aarch64_reader::decode;
+
+const CORPUS: &str = include_str!("corpus.txt");
+
+#[test]
+fn display_matches_corpus() {
+    let mut failures = Vec::new();
+    let mut count = 0;
+
+    for (number, line) in CORPUS.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut columns = line.split_whitespace();
+        let word = columns.next().unwrap();
+        let binst = u32::from_str_radix(word, 16)
+            .unwrap_or_else(|_| panic!("corpus.txt:{}: bad hex word {:?}", number + 1, word));
+        let expected = columns.collect::<Vec<_>>().join(" ");
+
+        let actual = format!("{}", decode(binst));
+        if actual != expected {
+            failures.push(format!("corpus.txt:{}: {}: expected {:?}, got {:?}", number + 1, word, expected, actual));
+        }
+        count += 1;
+    }
+
+    assert!(count > 0, "corpus.txt has no instructions");
+    assert!(failures.is_empty(), "{} of {} lines differ:\n{}", failures.len(), count, failures.join("\n"));
+}
@@ -0,0 +1,118 @@
+//! Differential test against a reference disassembler: decodes a random
+//! sample of words with both and compares the Display with the reference
+//! text. All divergences are reported at once.
+//!
+//! cargo test -p a2ir --features crosscheck --test crosscheck
+//!
+//! The reference is llvm-mc, run as a process like the corpus_fixtures
+//! example runs the assembler, so that the feature adds no dependency.
+//! AARCH64_MC names the llvm-mc to run, `llvm-mc` on the PATH by default.
+//! The tests fail if it cannot be run: enabling the feature asks for the
+//! comparison.
+//! Only the groups the decoder covers completely are compared so far: data
+//! processing (immediate), except for ADDG and SUBG, and data processing
+//! (3 source), whose long multiplies mix W and X operands.
+#![cfg(feature = "crosscheck")]
+#![allow(clippy::needless_return)]
+
+use std::collections::HashSet;
+use std::env;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use a2ir::aarch64_reader::{decode, Op};
+
+const SAMPLES: usize = 20000;
+
+/// Reports at most this many divergences.
+const SHOWN: usize = 50;
+
+/// Deterministic pseudo-random words with the bits of mask set to value,
+/// except those rejected by skip.
+fn sample_words(count: usize, mask: u32, value: u32, skip: fn(u32) -> bool) -> Vec<u32> {
+    let mut state: u32 = 0x2545f491;
+    let mut words = Vec::with_capacity(count);
+    while words.len() < count {
+        // xorshift32
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        let word = (state & !mask) | value;
+        if !skip(word) {
+            words.push(word);
+        }
+    }
+    return words;
+}
+
+/// Does llvm-mc print another alias than the preferred one of the Arm ARM?
+/// BFM Rd, ZR, #0, #imms satisfies the conditions of BFXIL, llvm-mc prints
+/// it as BFC Rd, #0, #width.
+fn known_alias_difference(word: u32) -> bool {
+    return word & 0x7fbf03e0 == 0x330003e0;
+}
+
+/// Disassembles words with llvm-mc: the text of each word, with the
+/// operands separated by single spaces, or None for invalid encodings.
+/// Err if llvm-mc cannot be run.
+fn reference(words: &[u32]) -> Result<Vec<Option<String>>, String> {
+    let mc = env::var("AARCH64_MC").unwrap_or_else(|_| "llvm-mc".to_string());
+    let mut child = Command::new(&mc)
+        .args(["--disassemble", "-triple=aarch64", "-mattr=+v8.7a"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("{}: {}", mc, e))?;
+
+    let input: String = words.iter().map(|w| w.to_le_bytes().map(|b| format!("{:#04x}", b)).join(",") + "\n").collect();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).map_err(|e| e.to_string())?;
+    let output = child.wait_with_output().map_err(|e| e.to_string())?;
+
+    // <stdin>:LINE:COLUMN: warning: invalid instruction encoding
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let invalid: HashSet<usize> = stderr
+        .lines()
+        .filter(|line| line.contains("invalid instruction encoding"))
+        .filter_map(|line| line.split(':').nth(1)?.parse().ok())
+        .collect();
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut texts = stdout
+        .lines()
+        .map(|line| line.split("//").next().unwrap().trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('.'))
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "));
+    return Ok((1..=words.len()).map(|number| if invalid.contains(&number) { None } else { texts.next() }).collect());
+}
+
+/// Compares the decoder with the reference on words, see the module doc.
+fn crosscheck(words: &[u32]) {
+    let expected = reference(words).unwrap_or_else(|e| panic!("cannot run the reference disassembler: {}", e));
+
+    let mut divergences = Vec::new();
+    for (word, expected) in words.iter().zip(expected) {
+        let inst = decode(*word);
+        let actual = match inst.op {
+            Op::A64_UNKNOWN | Op::A64_ERROR => None,
+            _ => Some(inst.to_string()),
+        };
+        if actual != expected && !known_alias_difference(*word) {
+            divergences.push(format!("{:08x}: reference {:?}, a2ir {:?} ({})", word, expected, actual, inst));
+        }
+    }
+
+    let shown = divergences.iter().take(SHOWN).cloned().collect::<Vec<_>>().join("\n");
+    assert!(divergences.is_empty(), "{} of {} words differ:\n{}", divergences.len(), words.len(), shown);
+}
+
+#[test]
+fn data_proc_imm_matches_reference() {
+    // op0 100x, without add/subtract (immediate, with tags)
+    crosscheck(&sample_words(SAMPLES, 0x1c000000, 0x10000000, |word| (word >> 23) & 0b111111 == 0b100011));
+}
+
+#[test]
+fn data_proc_3src_matches_reference() {
+    crosscheck(&sample_words(SAMPLES, 0x1f000000, 0x1b000000, |_| false));
+}
@@ -1,3 +1,5 @@
+#![allow(clippy::needless_return)]
+
 use std::io::{Cursor, Read, Seek, SeekFrom};
 use std::str::from_utf8;
 
@@ -25,6 +27,7 @@ impl NroSegment {
     }
 }
 
+#[allow(dead_code)]
 pub struct SwitchExecutable {
     program: Vec<u8>,
     text: Vec<u8>,